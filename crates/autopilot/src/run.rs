@@ -78,8 +78,9 @@ impl LivenessChecking for Liveness {
 pub async fn start(args: impl Iterator<Item = String>) {
     let args = Arguments::parse_from(args);
     observe::tracing::initialize(
-        args.shared.logging.log_filter.as_str(),
-        args.shared.logging.log_stderr_threshold,
+        &observe::tracing::Config::new(args.shared.logging.log_filter.as_str())
+            .with_stderr_threshold(args.shared.logging.log_stderr_threshold)
+            .with_format(args.shared.logging.log_format),
     );
     observe::panic_hook::install();
     tracing::info!("running autopilot with validated arguments:\n{}", args);
@@ -367,7 +368,7 @@ pub async fn run(args: Arguments) {
         http_factory.create(),
         chain_id,
     )
-    .map(Arc::new);
+    .map(|api| Arc::new(api.with_version(args.shared.one_inch_api_version)));
 
     let mut price_estimator_factory = PriceEstimatorFactory::new(
         &args.price_estimation,
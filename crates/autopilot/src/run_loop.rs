@@ -179,6 +179,7 @@ impl RunLoop {
                 }
             }
             let competition_table = SolverCompetitionDB {
+                version: SolverCompetitionDB::VERSION,
                 competition_simulation_block,
                 auction: CompetitionAuction {
                     orders: auction
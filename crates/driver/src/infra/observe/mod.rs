@@ -31,7 +31,7 @@ mod metrics;
 /// Setup the observability. The log argument configures the tokio tracing
 /// framework.
 pub fn init(log: &str) {
-    observe::tracing::initialize_reentrant(log);
+    observe::tracing::initialize_reentrant(&observe::tracing::Config::new(log));
     metrics::init();
 }
 
@@ -136,7 +136,7 @@ impl Solver {
             .post(self.config.endpoint.clone())
             .body(body)
             .timeout(timeout.duration().to_std().unwrap());
-        if let Some(id) = observe::request_id::get_task_local_storage() {
+        if let Some(id) = observe::request_id::get_task_local() {
             req = req.header("X-REQUEST-ID", id);
         }
         let res = util::http::send(SOLVER_RESPONSE_MAX_BYTES, req).await;
@@ -538,9 +538,9 @@ impl Setup {
     /// Create the test: set up onchain contracts and pools, start a mock HTTP
     /// server for the solver and start the HTTP server for the driver.
     pub async fn done(self) -> Test {
-        observe::tracing::initialize_reentrant(
+        observe::tracing::initialize_reentrant(&observe::tracing::Config::new(
             "driver=trace,driver::tests::setup::blockchain=debug",
-        );
+        ));
 
         if let Some(name) = self.name.as_ref() {
             tracing::warn!("\n***** [RUNNING TEST CASE] *****\n{name}");
@@ -1,5 +1,6 @@
 use {
-    super::TestNode,
+    super::{local_node::TestNodeApi, TestNode},
+    crate::setup::to_wei,
     ethcontract::H160,
     reqwest::{IntoUrl, Url},
     serde_json::json,
@@ -10,35 +11,54 @@ use {
 pub struct Forker<T> {
     forked_node_api: ForkedNodeApi<T>,
     fork_url: Url,
+    block_number: Option<u64>,
 }
 
 impl<T: Transport> Forker<T> {
-    pub async fn new(web3: &web3::Web3<T>, solver_address: H160, fork_url: impl IntoUrl) -> Self {
+    /// Forks the chain at `block_number` or the latest block if it is `None`
+    /// and impersonates and funds all solvers.
+    pub async fn new(
+        web3: &web3::Web3<T>,
+        solver_addresses: &[H160],
+        fork_url: impl IntoUrl,
+        block_number: Option<u64>,
+    ) -> Self {
         let fork_url = fork_url.into_url().expect("Invalid fork URL");
+        tracing::info!(%fork_url, ?block_number, "forking node");
 
         let forked_node_api = web3.api::<ForkedNodeApi<_>>();
         forked_node_api
-            .fork(&fork_url)
+            .fork(&fork_url, block_number)
             .await
             .expect("Test network must support anvil_reset");
 
-        forked_node_api
-            .impersonate(&solver_address)
-            .await
-            .expect("Test network must support anvil_impersonateAccount");
+        let test_node_api = web3.api::<TestNodeApi<_>>();
+        for solver_address in solver_addresses {
+            forked_node_api
+                .impersonate(solver_address)
+                .await
+                .expect("Test network must support anvil_impersonateAccount");
+            test_node_api
+                .set_balance(solver_address, &to_wei(100))
+                .await
+                .expect("Test network must support anvil_setBalance");
+        }
 
         Self {
             forked_node_api,
             fork_url,
+            block_number,
         }
     }
 }
 
 #[async_trait::async_trait(?Send)]
 impl<T: Transport> TestNode for Forker<T> {
+    /// Forking again discards all changes including impersonated accounts and
+    /// their balances.
     async fn reset(&self) {
         self.forked_node_api
-            .fork(&self.fork_url)
+            .fork(&self.fork_url, self.block_number)
             .await
             .expect("Test network must support anvil_reset");
     }
@@ -67,11 +87,15 @@ impl<T: Transport> Namespace<T> for ForkedNodeApi<T> {
 /// Relevant RPC calls for the Anvil network can be found at:
 /// https://book.getfoundry.sh/reference/anvil/
 impl<T: Transport> ForkedNodeApi<T> {
-    pub fn fork(&self, fork_url: &Url) -> CallFuture<(), T::Out> {
-        CallFuture::new(self.transport.execute(
-            "anvil_reset",
-            vec![json!({ "forking": {"jsonRpcUrl": fork_url.to_string()} })],
-        ))
+    pub fn fork(&self, fork_url: &Url, block_number: Option<u64>) -> CallFuture<(), T::Out> {
+        let mut forking = json!({ "jsonRpcUrl": fork_url.to_string() });
+        if let Some(block_number) = block_number {
+            forking["blockNumber"] = json!(block_number);
+        }
+        CallFuture::new(
+            self.transport
+                .execute("anvil_reset", vec![json!({ "forking": forking })]),
+        )
     }
 
     pub fn impersonate(&self, address: &H160) -> CallFuture<(), T::Out> {
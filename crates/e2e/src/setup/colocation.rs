@@ -6,6 +6,9 @@ use {
     tokio::task::JoinHandle,
 };
 
+/// The endpoint the driver started by [`start_driver`] serves its solver on.
+const DRIVER_ENDPOINT: &str = "http://localhost:11088/test_solver";
+
 pub async fn start_solver(weth: H160) -> Url {
     let config_file = config_tmp_file(format!(
         r#"
@@ -28,7 +31,9 @@ max-partial-attempts = 5
     });
 
     let solver_addr = bind_receiver.await.unwrap();
-    format!("http://{solver_addr}").parse().unwrap()
+    let endpoint = format!("http://{solver_addr}").parse().unwrap();
+    log_service_start("solver", &endpoint, None);
+    endpoint
 }
 
 pub fn start_driver(
@@ -73,6 +78,11 @@ mempool = "public"
         format!("--ethrpc={NODE_HOST}"),
     ];
 
+    log_service_start(
+        "driver",
+        &DRIVER_ENDPOINT.parse().unwrap(),
+        Some(solver_account.address()),
+    );
     tokio::task::spawn(async move {
         let _config_file = config_file;
         driver::run(args.into_iter(), None).await;
@@ -8,6 +8,9 @@ use {
     crate::nodes::{forked_node::Forker, local_node::Resetter, TestNode, NODE_HOST},
     anyhow::{anyhow, Result},
     ethcontract::{futures::FutureExt, H160},
+    observe::tracing::LogBuffer,
+    once_cell::sync::Lazy,
+    reqwest::Url,
     shared::ethrpc::{create_test_transport, Web3},
     std::{
         future::Future,
@@ -28,13 +31,38 @@ pub fn config_tmp_file<C: AsRef<[u8]>>(content: C) -> TempPath {
     file.into_temp_path()
 }
 
+/// Logs a structured event for a service a test starts so that the captured
+/// logs show which endpoint and solver account every service got wired up
+/// with.
+pub fn log_service_start(name: &str, endpoint: &Url, solver_address: Option<H160>) {
+    tracing::info!(
+        service = name,
+        %endpoint,
+        solver_address = solver_address.map(tracing::field::debug),
+        "starting service"
+    );
+}
+
 /// Reasonable default timeout for `wait_for_condition`.
 ///
 /// The correct timeout depends on the condition and where the test is run. For
 /// example, it can take a couple of seconds for a newly placed order to show up
 /// in the auction. When running on Github CI, anything can take an unexpectedly
 /// long time.
-pub const TIMEOUT: Duration = Duration::from_secs(30);
+///
+/// The default can be overridden with the `E2E_TIMEOUT_SECS` environment
+/// variable (e.g. for slow CI runners). Individual tests can still pass a
+/// custom duration to `wait_for_condition`.
+pub fn default_timeout() -> Duration {
+    static TIMEOUT: Lazy<Duration> = Lazy::new(|| {
+        std::env::var("E2E_TIMEOUT_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(30))
+    });
+    *TIMEOUT
+}
 
 /// Repeatedly evaluate condition until it returns true or the timeout is
 /// reached. If condition evaluates to true, Ok(()) is returned. If the timeout
@@ -45,19 +73,67 @@ pub async fn wait_for_condition<Fut>(
 ) -> Result<()>
 where
     Fut: Future<Output = bool>,
+{
+    wait_for_try_condition(timeout, || condition().map(Ok)).await
+}
+
+/// Like [`wait_for_condition`] but the condition can fail. The first error is
+/// returned immediately instead of waiting for the timeout.
+pub async fn wait_for_try_condition<Fut>(
+    timeout: Duration,
+    mut condition: impl FnMut() -> Fut,
+) -> Result<()>
+where
+    Fut: Future<Output = Result<bool>>,
 {
     let start = std::time::Instant::now();
-    while !condition().await {
+    while !condition().await? {
         tokio::time::sleep(Duration::from_millis(200)).await;
         if start.elapsed() > timeout {
             return Err(anyhow!("timeout"));
         }
     }
+    tracing::debug!(elapsed = ?start.elapsed(), "condition met");
     Ok(())
 }
 
+/// Repeatedly evaluate `f` until it returns `Some` value or the timeout is
+/// reached. Returns the first value or an error containing `description` if
+/// the timeout is reached.
+pub async fn wait_for_value<T, Fut>(
+    timeout: Duration,
+    description: &str,
+    mut f: impl FnMut() -> Fut,
+) -> Result<T>
+where
+    Fut: Future<Output = Option<T>>,
+{
+    let start = std::time::Instant::now();
+    loop {
+        if let Some(value) = f().await {
+            tracing::debug!(elapsed = ?start.elapsed(), description, "value available");
+            return Ok(value);
+        }
+        if start.elapsed() > timeout {
+            return Err(anyhow!(
+                "timeout after {timeout:?} waiting for {description}"
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
 static NODE_MUTEX: Mutex<()> = Mutex::new(());
 
+/// Logs of all services running in this process.
+static LOGS: Lazy<LogBuffer> = Lazy::new(LogBuffer::default);
+
+/// Returns the logs emitted during the currently running test so tests can
+/// assert that specific messages have (or haven't) been logged.
+pub fn captured_logs() -> LogBuffer {
+    LOGS.clone()
+}
+
 const DEFAULT_FILTERS: [&str; 9] = [
     "warn",
     "autopilot=debug",
@@ -85,34 +161,161 @@ where
 /// of the chain. The saved state is restored at the end of the test.
 /// The database is cleaned at the end of the test.
 ///
+/// The value returned by the closure is returned once the state has been
+/// restored.
+///
 /// This function also intializes tracing and sets panic hook.
 ///
 /// Note that tests calling with this function will not be run simultaneously.
-pub async fn run_test<F, Fut>(f: F)
+pub async fn run_test<F, Fut>(f: F) -> Fut::Output
 where
     F: FnOnce(Web3) -> Fut,
-    Fut: Future<Output = ()>,
+    Fut: Future,
+{
+    run(f, empty::<&str>(), vec![], None, None, None).await
+}
+
+/// Like [`run_test`] but only clears the tables of the specified database
+/// schema. Tests have to connect to the same schema, for example with
+/// [`Services::new_in_schema`], so that they don't interfere with the data of
+/// tests running in other schemas.
+///
+/// The schema needs to exist with all migrations applied, see the README.
+/// Tests still can't run simultaneously because they share the test node.
+/// Tests that only use the database can use
+/// [`run_database_test_in_schema`] instead.
+pub async fn run_test_in_schema<F, Fut>(f: F, schema: &str) -> Fut::Output
+where
+    F: FnOnce(Web3) -> Fut,
+    Fut: Future,
+{
+    run(f, empty::<&str>(), vec![], None, None, Some(schema)).await
+}
+
+/// Runs a test that only uses the database in the specified schema, see
+/// [`run_test_in_schema`]. Since the test node isn't used and reset, this
+/// doesn't wait for other tests and runs concurrently with tests in other
+/// schemas.
+///
+/// Captured logs are shared with concurrently running tests.
+pub async fn run_database_test_in_schema<F, Fut>(f: F, schema: &str) -> Fut::Output
+where
+    F: FnOnce() -> Fut,
+    Fut: Future,
 {
-    run(f, empty::<&str>(), None, None).await
+    let _log_guard = initialize(empty::<&str>());
+
+    services::clear_database_schema(schema).await;
+
+    let result = AssertUnwindSafe(f()).catch_unwind().await;
+
+    if result.is_err() && keep_state_on_failure() {
+        tracing::error!(
+            database = %db_url(Some(schema)),
+            "test failed; keeping database state for inspection"
+        );
+    } else {
+        services::clear_database_schema(schema).await;
+    }
+
+    match result {
+        Ok(output) => output,
+        Err(err) => panic::resume_unwind(err),
+    }
+}
+
+/// Like [`run_test`] but neither resets the test node nor clears the database.
+/// This makes read-only tests, for example ones checking the shape of API
+/// responses, considerably faster.
+///
+/// **Warning:** any state a read-only test mutates (transactions, orders,
+/// database rows, ...) leaks into all subsequent tests. Only use this for
+/// tests that really don't mutate any state.
+pub async fn run_test_readonly<F, Fut>(f: F) -> Fut::Output
+where
+    F: FnOnce(Web3) -> Fut,
+    Fut: Future,
+{
+    let _log_guard = initialize(empty::<&str>());
+    // See `run` for why the mutex may be poisoned.
+    let _lock = NODE_MUTEX.lock();
+    LOGS.clear();
+
+    let web3 = Web3::new(create_test_transport(NODE_HOST));
+    f(web3).await
 }
 
 pub async fn run_test_with_extra_filters<F, Fut, T>(
     f: F,
     extra_filters: impl IntoIterator<Item = T>,
-) where
+) -> Fut::Output
+where
     F: FnOnce(Web3) -> Fut,
-    Fut: Future<Output = ()>,
+    Fut: Future,
     T: AsRef<str>,
 {
-    run(f, extra_filters, None, None).await
+    run(f, extra_filters, vec![], None, None, None).await
+}
+
+pub async fn run_forked_test<F, Fut>(f: F, solver_address: H160, fork_url: String) -> Fut::Output
+where
+    F: FnOnce(Web3) -> Fut,
+    Fut: Future,
+{
+    run(
+        f,
+        empty::<&str>(),
+        vec![solver_address],
+        Some(fork_url),
+        None,
+        None,
+    )
+    .await
 }
 
-pub async fn run_forked_test<F, Fut>(f: F, solver_address: H160, fork_url: String)
+/// Like [`run_forked_test`] but impersonates and funds multiple solvers.
+pub async fn run_forked_test_with_solvers<F, Fut>(
+    f: F,
+    solver_addresses: Vec<H160>,
+    fork_url: String,
+) -> Fut::Output
+where
+    F: FnOnce(Web3) -> Fut,
+    Fut: Future,
+{
+    run(
+        f,
+        empty::<&str>(),
+        solver_addresses,
+        Some(fork_url),
+        None,
+        None,
+    )
+    .await
+}
+
+/// Like [`run_forked_test`] but forks the chain at the specified block instead
+/// of the latest one. This makes tests reproducible as the state of the fork
+/// doesn't change when the forked chain advances.
+pub async fn run_forked_test_at_block<F, Fut>(
+    f: F,
+    solver_address: H160,
+    fork_url: String,
+    block_number: u64,
+) -> Fut::Output
 where
     F: FnOnce(Web3) -> Fut,
-    Fut: Future<Output = ()>,
+    Fut: Future,
 {
-    run(f, empty::<&str>(), Some(solver_address), Some(fork_url)).await
+    run(
+        f,
+        empty::<&str>(),
+        vec![solver_address],
+        Some(fork_url),
+        Some(block_number),
+        None,
+    )
+    .await
 }
 
 pub async fn run_forked_test_with_extra_filters<F, Fut, T>(
@@ -120,26 +323,39 @@ pub async fn run_forked_test_with_extra_filters<F, Fut, T>(
     solver_address: H160,
     fork_url: String,
     extra_filters: impl IntoIterator<Item = T>,
-) where
+) -> Fut::Output
+where
     F: FnOnce(Web3) -> Fut,
-    Fut: Future<Output = ()>,
+    Fut: Future,
     T: AsRef<str>,
 {
-    run(f, extra_filters, Some(solver_address), Some(fork_url)).await
+    run(
+        f,
+        extra_filters,
+        vec![solver_address],
+        Some(fork_url),
+        None,
+        None,
+    )
+    .await
 }
 
 async fn run<F, Fut, T>(
     f: F,
     filters: impl IntoIterator<Item = T>,
-    solver_address: Option<H160>,
+    solver_addresses: Vec<H160>,
     fork_url: Option<String>,
-) where
+    block_number: Option<u64>,
+    schema: Option<&str>,
+) -> Fut::Output
+where
     F: FnOnce(Web3) -> Fut,
-    Fut: Future<Output = ()>,
+    Fut: Future,
     T: AsRef<str>,
 {
-    observe::tracing::initialize_reentrant(&with_default_filters(filters).join(","));
-    observe::panic_hook::install();
+    // Held until the end of the test so that the final log lines always get
+    // written.
+    let _log_guard = initialize(filters);
 
     // The mutex guarantees that no more than a test at a time is running on
     // the testing node.
@@ -147,18 +363,20 @@ async fn run<F, Fut, T>(
     // is not relevant for us as we are not interested in the data stored in
     // it but rather in the locked state.
     let _lock = NODE_MUTEX.lock();
+    // Only keep the logs of the current test.
+    LOGS.clear();
 
     let http = create_test_transport(NODE_HOST);
     let web3 = Web3::new(http);
 
-    let test_node: Box<dyn TestNode> =
-        if let (Some(fork_url), Some(solver_address)) = (fork_url, solver_address) {
-            Box::new(Forker::new(&web3, solver_address, fork_url).await)
-        } else {
-            Box::new(Resetter::new(&web3).await)
-        };
+    let test_node: Box<dyn TestNode> = match fork_url {
+        Some(fork_url) => {
+            Box::new(Forker::new(&web3, &solver_addresses, fork_url, block_number).await)
+        }
+        None => Box::new(Resetter::new(&web3).await),
+    };
 
-    services::clear_database().await;
+    clear_database_or_schema(schema).await;
 
     // Hack: the closure may actually be unwind unsafe; moreover, `catch_unwind`
     // does not catch some types of panics. In this cases, the state of the node
@@ -166,10 +384,74 @@ async fn run<F, Fut, T>(
     // is supposed to be used in a test environment.
     let result = AssertUnwindSafe(f(web3.clone())).catch_unwind().await;
 
-    test_node.reset().await;
-    services::clear_database().await;
+    if result.is_err() && keep_state_on_failure() {
+        tracing::error!(
+            node = NODE_HOST,
+            database = %db_url(schema),
+            "test failed; keeping node and database state for inspection"
+        );
+    } else {
+        test_node.reset().await;
+        clear_database_or_schema(schema).await;
+    }
+
+    match result {
+        Ok(output) => output,
+        Err(err) => panic::resume_unwind(err),
+    }
+}
+
+/// Whether the node and database state of a failing test should be kept for
+/// post-mortem inspection instead of being reset. Enabled by setting the
+/// `E2E_KEEP_STATE_ON_FAILURE` environment variable to `1`.
+///
+/// Since the state isn't restored, tests running after a failed one start from
+/// the state the failed test left behind and are likely to fail as well. Only
+/// use this when running a single test.
+fn keep_state_on_failure() -> bool {
+    std::env::var("E2E_KEEP_STATE_ON_FAILURE").is_ok_and(|value| value == "1")
+}
+
+/// Initializes tracing and installs the panic hook.
+fn initialize<T>(filters: impl IntoIterator<Item = T>) -> observe::tracing::FlushGuard
+where
+    T: AsRef<str>,
+{
+    let guard = observe::tracing::initialize_reentrant(
+        &observe::tracing::Config::new(&with_default_filters(filters).join(","))
+            .with_capture(LOGS.clone()),
+    );
+    observe::panic_hook::install();
+    guard
+}
+
+async fn clear_database_or_schema(schema: Option<&str>) {
+    match schema {
+        Some(schema) => services::clear_database_schema(schema).await,
+        None => services::clear_database().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::cell::Cell};
+
+    #[tokio::test]
+    async fn try_condition_returns_errors_early() {
+        let polls = Cell::new(0);
+        let result = wait_for_try_condition(Duration::from_secs(30), || {
+            polls.set(polls.get() + 1);
+            let poll = polls.get();
+            async move {
+                match poll {
+                    1 => Ok(false),
+                    _ => Err(anyhow!("rpc error")),
+                }
+            }
+        })
+        .await;
 
-    if let Err(err) = result {
-        panic::resume_unwind(err);
+        assert_eq!(result.unwrap_err().to_string(), "rpc error");
+        assert_eq!(polls.get(), 2);
     }
 }
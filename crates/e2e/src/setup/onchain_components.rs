@@ -5,6 +5,7 @@ use {
         ERC20Mintable,
         GnosisSafe,
         GnosisSafeCompatibilityFallbackHandler,
+        GnosisSafeProxyFactory,
     },
     ethcontract::{transaction::TransactionBuilder, Account, Bytes, PrivateKey, H160, H256, U256},
     hex_literal::hex,
@@ -129,6 +130,16 @@ where
         .await
         .expect("transaction reverted when estimating gas")
         .as_u64();
+    hook_for_undeployed_transaction(tx, gas_limit)
+}
+
+/// Creates a hook for a transaction with the specified gas limit. Useful for
+/// transactions that can't be simulated yet, for example because they call a
+/// contract that only gets deployed by an earlier hook.
+pub fn hook_for_undeployed_transaction<T>(tx: TransactionBuilder<T>, gas_limit: u64) -> Hook
+where
+    T: web3::Transport,
+{
     Hook {
         target: tx.to.unwrap(),
         call_data: tx.data.unwrap().0,
@@ -136,6 +147,27 @@ where
     }
 }
 
+/// Estimates the gas used by a transaction. Falls back to the specified gas
+/// limit if the estimation fails because the transaction reverts when
+/// simulated on the current state.
+///
+/// Note that this only helps for calls that actually revert. Calling an
+/// address without code (e.g. a contract that isn't deployed yet) succeeds
+/// and yields an estimate that is far too low for the real call, so use
+/// [`hook_for_undeployed_transaction`] with an explicit gas limit instead.
+pub async fn estimate_or_fallback_gas<T>(tx: TransactionBuilder<T>, fallback: u64) -> u64
+where
+    T: web3::Transport,
+{
+    match tx.estimate_gas().await {
+        Ok(gas) => gas.as_u64(),
+        Err(err) => {
+            tracing::info!(?err, fallback, "failed to estimate gas; using fallback");
+            fallback
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct TestAccount {
     account: Account,
@@ -565,6 +597,51 @@ impl OnchainComponents {
         cow
     }
 
+    /// Prepares a Safe owned by `owners` without deploying it. Returns the
+    /// [`Safe`] at its predicted address together with the hook that creates
+    /// it, so that it can be deployed with a pre-hook.
+    ///
+    /// The returned [`Safe`] signs with the first owner, so `threshold` needs
+    /// to be 1 for it to execute transactions on its own.
+    pub async fn deploy_safe(&self, owners: &[TestAccount], threshold: usize) -> (Safe, Hook) {
+        let owner = owners
+            .first()
+            .expect("Safe needs at least one owner")
+            .clone();
+
+        let singleton = GnosisSafe::builder(&self.web3).deploy().await.unwrap();
+        let fallback = GnosisSafeCompatibilityFallbackHandler::builder(&self.web3)
+            .deploy()
+            .await
+            .unwrap();
+        let factory = GnosisSafeProxyFactory::builder(&self.web3)
+            .deploy()
+            .await
+            .unwrap();
+
+        let setup = singleton
+            .setup(
+                owners.iter().map(TestAccount::address).collect(),
+                threshold.into(),
+                H160::default(),  // delegate call
+                Bytes::default(), // delegate call bytes
+                fallback.address(),
+                H160::default(), // relayer payment token
+                0.into(),        // relayer payment amount
+                H160::default(), // relayer address
+            )
+            .tx
+            .data
+            .unwrap();
+        let creation = factory.create_proxy(singleton.address(), Bytes(setup.0));
+
+        let address = creation.clone().view().call().await.unwrap();
+        let chain_id = self.web3.eth().chain_id().await.unwrap();
+        let safe = Safe::deployed(chain_id, GnosisSafe::at(&self.web3, address), owner);
+
+        (safe, hook_for_transaction(creation.tx).await)
+    }
+
     pub async fn send_wei(&self, to: H160, amount: U256) {
         TransactionBuilder::new(self.web3.clone())
             .value(amount)
@@ -1,7 +1,7 @@
 use {
     crate::{
         nodes::NODE_HOST,
-        setup::{wait_for_condition, Contracts, TIMEOUT},
+        setup::{default_timeout, wait_for_condition, Contracts},
     },
     clap::Parser,
     ethcontract::{H160, H256},
@@ -28,28 +28,50 @@ pub const VERSION_ENDPOINT: &str = "/api/v1/version";
 pub const SOLVER_COMPETITION_ENDPOINT: &str = "/api/v1/solver_competition";
 const LOCAL_DB_URL: &str = "postgresql://";
 
+/// Returns the URL of the local database. Connections using the URL of a
+/// `schema` only see the tables of that schema.
+pub fn db_url(schema: Option<&str>) -> String {
+    match schema {
+        Some(schema) => format!("{LOCAL_DB_URL}?options=-c%20search_path%3D{schema}"),
+        None => LOCAL_DB_URL.to_string(),
+    }
+}
+
 /// Wrapper over offchain services.
 /// Exposes various utility methods for tests.
 pub struct Services<'a> {
     contracts: &'a Contracts,
     http: Client,
     db: Db,
+    db_url: String,
 }
 
 impl<'a> Services<'a> {
     pub async fn new(contracts: &'a Contracts) -> Services<'a> {
+        Self::connect(contracts, db_url(None)).await
+    }
+
+    /// Like [`Services::new`] but the services only use the tables of the
+    /// specified database schema. See [`crate::setup::run_test_in_schema`].
+    pub async fn new_in_schema(contracts: &'a Contracts, schema: &str) -> Services<'a> {
+        Self::connect(contracts, db_url(Some(schema))).await
+    }
+
+    async fn connect(contracts: &'a Contracts, db_url: String) -> Services<'a> {
         Self {
             contracts,
             http: Client::builder()
                 .timeout(Duration::from_secs(10))
                 .build()
                 .unwrap(),
-            db: sqlx::PgPool::connect(LOCAL_DB_URL).await.unwrap(),
+            db: sqlx::PgPool::connect(&db_url).await.unwrap(),
+            db_url,
         }
     }
 
-    fn api_autopilot_arguments() -> impl Iterator<Item = String> {
+    fn api_autopilot_arguments(&self) -> impl Iterator<Item = String> {
         [
+            format!("--db-url={}", self.db_url),
             "--price-estimators=Baseline|0x0000000000000000000000000000000000000001".to_string(),
             "--native-price-estimators=Baseline".to_string(),
             "--amount-to-estimate-prices-with=1000000000000000000".to_string(),
@@ -91,7 +113,7 @@ impl<'a> Services<'a> {
         ]
         .into_iter()
         .chain(self.api_autopilot_solver_arguments())
-        .chain(Self::api_autopilot_arguments())
+        .chain(self.api_autopilot_arguments())
         .chain(extra_args);
 
         let args = autopilot::arguments::Arguments::try_parse_from(args).unwrap();
@@ -112,7 +134,7 @@ impl<'a> Services<'a> {
         ]
         .into_iter()
         .chain(self.api_autopilot_solver_arguments())
-        .chain(Self::api_autopilot_arguments())
+        .chain(self.api_autopilot_arguments())
         .chain(extra_args.into_iter());
 
         let args = orderbook::arguments::Arguments::try_parse_from(args).unwrap();
@@ -177,7 +199,7 @@ impl<'a> Services<'a> {
         };
 
         tracing::info!("Waiting for API to come up.");
-        wait_for_condition(TIMEOUT, is_up)
+        wait_for_condition(default_timeout(), is_up)
             .await
             .expect("waiting for API timed out");
     }
@@ -380,7 +402,17 @@ impl<'a> Services<'a> {
 
 pub async fn clear_database() {
     tracing::info!("Clearing database.");
-    let mut db = sqlx::PgConnection::connect(LOCAL_DB_URL).await.unwrap();
+    clear(&db_url(None)).await;
+}
+
+/// Like [`clear_database`] but only clears the tables of the specified schema.
+pub async fn clear_database_schema(schema: &str) {
+    tracing::info!(schema, "Clearing database schema.");
+    clear(&db_url(Some(schema))).await;
+}
+
+async fn clear(db_url: &str) {
+    let mut db = sqlx::PgConnection::connect(db_url).await.unwrap();
     let mut db = db.begin().await.unwrap();
     database::clear_DANGER_(&mut db).await.unwrap();
     db.commit().await.unwrap();
@@ -1,9 +1,8 @@
 use {
-    contracts::{GnosisSafe, GnosisSafeCompatibilityFallbackHandler, GnosisSafeProxyFactory},
     e2e::setup::*,
-    ethcontract::{Bytes, H160, U256},
+    ethcontract::U256,
     model::{
-        order::{Hook, OrderCreation, OrderCreationAppData, OrderKind},
+        order::{OrderCreation, OrderCreationAppData, OrderKind},
         signature::{hashed_eip712_message, EcdsaSigningScheme, Signature},
     },
     secp256k1::SecretKey,
@@ -110,7 +109,9 @@ async fn allowance(web3: Web3) {
             .unwrap()
             .is_zero()
     };
-    wait_for_condition(TIMEOUT, trade_happened).await.unwrap();
+    wait_for_condition(default_timeout(), trade_happened)
+        .await
+        .unwrap();
 
     // Check matching
     let balance = onchain
@@ -124,7 +125,9 @@ async fn allowance(web3: Web3) {
 
     tracing::info!("Waiting for auction to be cleared.");
     let auction_is_empty = || async { services.get_auction().await.auction.orders.is_empty() };
-    wait_for_condition(TIMEOUT, auction_is_empty).await.unwrap();
+    wait_for_condition(default_timeout(), auction_is_empty)
+        .await
+        .unwrap();
 
     // Check malicious custom interactions did not work.
     let allowance = cow
@@ -170,54 +173,12 @@ async fn allowance(web3: Web3) {
 async fn signature(web3: Web3) {
     let mut onchain = OnchainComponents::deploy(web3.clone()).await;
 
-    let chain_id = web3.eth().chain_id().await.unwrap();
-
     let [solver] = onchain.make_solvers(to_wei(1)).await;
     let [trader] = onchain.make_accounts(to_wei(1)).await;
 
-    // Deploy and setup a Gnosis Safe.
-    let safe_singleton = GnosisSafe::builder(&web3).deploy().await.unwrap();
-    let safe_fallback = GnosisSafeCompatibilityFallbackHandler::builder(&web3)
-        .deploy()
-        .await
-        .unwrap();
-    let safe_factory = GnosisSafeProxyFactory::builder(&web3)
-        .deploy()
-        .await
-        .unwrap();
-
-    // Prepare the Safe creation transaction, but don't execute it! This will
-    // be executed as a pre-hook.
-    let safe_creation_builder = safe_factory.create_proxy(
-        safe_singleton.address(),
-        ethcontract::Bytes(
-            safe_singleton
-                .setup(
-                    vec![trader.address()], // owners
-                    1.into(),               // threshold
-                    H160::default(),        // delegate call
-                    Bytes::default(),       // delegate call bytes
-                    safe_fallback.address(),
-                    H160::default(), // relayer payment token
-                    0.into(),        // relayer payment amount
-                    H160::default(), // relayer address
-                )
-                .tx
-                .data
-                .unwrap()
-                .0,
-        ),
-    );
-    let safe_creation = hook_for_transaction(safe_creation_builder.tx.clone()).await;
-
-    // Create a contract instance at the would-be address of the Safe we are
-    // creating with the pre-hook.
-    let safe_address = safe_creation_builder.clone().view().call().await.unwrap();
-    let safe = Safe::deployed(
-        chain_id,
-        GnosisSafe::at(&web3, safe_address),
-        trader.clone(),
-    );
+    // Prepare a Gnosis Safe, but don't deploy it! Its creation will be
+    // executed as a pre-hook.
+    let (safe, safe_creation) = onchain.deploy_safe(&[trader.clone()], 1).await;
 
     let [token] = onchain
         .deploy_tokens_with_weth_uni_v2_pools(to_wei(100_000), to_wei(100_000))
@@ -236,13 +197,9 @@ async fn signature(web3: Web3) {
             .0,
         0.into(),
     );
-    let approval = Hook {
-        target: approval_builder.tx.to.unwrap(),
-        call_data: approval_builder.tx.data.unwrap().0,
-        // The contract isn't deployed, so we can't estimate this. Instead, we
-        // just guess an amount that should be high enough.
-        gas_limit: 100_000,
-    };
+    // The Safe isn't deployed yet, so we can't estimate the gas needed for
+    // `execTransaction` and use a limit that should be high enough instead.
+    let approval = hook_for_undeployed_transaction(approval_builder.tx, 100_000);
 
     tracing::info!("Starting services.");
     let solver_endpoint = colocation::start_solver(onchain.contracts().weth.address()).await;
@@ -302,7 +259,9 @@ async fn signature(web3: Web3) {
             .unwrap()
             .is_zero()
     };
-    wait_for_condition(TIMEOUT, trade_happened).await.unwrap();
+    wait_for_condition(default_timeout(), trade_happened)
+        .await
+        .unwrap();
 
     // Check matching
     let balance = onchain
@@ -320,5 +279,7 @@ async fn signature(web3: Web3) {
 
     tracing::info!("Waiting for auction to be cleared.");
     let auction_is_empty = || async { services.get_auction().await.auction.orders.is_empty() };
-    wait_for_condition(TIMEOUT, auction_is_empty).await.unwrap();
+    wait_for_condition(default_timeout(), auction_is_empty)
+        .await
+        .unwrap();
 }
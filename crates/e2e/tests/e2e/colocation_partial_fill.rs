@@ -78,7 +78,9 @@ async fn test(web3: Web3) {
     tracing::info!("Waiting for trade.");
     let trade_happened =
         || async { token.balance_of(trader.address()).call().await.unwrap() != 0.into() };
-    wait_for_condition(TIMEOUT, trade_happened).await.unwrap();
+    wait_for_condition(default_timeout(), trade_happened)
+        .await
+        .unwrap();
 
     // We expect the partially fillable order to only fill half-way.
     let sell_balance = onchain
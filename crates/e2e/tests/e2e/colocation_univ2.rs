@@ -74,7 +74,9 @@ async fn test(web3: Web3) {
     tracing::info!("Waiting for trade.");
     let trade_happened =
         || async { token.balance_of(trader.address()).call().await.unwrap() != 0.into() };
-    wait_for_condition(TIMEOUT, trade_happened).await.unwrap();
+    wait_for_condition(default_timeout(), trade_happened)
+        .await
+        .unwrap();
 
     let balance = token.balance_of(trader.address()).call().await.unwrap();
     assert_eq!(balance, to_wei(1));
@@ -92,7 +94,7 @@ async fn test(web3: Web3) {
             ],
         )
     };
-    wait_for_condition(TIMEOUT, all_events_registered)
+    wait_for_condition(default_timeout(), all_events_registered)
         .await
         .unwrap();
 
@@ -118,7 +120,7 @@ async fn test(web3: Web3) {
             // and calldata is present
             && !data.call_data.call_data.is_empty()
     };
-    wait_for_condition(TIMEOUT, cip_20_data_updated)
+    wait_for_condition(default_timeout(), cip_20_data_updated)
         .await
         .unwrap();
 }
@@ -97,7 +97,9 @@ async fn test(web3: Web3) {
             .unwrap()
             .is_zero()
     };
-    wait_for_condition(TIMEOUT, trade_happened).await.unwrap();
+    wait_for_condition(default_timeout(), trade_happened)
+        .await
+        .unwrap();
 
     // Check matching
     let balance = onchain
@@ -111,7 +113,9 @@ async fn test(web3: Web3) {
 
     tracing::info!("Waiting for auction to be cleared.");
     let auction_is_empty = || async { services.get_auction().await.auction.orders.is_empty() };
-    wait_for_condition(TIMEOUT, auction_is_empty).await.unwrap();
+    wait_for_condition(default_timeout(), auction_is_empty)
+        .await
+        .unwrap();
 
     // Check malicious custom interactions did not work.
     let allowance = cow
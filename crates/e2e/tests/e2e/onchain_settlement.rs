@@ -124,7 +124,9 @@ async fn onchain_settlement(web3: Web3) {
     services.start_old_driver(solver.private_key(), vec![]);
     let trade_happened =
         || async { token_b.balance_of(trader_a.address()).call().await.unwrap() != 0.into() };
-    wait_for_condition(TIMEOUT, trade_happened).await.unwrap();
+    wait_for_condition(default_timeout(), trade_happened)
+        .await
+        .unwrap();
 
     // Check matching
     let balance = token_b.balance_of(trader_a.address()).call().await.unwrap();
@@ -134,5 +136,7 @@ async fn onchain_settlement(web3: Web3) {
 
     tracing::info!("Waiting for auction to be cleared.");
     let auction_is_empty = || async { services.get_auction().await.auction.orders.is_empty() };
-    wait_for_condition(TIMEOUT, auction_is_empty).await.unwrap();
+    wait_for_condition(default_timeout(), auction_is_empty)
+        .await
+        .unwrap();
 }
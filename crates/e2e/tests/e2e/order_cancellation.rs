@@ -147,7 +147,7 @@ async fn order_cancellation(web3: Web3) {
         place_order(1).await,
         place_order(2).await,
     ];
-    wait_for_condition(TIMEOUT, || async {
+    wait_for_condition(default_timeout(), || async {
         services.get_auction().await.auction.orders.len() == 3
     })
     .await
@@ -165,7 +165,7 @@ async fn order_cancellation(web3: Web3) {
 
     // Cancel one of them.
     cancel_order(order_uids[0]).await;
-    wait_for_condition(TIMEOUT, || async {
+    wait_for_condition(default_timeout(), || async {
         services.get_auction().await.auction.orders.len() == 2
     })
     .await
@@ -182,7 +182,7 @@ async fn order_cancellation(web3: Web3) {
 
     // Cancel the other two.
     cancel_orders(vec![order_uids[1], order_uids[2]]).await;
-    wait_for_condition(TIMEOUT, || async {
+    wait_for_condition(default_timeout(), || async {
         services.get_auction().await.auction.orders.is_empty()
     })
     .await
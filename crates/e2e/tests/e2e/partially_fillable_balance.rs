@@ -96,7 +96,9 @@ async fn test(web3: Web3) {
 
     tracing::info!("Waiting for order to show up in auction.");
     let has_order = || async { services.get_auction().await.auction.orders.len() == 1 };
-    wait_for_condition(TIMEOUT, has_order).await.unwrap();
+    wait_for_condition(default_timeout(), has_order)
+        .await
+        .unwrap();
 
     let auction = services.get_auction().await.auction;
     let order = auction.orders.into_iter().next().unwrap();
@@ -109,7 +111,9 @@ async fn test(web3: Web3) {
     services.start_old_driver(solver.private_key(), vec![]);
     let trade_happened =
         || async { token_b.balance_of(trader_a.address()).call().await.unwrap() != 0.into() };
-    wait_for_condition(TIMEOUT, trade_happened).await.unwrap();
+    wait_for_condition(default_timeout(), trade_happened)
+        .await
+        .unwrap();
 
     // Expecting a partial fill because order sells 100 but user only has balance of
     // 50.
@@ -100,7 +100,9 @@ async fn test(web3: Web3) {
 
     tracing::info!("Waiting for order to show up in auction.");
     let has_order = || async { services.get_auction().await.auction.orders.len() == 1 };
-    wait_for_condition(TIMEOUT, has_order).await.unwrap();
+    wait_for_condition(default_timeout(), has_order)
+        .await
+        .unwrap();
 
     let auction = services.get_auction().await.auction;
     let order = auction.orders.into_iter().next().unwrap();
@@ -114,7 +116,9 @@ async fn test(web3: Web3) {
     tracing::info!("Waiting for trade.");
     let trade_happened =
         || async { token_b.balance_of(trader_a.address()).call().await.unwrap() != 0.into() };
-    wait_for_condition(TIMEOUT, trade_happened).await.unwrap();
+    wait_for_condition(default_timeout(), trade_happened)
+        .await
+        .unwrap();
     let balance = token_b.balance_of(trader_a.address()).call().await.unwrap();
 
     // Add balance so that second trade happens.
@@ -122,12 +126,16 @@ async fn test(web3: Web3) {
     tracing::info!("Waiting for trade.");
     let trade_happened =
         || async { token_b.balance_of(trader_a.address()).call().await.unwrap() != balance };
-    wait_for_condition(TIMEOUT, trade_happened).await.unwrap();
+    wait_for_condition(default_timeout(), trade_happened)
+        .await
+        .unwrap();
 
     onchain.mint_blocks_past_reorg_threshold().await;
 
     let indexed_trades = || async { services.get_trades(&uid).await.unwrap().len() == 2 };
-    wait_for_condition(TIMEOUT, indexed_trades).await.unwrap();
+    wait_for_condition(default_timeout(), indexed_trades)
+        .await
+        .unwrap();
     let trades = services.get_trades(&uid).await.unwrap();
 
     tracing::info!("waiting for solver competitions to get indexed");
@@ -139,7 +147,7 @@ async fn test(web3: Web3) {
                 .is_ok()
         })
     };
-    wait_for_condition(TIMEOUT, competitions_indexed)
+    wait_for_condition(default_timeout(), competitions_indexed)
         .await
         .unwrap();
 
@@ -65,7 +65,7 @@ async fn refunder_tx(web3: Web3) {
     let order_id = ethflow_order.uid(onchain.contracts()).await;
 
     tracing::info!("Waiting for order to be indexed.");
-    wait_for_condition(TIMEOUT, || async {
+    wait_for_condition(default_timeout(), || async {
         services.get_order(&order_id).await.is_ok()
     })
     .await
@@ -120,5 +120,7 @@ async fn refunder_tx(web3: Web3) {
             .refund_tx_hash
             .is_some()
     };
-    wait_for_condition(TIMEOUT, has_tx_hash).await.unwrap();
+    wait_for_condition(default_timeout(), has_tx_hash)
+        .await
+        .unwrap();
 }
@@ -105,7 +105,7 @@ async fn onchain_settlement_without_liquidity(web3: Web3) {
 
     // Drive solution
     tracing::info!("Waiting for trade.");
-    wait_for_condition(TIMEOUT, || async {
+    wait_for_condition(default_timeout(), || async {
         services.get_auction().await.auction.orders.len() == 1
     })
     .await
@@ -119,7 +119,7 @@ async fn onchain_settlement_without_liquidity(web3: Web3) {
             token_b.address()
         )],
     );
-    wait_for_condition(TIMEOUT, || async {
+    wait_for_condition(default_timeout(), || async {
         services.get_auction().await.auction.orders.is_empty()
     })
     .await
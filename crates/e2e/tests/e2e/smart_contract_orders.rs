@@ -157,7 +157,7 @@ async fn smart_contract_orders(web3: Web3) {
     );
 
     // Check that the presignature event was received.
-    wait_for_condition(TIMEOUT, || async {
+    wait_for_condition(default_timeout(), || async {
         services.get_auction().await.auction.orders.len() == 2
     })
     .await
@@ -167,7 +167,7 @@ async fn smart_contract_orders(web3: Web3) {
     // Drive solution
     tracing::info!("Waiting for trade.");
     services.start_old_driver(solver.private_key(), vec![]);
-    wait_for_condition(TIMEOUT, || async {
+    wait_for_condition(default_timeout(), || async {
         services.get_auction().await.auction.orders.is_empty()
     })
     .await
@@ -79,5 +79,7 @@ async fn test(web3: Web3) {
         let events = crate::database::events_of_order(services.db(), &uid).await;
         events.last().map(|e| e.label) == Some(OrderEventLabel::Invalid)
     };
-    wait_for_condition(TIMEOUT, order_is_invalid).await.unwrap();
+    wait_for_condition(default_timeout(), order_is_invalid)
+        .await
+        .unwrap();
 }
@@ -272,7 +272,7 @@ mod tests {
     #[tokio::test]
     #[ignore]
     async fn mainnet() {
-        observe::tracing::initialize_reentrant("shared=debug");
+        observe::tracing::initialize_reentrant(&observe::tracing::Config::new("shared=debug"));
         let node = std::env::var("NODE_URL").unwrap();
         let transport = create_test_transport(&node);
         let web3 = Web3::new(transport);
@@ -1,10 +1,18 @@
 use {
-    crate::{auction::AuctionId, bytes_hex::BytesHex, order::OrderUid},
-    number::u256_decimal::{self, DecimalU256},
+    crate::{
+        auction::AuctionId,
+        bytes_hex::BytesHex,
+        order::{self, OrderUid},
+    },
+    num::BigRational,
+    number::{
+        conversions::u256_to_big_int,
+        u256_decimal::{self, DecimalU256},
+    },
     primitive_types::{H160, H256, U256},
     serde::{Deserialize, Serialize},
     serde_with::serde_as,
-    std::collections::{BTreeMap, HashSet},
+    std::collections::{BTreeMap, BTreeSet, HashMap, HashSet},
 };
 
 /// As a temporary measure the driver informs the api about per competition data
@@ -24,6 +32,35 @@ pub struct Request {
     pub prices: BTreeMap<H160, U256>, // external prices for auction
 }
 
+impl Request {
+    /// The sum of the solver fees of all executions.
+    pub fn total_solver_fees(&self) -> Result<U256, FeeOverflow> {
+        sum(self
+            .executions
+            .iter()
+            .map(|(_, execution)| execution.solver_fee))
+    }
+
+    /// The sum of the surplus fees of the executions that have one.
+    pub fn surplus_fees(&self) -> Result<U256, FeeOverflow> {
+        sum(self
+            .executions
+            .iter()
+            .filter_map(|(_, execution)| execution.surplus_fee))
+    }
+}
+
+fn sum(amounts: impl IntoIterator<Item = U256>) -> Result<U256, FeeOverflow> {
+    amounts
+        .into_iter()
+        .try_fold(U256::zero(), |total, amount| total.checked_add(amount))
+        .ok_or(FeeOverflow)
+}
+
+/// The sum of fees doesn't fit into a `U256`.
+#[derive(Debug, Eq, PartialEq)]
+pub struct FeeOverflow;
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Scores {
     pub winner: H160,
@@ -34,6 +71,20 @@ pub struct Scores {
     pub block_deadline: u64,
 }
 
+impl Scores {
+    /// By how much the winning score exceeds the reference score. Returns
+    /// `None` if the reference score is higher, which is invalid.
+    pub fn winner_margin(&self) -> Option<U256> {
+        self.winning_score.checked_sub(self.reference_score)
+    }
+
+    /// Checks that the winning score isn't lower than the reference score and
+    /// that a block deadline is set.
+    pub fn is_valid(&self) -> bool {
+        self.winner_margin().is_some() && self.block_deadline > 0
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Transaction {
     pub account: H160,
@@ -54,6 +105,10 @@ pub struct Execution {
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SolverCompetitionDB {
+    /// Version of the schema the competition was stored with. Competitions
+    /// that were stored before the version was introduced have version 0.
+    #[serde(default)]
+    pub version: u32,
     pub gas_price: f64,
     pub auction_start_block: u64,
     pub liquidity_collected_block: u64,
@@ -62,6 +117,42 @@ pub struct SolverCompetitionDB {
     pub solutions: Vec<SolverSettlement>,
 }
 
+impl SolverCompetitionDB {
+    /// The current version of the schema. Bump it whenever the shape of the
+    /// stored data changes.
+    pub const VERSION: u32 = 1;
+
+    /// Returns the settlement that won the competition.
+    ///
+    /// This is the settlement with the highest score. Settlements without a
+    /// score only compete on their objective value if no settlement has a
+    /// score. Ties are broken in favour of the lower solver address.
+    pub fn winner(&self) -> Option<&SolverSettlement> {
+        if self
+            .solutions
+            .iter()
+            .any(|solution| solution.score.is_some())
+        {
+            self.solutions
+                .iter()
+                .filter_map(|solution| Some((solution.score?.score(), solution)))
+                .max_by(|(a_score, a), (b_score, b)| {
+                    a_score
+                        .cmp(b_score)
+                        .then_with(|| b.solver_address.cmp(&a.solver_address))
+                })
+                .map(|(_, solution)| solution)
+        } else {
+            self.solutions.iter().max_by(|a, b| {
+                a.objective
+                    .total
+                    .total_cmp(&b.objective.total)
+                    .then_with(|| b.solver_address.cmp(&a.solver_address))
+            })
+        }
+    }
+}
+
 /// Returned by the `/solver_competition` endpoint.
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -72,6 +163,145 @@ pub struct SolverCompetitionAPI {
     pub common: SolverCompetitionDB,
 }
 
+impl SolverCompetitionAPI {
+    /// Flattens the competition into one record per executed order of each
+    /// solution. Useful for exporting competitions to column oriented
+    /// analytics stores.
+    pub fn to_flat_records(&self) -> Vec<CompetitionRecord> {
+        self.common
+            .solutions
+            .iter()
+            .flat_map(|solution| {
+                solution.orders.iter().map(|order| CompetitionRecord {
+                    auction_id: self.auction_id,
+                    solver: solution.solver.clone(),
+                    solver_address: solution.solver_address,
+                    order: order.id,
+                    executed_amount: order.executed_amount,
+                    score: solution.score.as_ref().map(Score::score),
+                    ranking: solution.ranking,
+                    transaction_hash: self.transaction_hash,
+                })
+            })
+            .collect()
+    }
+
+    /// Compares the competition with `other`, e.g. what the driver reported
+    /// with what got stored. Changes are reported from `self` to `other`.
+    /// Solutions are matched by solver.
+    pub fn diff(&self, other: &Self) -> CompetitionDiff {
+        let solutions = |competition: &Self| {
+            competition
+                .common
+                .solutions
+                .iter()
+                .map(|solution| (solution.solver.as_str(), solution))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let (old, new) = (solutions(self), solutions(other));
+        let missing_from = |solutions: &BTreeMap<&str, _>, other: &BTreeMap<&str, _>| {
+            solutions
+                .keys()
+                .filter(|solver| !other.contains_key(*solver))
+                .map(|solver| solver.to_string())
+                .collect::<Vec<_>>()
+        };
+        CompetitionDiff {
+            added_solutions: missing_from(&new, &old),
+            removed_solutions: missing_from(&old, &new),
+            changed_solutions: old
+                .iter()
+                .filter_map(|(solver, old)| {
+                    let diff = old.diff(new.get(solver)?);
+                    (!diff.is_empty()).then_some(diff)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Differences between two competitions, see [`SolverCompetitionAPI::diff`].
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CompetitionDiff {
+    /// Solvers that only have a solution in the new competition.
+    pub added_solutions: Vec<String>,
+    /// Solvers that only have a solution in the old competition.
+    pub removed_solutions: Vec<String>,
+    /// Differences between solutions of solvers present in both competitions.
+    pub changed_solutions: Vec<SolutionDiff>,
+}
+
+impl CompetitionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_solutions.is_empty()
+            && self.removed_solutions.is_empty()
+            && self.changed_solutions.is_empty()
+    }
+}
+
+/// Differences between two solutions of the same solver.
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SolutionDiff {
+    pub solver: String,
+    /// Clearing prices that differ, by token.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub clearing_prices: BTreeMap<H160, PriceChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<Change<Option<Score>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ranking: Option<Change<Option<usize>>>,
+}
+
+impl SolutionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.clearing_prices.is_empty() && self.score.is_none() && self.ranking.is_none()
+    }
+}
+
+/// A value that changed between two competitions.
+#[derive(Clone, Debug, Serialize, Eq, PartialEq)]
+pub struct Change<T> {
+    pub old: T,
+    pub new: T,
+}
+
+impl<T: PartialEq> Change<T> {
+    fn between(old: T, new: T) -> Option<Self> {
+        (old != new).then_some(Self { old, new })
+    }
+}
+
+/// A clearing price that changed between two competitions. `None` if the
+/// solution has no price for the token.
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Eq, PartialEq)]
+pub struct PriceChange {
+    #[serde_as(as = "Option<DecimalU256>")]
+    pub old: Option<U256>,
+    #[serde_as(as = "Option<DecimalU256>")]
+    pub new: Option<U256>,
+}
+
+/// A single executed order of a solution with the denormalized data of the
+/// solution and competition it belongs to.
+#[serde_as]
+#[derive(Clone, Debug, Default, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CompetitionRecord {
+    pub auction_id: AuctionId,
+    pub solver: String,
+    pub solver_address: H160,
+    pub order: OrderUid,
+    #[serde_as(as = "DecimalU256")]
+    pub executed_amount: U256,
+    #[serde_as(as = "Option<DecimalU256>")]
+    pub score: Option<U256>,
+    pub ranking: Option<usize>,
+    pub transaction_hash: Option<H256>,
+}
+
 #[serde_as]
 #[derive(Clone, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -81,6 +311,24 @@ pub struct CompetitionAuction {
     pub prices: BTreeMap<H160, U256>,
 }
 
+impl CompetitionAuction {
+    /// The external price of `token`. `None` if the auction has no price for
+    /// it.
+    pub fn price(&self, token: H160) -> Option<U256> {
+        self.prices.get(&token).copied()
+    }
+
+    /// The ratio of the external prices of `a` and `b`, i.e. how many `b` one
+    /// `a` is worth. `None` if either price is missing or zero.
+    pub fn price_ratio(&self, a: H160, b: H160) -> Option<BigRational> {
+        let price = |token| self.price(token).filter(|price| !price.is_zero());
+        Some(BigRational::new(
+            u256_to_big_int(&price(a)?),
+            u256_to_big_int(&price(b)?),
+        ))
+    }
+}
+
 #[serde_as]
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -105,6 +353,101 @@ pub struct SolverSettlement {
     pub uninternalized_call_data: Option<Vec<u8>>,
 }
 
+impl SolverSettlement {
+    /// Checks that the settlement has a non-zero clearing price for the sell
+    /// and buy token of each of its orders. `orders` has to contain the full
+    /// orders that the settlement executes.
+    pub fn validate_clearing_prices(
+        &self,
+        orders: &[order::Order],
+    ) -> Result<(), ClearingPriceError> {
+        let orders: HashMap<_, _> = orders
+            .iter()
+            .map(|order| (order.metadata.uid, &order.data))
+            .collect();
+        for executed in &self.orders {
+            let data = orders
+                .get(&executed.id)
+                .ok_or(ClearingPriceError::UnknownOrder(executed.id))?;
+            for token in [data.sell_token, data.buy_token] {
+                match self.clearing_prices.get(&token) {
+                    None => return Err(ClearingPriceError::MissingPrice(token)),
+                    Some(price) if price.is_zero() => {
+                        return Err(ClearingPriceError::ZeroPrice(token))
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+        if let Some((token, _)) = self
+            .clearing_prices
+            .iter()
+            .find(|(_, price)| price.is_zero())
+        {
+            return Err(ClearingPriceError::ZeroPrice(*token));
+        }
+        Ok(())
+    }
+
+    /// Compares the settlement with `other`, see [`SolverCompetitionAPI::diff`].
+    fn diff(&self, other: &Self) -> SolutionDiff {
+        let tokens = self
+            .clearing_prices
+            .keys()
+            .chain(other.clearing_prices.keys())
+            .collect::<BTreeSet<_>>();
+        SolutionDiff {
+            solver: self.solver.clone(),
+            clearing_prices: tokens
+                .into_iter()
+                .filter_map(|token| {
+                    let old = self.clearing_prices.get(token).copied();
+                    let new = other.clearing_prices.get(token).copied();
+                    (old != new).then_some((*token, PriceChange { old, new }))
+                })
+                .collect(),
+            score: Change::between(self.score, other.score),
+            ranking: Change::between(self.ranking, other.ranking),
+        }
+    }
+
+    /// The sizes of the settlement's calldata with and without internalized
+    /// interactions.
+    pub fn calldata_sizes(&self) -> CalldataSizes {
+        CalldataSizes {
+            internalized: self.call_data.len(),
+            uninternalized: self.uninternalized_call_data.as_ref().map(Vec::len),
+        }
+    }
+
+    /// The number of calldata bytes saved by internalizing interactions. `None`
+    /// if the uninternalized calldata is unknown or not larger.
+    pub fn calldata_savings(&self) -> Option<usize> {
+        let sizes = self.calldata_sizes();
+        sizes
+            .uninternalized?
+            .checked_sub(sizes.internalized)
+            .filter(|savings| *savings > 0)
+    }
+}
+
+/// Calldata sizes of a settlement in bytes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CalldataSizes {
+    pub internalized: usize,
+    pub uninternalized: Option<usize>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ClearingPriceError {
+    /// The settlement executes an order that wasn't provided.
+    UnknownOrder(OrderUid),
+    /// A token traded by an order has no clearing price.
+    MissingPrice(H160),
+    /// A token has a clearing price of zero.
+    ZeroPrice(H160),
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Objective {
@@ -153,6 +496,47 @@ impl Score {
             Self::Discounted(score) => *score,
         }
     }
+
+    /// Creates a [`Score::Discounted`] by applying a discount in basis points
+    /// to the `protocol` score.
+    pub fn discounted(protocol: U256, discount_bps: u32) -> Result<Self, ScoreError> {
+        if discount_bps > 10_000 {
+            return Err(ScoreError::DiscountOutOfRange(discount_bps));
+        }
+        let score = protocol
+            .checked_mul((10_000 - discount_bps).into())
+            .ok_or(ScoreError::Overflow)?
+            / 10_000;
+        Ok(Self::Discounted(score))
+    }
+
+    /// Checks that the score is valid for a solution whose protocol score
+    /// (i.e. objective value) is `protocol`.
+    ///
+    /// Scores must be non-zero and discounted scores can't exceed the protocol
+    /// score they were discounted from.
+    pub fn validate(&self, protocol: U256) -> Result<(), ScoreError> {
+        if self.score().is_zero() {
+            return Err(ScoreError::Zero);
+        }
+        if let Self::Discounted(discounted) = self {
+            if *discounted > protocol {
+                return Err(ScoreError::DiscountedAboveProtocol {
+                    discounted: *discounted,
+                    protocol,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ScoreError {
+    Zero,
+    DiscountedAboveProtocol { discounted: U256, protocol: U256 },
+    DiscountOutOfRange(u32),
+    Overflow,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
@@ -167,10 +551,59 @@ pub struct Order {
 mod tests {
     use {super::*, maplit::btreemap};
 
+    fn fixture() -> SolverCompetitionAPI {
+        SolverCompetitionAPI {
+            auction_id: 0,
+            transaction_hash: Some(H256([0x11; 32])),
+            common: SolverCompetitionDB {
+                version: SolverCompetitionDB::VERSION,
+                gas_price: 1.,
+                auction_start_block: 13,
+                liquidity_collected_block: 14,
+                competition_simulation_block: 15,
+                auction: CompetitionAuction {
+                    orders: vec![
+                        OrderUid([0x11; 56]),
+                        OrderUid([0x22; 56]),
+                        OrderUid([0x33; 56]),
+                    ],
+                    prices: btreemap! {
+                        H160([0x11; 20]) => 1000.into(),
+                        H160([0x22; 20]) => 2000.into(),
+                        H160([0x33; 20]) => 3000.into(),
+                    },
+                },
+                solutions: vec![SolverSettlement {
+                    solver: "2".to_string(),
+                    solver_address: H160([0x22; 20]),
+                    objective: Objective {
+                        total: 3.,
+                        surplus: 4.,
+                        fees: 5.,
+                        cost: 6.,
+                        gas: 7,
+                    },
+                    score: Some(Score::Solver(1.into())),
+                    ranking: Some(1),
+                    clearing_prices: btreemap! {
+                        H160([0x22; 20]) => 8.into(),
+                    },
+                    orders: vec![Order {
+                        id: OrderUid([0x33; 56]),
+                        executed_amount: 12.into(),
+                    }],
+                    call_data: vec![0x13],
+                    uninternalized_call_data: Some(vec![0x13, 0x14]),
+                }],
+            },
+        }
+    }
+
     #[test]
     fn serialize() {
         let correct = serde_json::json!({
             "auctionId": 0,
+            "version": 1u32,
             "gasPrice": 1.0f64,
             "auctionStartBlock": 13u64,
             "liquidityCollectedBlock": 14u64,
@@ -224,54 +657,338 @@ mod tests {
             ],
         });
 
-        let orig = SolverCompetitionAPI {
-            auction_id: 0,
-            transaction_hash: Some(H256([0x11; 32])),
-            common: SolverCompetitionDB {
-                gas_price: 1.,
-                auction_start_block: 13,
-                liquidity_collected_block: 14,
-                competition_simulation_block: 15,
-                auction: CompetitionAuction {
-                    orders: vec![
-                        OrderUid([0x11; 56]),
-                        OrderUid([0x22; 56]),
-                        OrderUid([0x33; 56]),
-                    ],
-                    prices: btreemap! {
-                        H160([0x11; 20]) => 1000.into(),
-                        H160([0x22; 20]) => 2000.into(),
-                        H160([0x33; 20]) => 3000.into(),
-                    },
+        let orig = fixture();
+
+        let serialized = serde_json::to_value(&orig).unwrap();
+        assert_eq!(correct, serialized);
+        let deserialized: SolverCompetitionAPI = serde_json::from_value(correct.clone()).unwrap();
+        assert_eq!(orig, deserialized);
+
+        // Competitions stored before the schema was versioned default to 0.
+        let mut legacy = correct;
+        legacy.as_object_mut().unwrap().remove("version");
+        let deserialized: SolverCompetitionAPI = serde_json::from_value(legacy).unwrap();
+        assert_eq!(deserialized.common.version, 0);
+    }
+
+    #[test]
+    fn flat_records() {
+        let mut competition = fixture();
+        let mut second = competition.common.solutions[0].clone();
+        second.solver = "3".to_string();
+        second.solver_address = H160([0x33; 20]);
+        second.score = None;
+        second.ranking = Some(2);
+        second.orders.push(Order {
+            id: OrderUid([0x11; 56]),
+            executed_amount: 13.into(),
+        });
+        competition.common.solutions.push(second);
+
+        let records = competition.to_flat_records();
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[0],
+            CompetitionRecord {
+                auction_id: 0,
+                solver: "2".to_string(),
+                solver_address: H160([0x22; 20]),
+                order: OrderUid([0x33; 56]),
+                executed_amount: 12.into(),
+                score: Some(1.into()),
+                ranking: Some(1),
+                transaction_hash: Some(H256([0x11; 32])),
+            }
+        );
+        assert_eq!(
+            records[2],
+            CompetitionRecord {
+                auction_id: 0,
+                solver: "3".to_string(),
+                solver_address: H160([0x33; 20]),
+                order: OrderUid([0x11; 56]),
+                executed_amount: 13.into(),
+                score: None,
+                ranking: Some(2),
+                transaction_hash: Some(H256([0x11; 32])),
+            }
+        );
+    }
+
+    #[test]
+    fn winner_margin() {
+        let scores = |winning_score: u64, reference_score: u64| Scores {
+            winning_score: winning_score.into(),
+            reference_score: reference_score.into(),
+            block_deadline: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(scores(10, 3).winner_margin(), Some(7.into()));
+        assert!(scores(10, 3).is_valid());
+
+        assert_eq!(scores(10, 10).winner_margin(), Some(0.into()));
+        assert!(scores(10, 10).is_valid());
+
+        assert_eq!(scores(3, 10).winner_margin(), None);
+        assert!(!scores(3, 10).is_valid());
+
+        let without_deadline = Scores {
+            block_deadline: 0,
+            ..scores(10, 3)
+        };
+        assert!(!without_deadline.is_valid());
+    }
+
+    #[test]
+    fn request_fee_sums() {
+        let execution = |solver_fee: u64, surplus_fee: Option<u64>| {
+            (
+                OrderUid::default(),
+                Execution {
+                    surplus_fee: surplus_fee.map(U256::from),
+                    solver_fee: solver_fee.into(),
                 },
-                solutions: vec![SolverSettlement {
-                    solver: "2".to_string(),
-                    solver_address: H160([0x22; 20]),
-                    objective: Objective {
-                        total: 3.,
-                        surplus: 4.,
-                        fees: 5.,
-                        cost: 6.,
-                        gas: 7,
-                    },
-                    score: Some(Score::Solver(1.into())),
-                    ranking: Some(1),
-                    clearing_prices: btreemap! {
-                        H160([0x22; 20]) => 8.into(),
+            )
+        };
+        let request = Request {
+            executions: vec![
+                execution(1, Some(10)),
+                execution(2, None),
+                execution(3, Some(20)),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(request.total_solver_fees().unwrap(), 6.into());
+        assert_eq!(request.surplus_fees().unwrap(), 30.into());
+
+        let overflowing = Request {
+            executions: vec![
+                execution(1, None),
+                (
+                    OrderUid::default(),
+                    Execution {
+                        surplus_fee: None,
+                        solver_fee: U256::MAX,
                     },
-                    orders: vec![Order {
-                        id: OrderUid([0x33; 56]),
-                        executed_amount: 12.into(),
-                    }],
-                    call_data: vec![0x13],
-                    uninternalized_call_data: Some(vec![0x13, 0x14]),
+                ),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(overflowing.total_solver_fees(), Err(FeeOverflow));
+        assert_eq!(overflowing.surplus_fees().unwrap(), 0.into());
+    }
+
+    #[test]
+    fn discounted_score() {
+        assert_eq!(
+            Score::discounted(1_000.into(), 250),
+            Ok(Score::Discounted(975.into())),
+        );
+        assert_eq!(
+            Score::discounted(1_000.into(), 10_000),
+            Ok(Score::Discounted(0.into())),
+        );
+        assert_eq!(
+            Score::discounted(1_000.into(), 10_001),
+            Err(ScoreError::DiscountOutOfRange(10_001)),
+        );
+        assert_eq!(Score::discounted(U256::MAX, 1), Err(ScoreError::Overflow));
+    }
+
+    #[test]
+    fn validate_score() {
+        assert_eq!(Score::Solver(1.into()).validate(0.into()), Ok(()));
+        assert_eq!(Score::Discounted(10.into()).validate(10.into()), Ok(()));
+        assert_eq!(
+            Score::Protocol(0.into()).validate(0.into()),
+            Err(ScoreError::Zero),
+        );
+        assert_eq!(
+            Score::discounted(1_000.into(), 10_000)
+                .unwrap()
+                .validate(1_000.into()),
+            Err(ScoreError::Zero),
+        );
+        assert_eq!(
+            Score::Discounted(11.into()).validate(10.into()),
+            Err(ScoreError::DiscountedAboveProtocol {
+                discounted: 11.into(),
+                protocol: 10.into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn validate_clearing_prices() {
+        let sell_token = H160([1; 20]);
+        let buy_token = H160([2; 20]);
+        let order = order::Order {
+            metadata: order::OrderMetadata {
+                uid: OrderUid([1; 56]),
+                ..Default::default()
+            },
+            data: order::OrderData {
+                sell_token,
+                buy_token,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let settlement = |clearing_prices| SolverSettlement {
+            clearing_prices,
+            orders: vec![Order {
+                id: OrderUid([1; 56]),
+                executed_amount: 1.into(),
+            }],
+            ..Default::default()
+        };
+
+        let valid = settlement(btreemap! {
+            sell_token => 1.into(),
+            buy_token => 2.into(),
+        });
+        assert_eq!(valid.validate_clearing_prices(&[order.clone()]), Ok(()));
+        assert_eq!(
+            valid.validate_clearing_prices(&[]),
+            Err(ClearingPriceError::UnknownOrder(OrderUid([1; 56]))),
+        );
+
+        let missing_price = settlement(btreemap! { sell_token => 1.into() });
+        assert_eq!(
+            missing_price.validate_clearing_prices(&[order.clone()]),
+            Err(ClearingPriceError::MissingPrice(buy_token)),
+        );
+
+        let zero_price = settlement(btreemap! {
+            sell_token => 1.into(),
+            buy_token => 2.into(),
+            H160([3; 20]) => 0.into(),
+        });
+        assert_eq!(
+            zero_price.validate_clearing_prices(&[order]),
+            Err(ClearingPriceError::ZeroPrice(H160([3; 20]))),
+        );
+    }
+
+    #[test]
+    fn competition_prices() {
+        let auction = CompetitionAuction {
+            prices: btreemap! {
+                H160([1; 20]) => 1000.into(),
+                H160([2; 20]) => 4000.into(),
+                H160([3; 20]) => 0.into(),
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(auction.price(H160([1; 20])), Some(1000.into()));
+        assert_eq!(auction.price(H160([4; 20])), None);
+        assert_eq!(
+            auction.price_ratio(H160([1; 20]), H160([2; 20])),
+            Some(BigRational::new(1.into(), 4.into()))
+        );
+        // Absent and zero prices.
+        assert_eq!(auction.price_ratio(H160([1; 20]), H160([4; 20])), None);
+        assert_eq!(auction.price_ratio(H160([4; 20]), H160([1; 20])), None);
+        assert_eq!(auction.price_ratio(H160([1; 20]), H160([3; 20])), None);
+        assert_eq!(auction.price_ratio(H160([3; 20]), H160([1; 20])), None);
+    }
+
+    #[test]
+    fn diff() {
+        let old = fixture();
+        assert!(old.diff(&old).is_empty());
+
+        let mut new = fixture();
+        new.common.solutions[0].score = Some(Score::Solver(2.into()));
+        assert_eq!(
+            old.diff(&new),
+            CompetitionDiff {
+                changed_solutions: vec![SolutionDiff {
+                    solver: "2".to_string(),
+                    score: Some(Change {
+                        old: Some(Score::Solver(1.into())),
+                        new: Some(Score::Solver(2.into())),
+                    }),
+                    ..Default::default()
                 }],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn calldata_savings() {
+        let settlement = fixture().common.solutions.remove(0);
+        assert_eq!(
+            settlement.calldata_sizes(),
+            CalldataSizes {
+                internalized: 1,
+                uninternalized: Some(2),
+            }
+        );
+        assert_eq!(settlement.calldata_savings(), Some(1));
+
+        let same = SolverSettlement {
+            uninternalized_call_data: Some(settlement.call_data.clone()),
+            ..settlement.clone()
+        };
+        assert_eq!(same.calldata_savings(), None);
+        let missing = SolverSettlement {
+            uninternalized_call_data: None,
+            ..settlement
+        };
+        assert_eq!(missing.calldata_savings(), None);
+    }
+
+    #[test]
+    fn winner() {
+        let settlement = |address: u8, total: f64, score: Option<u64>| SolverSettlement {
+            solver_address: H160([address; 20]),
+            objective: Objective {
+                total,
+                ..Default::default()
             },
+            score: score.map(|score| Score::Solver(score.into())),
+            ..Default::default()
+        };
+        let winner = |solutions: Vec<SolverSettlement>| {
+            SolverCompetitionDB {
+                solutions,
+                ..Default::default()
+            }
+            .winner()
+            .map(|winner| winner.solver_address)
         };
 
-        let serialized = serde_json::to_value(&orig).unwrap();
-        assert_eq!(correct, serialized);
-        let deserialized: SolverCompetitionAPI = serde_json::from_value(correct).unwrap();
-        assert_eq!(orig, deserialized);
+        assert_eq!(winner(vec![]), None);
+        assert_eq!(
+            winner(vec![
+                settlement(1, 3., Some(1)),
+                settlement(2, 1., Some(3)),
+                settlement(3, 2., Some(2)),
+            ]),
+            Some(H160([2; 20])),
+        );
+        // Ties are broken by solver address.
+        assert_eq!(
+            winner(vec![settlement(2, 0., Some(1)), settlement(1, 0., Some(1))]),
+            Some(H160([1; 20])),
+        );
+        // Settlements without a score lose against ones with a score.
+        assert_eq!(
+            winner(vec![settlement(1, 3., None), settlement(2, 1., Some(1))]),
+            Some(H160([2; 20])),
+        );
+        // Without any scores the objective value decides.
+        assert_eq!(
+            winner(vec![
+                settlement(1, 1., None),
+                settlement(3, 3., None),
+                settlement(2, 3., None),
+            ]),
+            Some(H160([2; 20])),
+        );
     }
 }
@@ -4,4 +4,5 @@
 pub mod metrics;
 pub mod panic_hook;
 pub mod request_id;
+pub mod shutdown;
 pub mod tracing;
@@ -1,8 +1,29 @@
+#[cfg(feature = "server")]
+use {
+    hyper::{
+        header::CONTENT_TYPE,
+        service::{make_service_fn, service_fn},
+        Body,
+        Method,
+        Request,
+        Response,
+        StatusCode,
+    },
+    std::{convert::Infallible, net::SocketAddr},
+    tokio::task::JoinHandle,
+};
 use {once_cell::sync::OnceCell, std::collections::HashMap};
 
+#[cfg(feature = "exemplars")]
+pub mod exemplars;
+
 /// Global metrics registry used by all components.
 static REGISTRY: OnceCell<prometheus_metric_storage::StorageRegistry> = OnceCell::new();
 
+/// Prefix and common labels of the global registry.
+#[cfg(feature = "exemplars")]
+static NAMESPACE: OnceCell<exemplars::Namespace> = OnceCell::new();
+
 /// Configure global metrics registry.
 ///
 /// This function allows specifying common prefix that will be added
@@ -18,9 +39,7 @@ static REGISTRY: OnceCell<prometheus_metric_storage::StorageRegistry> = OnceCell
 /// any call to [`get_registry`]. This function also panics if registry
 /// configuration is invalid.
 pub fn setup_registry(prefix: Option<String>, labels: Option<HashMap<String, String>>) {
-    let registry = prometheus::Registry::new_custom(prefix, labels).unwrap();
-    let storage_registry = prometheus_metric_storage::StorageRegistry::new(registry);
-    REGISTRY.set(storage_registry).unwrap();
+    set_registry(prefix, labels).unwrap();
 }
 
 /// Like [`setup_registry`], but can be called multiple times in a row.
@@ -28,9 +47,24 @@ pub fn setup_registry(prefix: Option<String>, labels: Option<HashMap<String, Str
 ///
 /// Useful for tests.
 pub fn setup_registry_reentrant(prefix: Option<String>, labels: Option<HashMap<String, String>>) {
+    set_registry(prefix, labels).ok();
+}
+
+fn set_registry(
+    prefix: Option<String>,
+    labels: Option<HashMap<String, String>>,
+) -> Result<(), prometheus_metric_storage::StorageRegistry> {
+    #[cfg(feature = "exemplars")]
+    let namespace = exemplars::Namespace {
+        prefix: prefix.clone(),
+        labels: labels.clone().unwrap_or_default(),
+    };
     let registry = prometheus::Registry::new_custom(prefix, labels).unwrap();
     let storage_registry = prometheus_metric_storage::StorageRegistry::new(registry);
-    REGISTRY.set(storage_registry).ok();
+    REGISTRY.set(storage_registry)?;
+    #[cfg(feature = "exemplars")]
+    NAMESPACE.set(namespace).ok();
+    Ok(())
 }
 
 /// Get the global instance of the metrics registry.
@@ -38,6 +72,19 @@ pub fn get_registry() -> &'static prometheus::Registry {
     get_storage_registry().registry()
 }
 
+/// Registers `metric` with the global metrics registry so that it gets served
+/// together with the metrics of all other components. Components that need
+/// an isolated registry (e.g. in tests) can still register their metrics with
+/// an explicit [`prometheus::Registry`].
+///
+/// Registering a metric whose name is already registered returns an error
+/// instead of panicking.
+pub fn register_default(
+    metric: impl prometheus::core::Collector + 'static,
+) -> prometheus::Result<()> {
+    get_registry().register(Box::new(metric))
+}
+
 /// Get the global instance of the metric storage registry.
 ///
 /// # Implementation notice
@@ -51,3 +98,409 @@ pub fn get_registry() -> &'static prometheus::Registry {
 pub fn get_storage_registry() -> &'static prometheus_metric_storage::StorageRegistry {
     REGISTRY.get_or_init(prometheus_metric_storage::StorageRegistry::default)
 }
+
+/// Starts a timer that records the elapsed seconds in a histogram when the
+/// returned guard gets dropped. The time gets recorded on every exit of the
+/// scope holding the guard, including early returns with `?` and panics.
+///
+/// Takes a [`prometheus::Histogram`] or a [`prometheus::HistogramVec`]
+/// followed by its label values:
+///
+/// ```ignore
+/// let _timer = observe::time_block!(metrics.solve_seconds);
+/// let _timer = observe::time_block!(metrics.estimate_seconds, "baseline", "sell");
+/// ```
+#[macro_export]
+macro_rules! time_block {
+    ($histogram:expr $(,)?) => {
+        $histogram.start_timer()
+    };
+    ($histogram:expr, $($label:expr),+ $(,)?) => {
+        $histogram.with_label_values(&[$($label),+]).start_timer()
+    };
+}
+
+/// Serves the metrics of the `registry` at `GET /metrics`. All other requests
+/// get a 404 response. Uses the global registry if no `registry` is passed.
+///
+/// With the `exemplars` feature the metrics get served in the OpenMetrics
+/// format including exemplars, see [`exemplars`]. Exemplars of a passed
+/// `registry` are only found if it has no prefix or common labels.
+#[cfg(feature = "server")]
+pub fn serve(address: SocketAddr, registry: Option<&prometheus::Registry>) -> JoinHandle<()> {
+    let registry = registry.cloned();
+    let make_service = make_service_fn(move |_| {
+        let registry = registry.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let response = handle_request(registry.as_ref(), &request);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+    tracing::info!(%address, "serving metrics");
+    tokio::spawn(async move {
+        let server = match hyper::Server::try_bind(&address) {
+            Ok(server) => server,
+            Err(err) => {
+                tracing::error!(?err, %address, "could not bind metrics server");
+                return;
+            }
+        };
+        if let Err(err) = server.serve(make_service).await {
+            tracing::error!(?err, "metrics server failed");
+        }
+    })
+}
+
+#[cfg(feature = "server")]
+fn handle_request(
+    registry: Option<&prometheus::Registry>,
+    request: &Request<Body>,
+) -> Response<Body> {
+    if request.method() != Method::GET || request.uri().path() != "/metrics" {
+        return status(StatusCode::NOT_FOUND);
+    }
+    // Exemplars are only supported by the OpenMetrics format.
+    #[cfg(feature = "exemplars")]
+    let (content_type, buffer) = {
+        let namespace = match registry {
+            Some(_) => Default::default(),
+            None => NAMESPACE.get().cloned().unwrap_or_default(),
+        };
+        let registry = registry.unwrap_or_else(get_registry);
+        (
+            exemplars::CONTENT_TYPE,
+            exemplars::encode(&registry.gather(), &namespace).into_bytes(),
+        )
+    };
+    #[cfg(not(feature = "exemplars"))]
+    let (content_type, buffer) = {
+        use prometheus::Encoder as _;
+        let registry = registry.unwrap_or_else(get_registry);
+        let mut buffer = Vec::new();
+        if let Err(err) = prometheus::TextEncoder::new().encode(&registry.gather(), &mut buffer) {
+            tracing::error!(?err, "could not encode metrics");
+            return status(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        (prometheus::TEXT_FORMAT, buffer)
+    };
+    Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .body(buffer.into())
+        .unwrap()
+}
+
+#[cfg(feature = "server")]
+fn status(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Registers metrics about the current process with the `registry`. The
+/// values get refreshed whenever the registry gets gathered so there is no
+/// background task polling them.
+///
+/// The `process_*` metrics (CPU time, resident memory, open file descriptors)
+/// are only available on Linux. On other platforms they are not registered.
+///
+/// The `tokio_*` metrics (worker threads, alive tasks, queue depths) are only
+/// available when compiling with `--cfg tokio_unstable` and calling this
+/// function from within a tokio runtime.
+#[cfg_attr(not(any(target_os = "linux", tokio_unstable)), allow(unused_variables))]
+pub fn register_process_metrics(registry: &prometheus::Registry) {
+    #[cfg(target_os = "linux")]
+    {
+        let result = process_metrics::Collector::new()
+            .and_then(|collector| registry.register(Box::new(collector)));
+        if let Err(err) = result {
+            tracing::warn!(?err, "failed to register process metrics");
+        }
+    }
+
+    #[cfg(tokio_unstable)]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        let result = tokio_metrics::Collector::new(handle)
+            .and_then(|collector| registry.register(Box::new(collector)));
+        if let Err(err) = result {
+            tracing::warn!(?err, "failed to register tokio runtime metrics");
+        }
+    }
+}
+
+/// Reads the metrics of the current process from `/proc` when gathered. The
+/// metric names follow the Prometheus conventions for process metrics.
+#[cfg(target_os = "linux")]
+mod process_metrics {
+    use prometheus::{
+        core::{Collector as _, Desc},
+        proto::MetricFamily,
+        Counter,
+        IntGauge,
+    };
+
+    /// The unit of CPU times in `/proc`. It is fixed to 100 on Linux
+    /// regardless of the kernel's internal tick rate.
+    const TICKS_PER_SECOND: f64 = 100.;
+
+    pub struct Collector {
+        cpu_seconds: Counter,
+        resident_memory: IntGauge,
+        virtual_memory: IntGauge,
+        open_fds: IntGauge,
+        threads: IntGauge,
+    }
+
+    impl Collector {
+        pub fn new() -> prometheus::Result<Self> {
+            Ok(Self {
+                cpu_seconds: Counter::new(
+                    "process_cpu_seconds_total",
+                    "Total user and system CPU time spent in seconds",
+                )?,
+                resident_memory: IntGauge::new(
+                    "process_resident_memory_bytes",
+                    "Resident memory size in bytes",
+                )?,
+                virtual_memory: IntGauge::new(
+                    "process_virtual_memory_bytes",
+                    "Virtual memory size in bytes",
+                )?,
+                open_fds: IntGauge::new("process_open_fds", "Number of open file descriptors")?,
+                threads: IntGauge::new("process_threads", "Number of OS threads")?,
+            })
+        }
+
+        fn update(&self) {
+            if let Some(cpu_seconds) = std::fs::read_to_string("/proc/self/stat")
+                .ok()
+                .and_then(|stat| cpu_seconds(&stat))
+            {
+                // Counters can only be incremented.
+                let delta = cpu_seconds - self.cpu_seconds.get();
+                if delta > 0. {
+                    self.cpu_seconds.inc_by(delta);
+                }
+            }
+            if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+                let kilobytes = |key| status_value(&status, key).map(|value| value * 1024);
+                if let Some(bytes) = kilobytes("VmRSS") {
+                    self.resident_memory.set(bytes as i64);
+                }
+                if let Some(bytes) = kilobytes("VmSize") {
+                    self.virtual_memory.set(bytes as i64);
+                }
+                if let Some(threads) = status_value(&status, "Threads") {
+                    self.threads.set(threads as i64);
+                }
+            }
+            if let Ok(fds) = std::fs::read_dir("/proc/self/fd") {
+                self.open_fds.set(fds.count() as i64);
+            }
+        }
+    }
+
+    impl prometheus::core::Collector for Collector {
+        fn desc(&self) -> Vec<&Desc> {
+            let mut descs = self.cpu_seconds.desc();
+            for gauge in [
+                &self.resident_memory,
+                &self.virtual_memory,
+                &self.open_fds,
+                &self.threads,
+            ] {
+                descs.extend(gauge.desc());
+            }
+            descs
+        }
+
+        fn collect(&self) -> Vec<MetricFamily> {
+            self.update();
+            let mut families = self.cpu_seconds.collect();
+            for gauge in [
+                &self.resident_memory,
+                &self.virtual_memory,
+                &self.open_fds,
+                &self.threads,
+            ] {
+                families.extend(gauge.collect());
+            }
+            families
+        }
+    }
+
+    /// Parses the user and system CPU time from the contents of
+    /// `/proc/<pid>/stat`.
+    fn cpu_seconds(stat: &str) -> Option<f64> {
+        // The process name in parentheses can contain spaces so fields are
+        // counted from its end. `utime` and `stime` are the 14th and 15th
+        // fields, the state following the name is the 3rd.
+        let (_, fields) = stat.rsplit_once(')')?;
+        let mut fields = fields.split_whitespace().skip(11);
+        let user: u64 = fields.next()?.parse().ok()?;
+        let system: u64 = fields.next()?.parse().ok()?;
+        Some((user + system) as f64 / TICKS_PER_SECOND)
+    }
+
+    /// Parses the numeric value of `key` from the contents of
+    /// `/proc/<pid>/status`, e.g. `VmRSS:	  1024 kB`.
+    fn status_value(status: &str, key: &str) -> Option<u64> {
+        status.lines().find_map(|line| {
+            let value = line.strip_prefix(key)?.strip_prefix(':')?;
+            value.split_whitespace().next()?.parse().ok()
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn parses_proc_files() {
+            let stat = "1234 (my (odd) name) S 1 1234 1234 0 -1 4194560 100 0 0 0 250 50 0 0 20 \
+                        0 8 0 100 1000 200";
+            assert_eq!(cpu_seconds(stat), Some(3.));
+
+            let status = "Name:\tservices\nVmSize:\t   2048 kB\nVmRSS:\t   1024 kB\nThreads:\t8\n";
+            assert_eq!(status_value(status, "VmRSS"), Some(1024));
+            assert_eq!(status_value(status, "VmSize"), Some(2048));
+            assert_eq!(status_value(status, "Threads"), Some(8));
+            assert_eq!(status_value(status, "Vm"), None);
+        }
+
+        #[test]
+        fn collects_metrics_of_current_process() {
+            let families = Collector::new().unwrap().collect();
+            let value = |name| {
+                families
+                    .iter()
+                    .find(|family| family.get_name() == name)
+                    .unwrap()
+                    .get_metric()[0]
+                    .get_gauge()
+                    .get_value()
+            };
+            assert!(value("process_resident_memory_bytes") > 0.);
+            assert!(value("process_open_fds") > 0.);
+            assert!(value("process_threads") >= 1.);
+        }
+    }
+}
+
+#[cfg(tokio_unstable)]
+mod tokio_metrics {
+    use prometheus::{
+        core::{Collector as _, Desc},
+        proto::MetricFamily,
+        IntGauge,
+    };
+
+    /// Reads the metrics of a tokio runtime when gathered.
+    pub struct Collector {
+        handle: tokio::runtime::Handle,
+        workers: IntGauge,
+        blocking_threads: IntGauge,
+        active_tasks: IntGauge,
+        injection_queue_depth: IntGauge,
+        blocking_queue_depth: IntGauge,
+    }
+
+    impl Collector {
+        pub fn new(handle: tokio::runtime::Handle) -> prometheus::Result<Self> {
+            Ok(Self {
+                handle,
+                workers: IntGauge::new("tokio_workers", "Number of worker threads")?,
+                blocking_threads: IntGauge::new(
+                    "tokio_blocking_threads",
+                    "Number of additional threads spawned for blocking tasks",
+                )?,
+                active_tasks: IntGauge::new("tokio_active_tasks", "Number of alive tasks")?,
+                injection_queue_depth: IntGauge::new(
+                    "tokio_injection_queue_depth",
+                    "Number of tasks scheduled from outside of the runtime",
+                )?,
+                blocking_queue_depth: IntGauge::new(
+                    "tokio_blocking_queue_depth",
+                    "Number of tasks waiting for a blocking thread",
+                )?,
+            })
+        }
+
+        fn gauges(&self) -> [&IntGauge; 5] {
+            [
+                &self.workers,
+                &self.blocking_threads,
+                &self.active_tasks,
+                &self.injection_queue_depth,
+                &self.blocking_queue_depth,
+            ]
+        }
+    }
+
+    impl prometheus::core::Collector for Collector {
+        fn desc(&self) -> Vec<&Desc> {
+            self.gauges()
+                .into_iter()
+                .flat_map(|gauge| gauge.desc())
+                .collect()
+        }
+
+        fn collect(&self) -> Vec<MetricFamily> {
+            let metrics = self.handle.metrics();
+            self.workers.set(metrics.num_workers() as i64);
+            self.blocking_threads
+                .set(metrics.num_blocking_threads() as i64);
+            self.active_tasks.set(metrics.active_tasks_count() as i64);
+            self.injection_queue_depth
+                .set(metrics.injection_queue_depth() as i64);
+            self.blocking_queue_depth
+                .set(metrics.blocking_queue_depth() as i64);
+            self.gauges()
+                .into_iter()
+                .flat_map(|gauge| gauge.collect())
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        prometheus::{HistogramOpts, HistogramVec, IntCounter},
+    };
+
+    #[test]
+    fn register_default_rejects_duplicates() {
+        let counter = || IntCounter::new("register_default_test", "test").unwrap();
+        register_default(counter()).unwrap();
+        assert!(register_default(counter()).is_err());
+        assert!(get_registry()
+            .gather()
+            .iter()
+            .any(|family| family.get_name() == "register_default_test"));
+    }
+
+    #[test]
+    fn time_block_records_on_early_return() {
+        let histogram =
+            HistogramVec::new(HistogramOpts::new("test_seconds", "test"), &["label"]).unwrap();
+        let fallible = |fail: bool| -> Result<(), ()> {
+            let _timer = crate::time_block!(histogram, "fallible");
+            if fail {
+                Err(())?;
+            }
+            Ok(())
+        };
+
+        fallible(false).unwrap();
+        fallible(true).unwrap_err();
+
+        let count = histogram
+            .with_label_values(&["fallible"])
+            .get_sample_count();
+        assert_eq!(count, 2);
+    }
+}
@@ -0,0 +1,344 @@
+//! Prometheus exemplars linking histogram observations to the request id of
+//! the task that made them, see [`crate::request_id`].
+//!
+//! The `prometheus` crate doesn't support exemplars so they get stored here
+//! and added to the metrics when encoding them in the OpenMetrics text format
+//! with [`encode`]. Only the most recent exemplar of every histogram bucket is
+//! kept.
+//!
+//! Exemplars are recorded under the histograms' own names and labels. A
+//! registry created with a prefix or common labels adds those when gathering
+//! the metrics, so [`encode`] needs the registry's [`Namespace`] to find the
+//! exemplars again.
+
+use {
+    once_cell::sync::Lazy,
+    prometheus::{
+        core::{Collector as _, Metric as _},
+        proto::{LabelPair, MetricFamily, MetricType},
+        Histogram,
+    },
+    std::{collections::HashMap, fmt::Write as _, sync::Mutex, time::SystemTime},
+};
+
+/// The content type of metrics encoded with [`encode`].
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+static EXEMPLARS: Lazy<Mutex<HashMap<Bucket, Exemplar>>> = Lazy::new(Default::default);
+
+/// The prefix and common labels that a registry adds to all metrics it
+/// gathers, see [`prometheus::Registry::new_custom`].
+#[derive(Clone, Debug, Default)]
+pub struct Namespace {
+    pub prefix: Option<String>,
+    pub labels: HashMap<String, String>,
+}
+
+impl Namespace {
+    /// Strips the prefix from a gathered metric name.
+    fn name<'a>(&self, name: &'a str) -> &'a str {
+        self.prefix
+            .as_ref()
+            .and_then(|prefix| name.strip_prefix(prefix.as_str())?.strip_prefix('_'))
+            .unwrap_or(name)
+    }
+
+    /// Removes the common labels from the labels of a gathered metric.
+    fn labels<'a>(&self, labels: &'a [LabelPair]) -> Vec<&'a LabelPair> {
+        labels
+            .iter()
+            .filter(|label| {
+                self.labels.get(label.get_name()).map(String::as_str) != Some(label.get_value())
+            })
+            .collect()
+    }
+}
+
+/// Identifies a bucket of a histogram.
+#[derive(Debug, Eq, Hash, PartialEq)]
+struct Bucket {
+    name: String,
+    labels: Vec<(String, String)>,
+    /// The bits of the bucket's upper bound.
+    upper_bound: u64,
+}
+
+impl Bucket {
+    fn new<'a>(
+        name: &str,
+        labels: impl IntoIterator<Item = &'a LabelPair>,
+        upper_bound: f64,
+    ) -> Self {
+        let mut labels = labels
+            .into_iter()
+            .map(|label| (label.get_name().to_owned(), label.get_value().to_owned()))
+            .collect::<Vec<_>>();
+        labels.sort();
+        Self {
+            name: name.to_owned(),
+            labels,
+            upper_bound: upper_bound.to_bits(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Exemplar {
+    request_id: String,
+    value: f64,
+    /// Seconds since the Unix epoch.
+    timestamp: f64,
+}
+
+/// Observes `value` in the `histogram` and, if the current task has a request
+/// id, records it as the exemplar of the bucket the value falls into. Without
+/// a request id this is the same as [`Histogram::observe`].
+pub fn observe(histogram: &Histogram, value: f64) {
+    histogram.observe(value);
+    let Some(request_id) = crate::request_id::get_task_local() else {
+        return;
+    };
+    let Some(desc) = histogram.desc().into_iter().next() else {
+        return;
+    };
+    let metric = histogram.metric();
+    let upper_bound = metric
+        .get_histogram()
+        .get_bucket()
+        .iter()
+        .map(|bucket| bucket.get_upper_bound())
+        .find(|upper_bound| value <= *upper_bound)
+        .unwrap_or(f64::INFINITY);
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    EXEMPLARS.lock().unwrap().insert(
+        Bucket::new(&desc.fq_name, metric.get_label(), upper_bound),
+        Exemplar {
+            request_id,
+            value,
+            timestamp,
+        },
+    );
+}
+
+/// Encodes the metric families in the OpenMetrics text format including the
+/// recorded exemplars. `namespace` has to be the one of the registry that
+/// gathered the `families`.
+pub fn encode(families: &[MetricFamily], namespace: &Namespace) -> String {
+    let exemplars = EXEMPLARS.lock().unwrap();
+    let mut output = String::new();
+    for family in families {
+        let name = family.get_name();
+        let (family_name, kind) = match family.get_field_type() {
+            // OpenMetrics counter families don't have the `_total` suffix of
+            // their samples.
+            MetricType::COUNTER => (name.strip_suffix("_total").unwrap_or(name), "counter"),
+            MetricType::GAUGE => (name, "gauge"),
+            MetricType::HISTOGRAM => (name, "histogram"),
+            MetricType::SUMMARY => (name, "summary"),
+            MetricType::UNTYPED => (name, "unknown"),
+        };
+        writeln!(output, "# TYPE {family_name} {kind}").unwrap();
+        writeln!(output, "# HELP {family_name} {}", escape(family.get_help())).unwrap();
+        for metric in family.get_metric() {
+            let labels = metric.get_label();
+            match family.get_field_type() {
+                MetricType::COUNTER => {
+                    let value = metric.get_counter().get_value();
+                    sample(
+                        &mut output,
+                        &format!("{family_name}_total"),
+                        labels,
+                        None,
+                        value,
+                    );
+                }
+                MetricType::GAUGE => {
+                    sample(
+                        &mut output,
+                        name,
+                        labels,
+                        None,
+                        metric.get_gauge().get_value(),
+                    );
+                }
+                MetricType::UNTYPED => {
+                    sample(
+                        &mut output,
+                        name,
+                        labels,
+                        None,
+                        metric.get_untyped().get_value(),
+                    );
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    let own_name = namespace.name(name);
+                    let own_labels = namespace.labels(labels);
+                    let mut upper_bounds = histogram
+                        .get_bucket()
+                        .iter()
+                        .map(|bucket| (bucket.get_upper_bound(), bucket.get_cumulative_count()))
+                        .collect::<Vec<_>>();
+                    if upper_bounds.last().map(|(upper_bound, _)| *upper_bound)
+                        != Some(f64::INFINITY)
+                    {
+                        upper_bounds.push((f64::INFINITY, histogram.get_sample_count()));
+                    }
+                    for (upper_bound, count) in upper_bounds {
+                        let le = ("le", float(upper_bound));
+                        sample(
+                            &mut output,
+                            &format!("{name}_bucket"),
+                            labels,
+                            Some(le),
+                            count as f64,
+                        );
+                        let bucket = Bucket::new(own_name, own_labels.iter().copied(), upper_bound);
+                        if let Some(exemplar) = exemplars.get(&bucket) {
+                            // Replace the newline of the sample.
+                            output.pop();
+                            writeln!(
+                                output,
+                                " # {{request_id=\"{}\"}} {} {}",
+                                escape(&exemplar.request_id),
+                                float(exemplar.value),
+                                exemplar.timestamp
+                            )
+                            .unwrap();
+                        }
+                    }
+                    let sum = histogram.get_sample_sum();
+                    sample(&mut output, &format!("{name}_sum"), labels, None, sum);
+                    let count = histogram.get_sample_count() as f64;
+                    sample(&mut output, &format!("{name}_count"), labels, None, count);
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        let label = ("quantile", float(quantile.get_quantile()));
+                        sample(&mut output, name, labels, Some(label), quantile.get_value());
+                    }
+                    let sum = summary.get_sample_sum();
+                    sample(&mut output, &format!("{name}_sum"), labels, None, sum);
+                    let count = summary.get_sample_count() as f64;
+                    sample(&mut output, &format!("{name}_count"), labels, None, count);
+                }
+            }
+        }
+    }
+    output.push_str("# EOF\n");
+    output
+}
+
+/// Writes a sample line. `extra` is an additional label like a bucket's `le`.
+fn sample(
+    output: &mut String,
+    name: &str,
+    labels: &[LabelPair],
+    extra: Option<(&str, String)>,
+    value: f64,
+) {
+    let labels = labels
+        .iter()
+        .map(|label| (label.get_name(), label.get_value().to_owned()))
+        .chain(extra)
+        .map(|(name, value)| format!("{name}=\"{}\"", escape(&value)))
+        .collect::<Vec<_>>();
+    if labels.is_empty() {
+        writeln!(output, "{name} {}", float(value)).unwrap();
+    } else {
+        writeln!(output, "{name}{{{}}} {}", labels.join(","), float(value)).unwrap();
+    }
+}
+
+fn float(value: f64) -> String {
+    if value == f64::INFINITY {
+        "+Inf".to_owned()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_owned()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, prometheus::HistogramOpts};
+
+    #[tokio::test]
+    async fn records_request_id_exemplars() {
+        let histogram = Histogram::with_opts(
+            HistogramOpts::new("exemplar_test_seconds", "test").buckets(vec![0.1, 1.]),
+        )
+        .unwrap();
+        let registry = prometheus::Registry::new();
+        registry.register(Box::new(histogram.clone())).unwrap();
+
+        observe(&histogram, 0.05);
+        crate::request_id::set_task_local("request-1".to_owned(), async {
+            observe(&histogram, 0.5);
+        })
+        .await;
+
+        let output = encode(&registry.gather(), &Namespace::default());
+        assert!(output.contains("exemplar_test_seconds_bucket{le=\"0.1\"} 1\n"));
+        assert!(output.contains(
+            "exemplar_test_seconds_bucket{le=\"1\"} 2 # {request_id=\"request-1\"} 0.5 "
+        ));
+        assert!(output.contains("exemplar_test_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(output.contains("exemplar_test_seconds_count 2\n"));
+        assert!(output.ends_with("# EOF\n"));
+    }
+
+    #[tokio::test]
+    async fn records_exemplars_of_prefixed_registries() {
+        let histogram = prometheus::HistogramVec::new(
+            HistogramOpts::new("exemplar_prefix_test_seconds", "test").buckets(vec![1.]),
+            &["kind"],
+        )
+        .unwrap();
+        let namespace = Namespace {
+            prefix: Some("service".to_owned()),
+            labels: HashMap::from([("network".to_owned(), "mainnet".to_owned())]),
+        };
+        let registry = prometheus::Registry::new_custom(
+            namespace.prefix.clone(),
+            Some(namespace.labels.clone()),
+        )
+        .unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+
+        crate::request_id::set_task_local("request-2".to_owned(), async {
+            observe(&histogram.with_label_values(&["a"]), 0.5);
+        })
+        .await;
+
+        let output = encode(&registry.gather(), &namespace);
+        assert!(output.contains(
+            "service_exemplar_prefix_test_seconds_bucket{kind=\"a\",network=\"mainnet\",le=\"1\"} \
+             1 # {request_id=\"request-2\"} 0.5 "
+        ));
+    }
+
+    #[test]
+    fn encodes_counters_without_total_suffix_in_family_name() {
+        let counter = prometheus::IntCounter::new("exemplar_test_total", "test").unwrap();
+        counter.inc();
+        let registry = prometheus::Registry::new();
+        registry.register(Box::new(counter)).unwrap();
+
+        let output = encode(&registry.gather(), &Namespace::default());
+        assert!(output.contains("# TYPE exemplar_test counter\n"));
+        assert!(output.contains("exemplar_test_total 1\n"));
+    }
+}
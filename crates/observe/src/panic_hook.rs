@@ -1,3 +1,16 @@
+use {
+    prometheus::{IntCounterVec, Opts},
+    std::{
+        backtrace::Backtrace,
+        fs::{self, OpenOptions},
+        io::Write as _,
+        panic::PanicInfo,
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicU64, Ordering},
+        time::SystemTime,
+    },
+};
+
 /// Install a panic hook that first calls the previous panic hook and then exits
 /// the process.
 ///
@@ -12,19 +25,233 @@ pub fn install() {
     let previous_hook = std::panic::take_hook();
     let new_hook = move |info: &std::panic::PanicInfo| {
         previous_hook(info);
+        // Exiting the process skips destructors so make sure the logs of the
+        // panic don't get lost.
+        crate::tracing::flush();
         std::process::exit(1);
     };
     std::panic::set_hook(Box::new(new_hook));
 }
 
+/// Install a panic hook that logs panics with tracing and then calls `next`.
+///
+/// Unlike the hook installed by [`crate::tracing::initialize`] this doesn't
+/// replace hooks installed by others, for example by test frameworks, as long
+/// as they get passed in as `next`. Our logging always runs first, then `next`.
+/// Like [`install_with_metrics`] this does not exit the process.
+pub fn install_chained(next: Box<dyn Fn(&PanicInfo) + Sync + Send>) {
+    std::panic::set_hook(Box::new(move |info: &PanicInfo| {
+        crate::tracing::log_panic(info);
+        next(info);
+    }));
+}
+
+/// Install a panic hook that increments the `panics_total` metric labelled by
+/// the source file of the panic and then calls the previous panic hook.
+///
+/// Unlike [`install`] this does not exit the process so panics that get caught
+/// (e.g. by tokio tasks) are counted, too. Call [`install`] afterwards to also
+/// exit the process on panics.
+pub fn install_with_metrics(registry: &prometheus::Registry) {
+    let panics = IntCounterVec::new(
+        Opts::new("panics_total", "Number of panics by source file."),
+        &["file"],
+    )
+    .unwrap();
+    if let Err(err) = registry.register(Box::new(panics.clone())) {
+        tracing::warn!(?err, "failed to register panic metrics");
+    }
+    let previous_hook = std::panic::take_hook();
+    let new_hook = move |info: &std::panic::PanicInfo| {
+        let file = info
+            .location()
+            .map_or("unknown", |location| location.file());
+        // Panicking inside of the panic hook aborts the process so we use the
+        // fallible getter instead of `with_label_values`.
+        if let Ok(counter) = panics.get_metric_with_label_values(&[file]) {
+            counter.inc();
+        }
+        previous_hook(info);
+    };
+    std::panic::set_hook(Box::new(new_hook));
+}
+
+/// Install a panic hook that writes the message, location and backtrace of
+/// panics to a `crash-<timestamp>-<n>.txt` file in `dir` and then calls the
+/// previous panic hook. The directory gets created if it doesn't exist.
+///
+/// Useful for crash forensics on machines without a log collector. Writing the
+/// file is best-effort, failures only get logged. Like
+/// [`install_with_metrics`] this does not exit the process.
+pub fn install_with_crash_dir(dir: impl Into<PathBuf>) {
+    let dir = dir.into();
+    let previous_hook = std::panic::take_hook();
+    let new_hook = move |info: &PanicInfo| {
+        if let Err(err) = write_crash_file(&dir, info) {
+            tracing::warn!(?err, ?dir, "failed to write crash file");
+        }
+        previous_hook(info);
+    };
+    std::panic::set_hook(Box::new(new_hook));
+}
+
+fn write_crash_file(dir: &Path, info: &PanicInfo) -> std::io::Result<()> {
+    // Distinguishes files of panics happening in the same millisecond.
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    fs::create_dir_all(dir)?;
+    // Every panic gets its own file so concurrent panics can't interleave.
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dir.join(format!("crash-{timestamp}-{n}.txt")))?;
+
+    let thread = std::thread::current();
+    let payload = info.payload();
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        *message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "Box<dyn Any>"
+    };
+    writeln!(file, "thread: {}", thread.name().unwrap_or("<unnamed>"))?;
+    if let Some(location) = info.location() {
+        writeln!(file, "location: {location}")?;
+    }
+    writeln!(file, "message: {message}")?;
+    writeln!(file, "backtrace:\n{}", Backtrace::force_capture())?;
+    file.sync_all()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use {
+        super::*,
+        std::sync::{atomic::AtomicBool, Mutex, MutexGuard, PoisonError},
+    };
+
+    /// The panic hook is global, so tests installing hooks must not run
+    /// concurrently and have to restore the previous hook when done.
+    struct HookGuard {
+        previous: Option<Box<dyn Fn(&PanicInfo) + Sync + Send>>,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl HookGuard {
+        fn lock() -> Self {
+            static LOCK: Mutex<()> = Mutex::new(());
+            let lock = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+            Self {
+                previous: Some(std::panic::take_hook()),
+                _lock: lock,
+            }
+        }
+    }
+
+    impl Drop for HookGuard {
+        fn drop(&mut self) {
+            // Setting the hook while panicking aborts the process.
+            if let (Some(previous), false) = (self.previous.take(), std::thread::panicking()) {
+                std::panic::set_hook(previous);
+            }
+        }
+    }
+
+    #[test]
+    fn counts_caught_panics() {
+        let _hook = HookGuard::lock();
+        let registry = prometheus::Registry::new();
+        install_with_metrics(&registry);
+
+        assert!(std::panic::catch_unwind(|| panic!("caught panic")).is_err());
+
+        let panics = registry
+            .gather()
+            .into_iter()
+            .find(|family| family.get_name() == "panics_total")
+            .unwrap();
+        let counter = panics
+            .get_metric()
+            .iter()
+            .find(|metric| metric.get_label()[0].get_value() == file!())
+            .unwrap();
+        assert_eq!(counter.get_counter().get_value(), 1.);
+    }
+
+    #[test]
+    fn writes_crash_files() {
+        let dir = std::env::temp_dir().join(format!("crashes-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let _hook = HookGuard::lock();
+        install_with_crash_dir(&dir);
+
+        let threads = ["first", "second"].map(|message| {
+            std::thread::spawn(move || {
+                assert!(std::panic::catch_unwind(|| panic!("crash {message}")).is_err());
+            })
+        });
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Other tests might panic concurrently so only look at our crashes.
+        let crashes = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .filter(|crash| crash.contains("message: crash "))
+            .collect::<Vec<_>>();
+        assert_eq!(crashes.len(), 2);
+        for message in ["crash first", "crash second"] {
+            let crash = crashes
+                .iter()
+                .find(|crash| crash.contains(message))
+                .unwrap();
+            assert_eq!(crash.matches("message:").count(), 1);
+            assert!(crash.contains(&format!("location: {}", file!())));
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn chained_hooks_run_after_logging() {
+        static SENTINEL: AtomicBool = AtomicBool::new(false);
+        let _hook = HookGuard::lock();
+        let previous_hook = std::panic::take_hook();
+        let sentinel = move |info: &PanicInfo| {
+            // Other tests might panic concurrently so only look at our panic.
+            if info.to_string().contains("chained panic") {
+                SENTINEL.store(true, Ordering::SeqCst);
+            }
+            previous_hook(info);
+        };
+        install_chained(Box::new(sentinel));
+
+        let logs = crate::tracing::LogBuffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(logs.clone())
+            .with_ansi(false)
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(std::panic::catch_unwind(|| panic!("chained panic")).is_err());
+        });
+
+        assert!(logs.contains("chained panic"));
+        assert!(SENTINEL.load(Ordering::SeqCst));
+    }
 
     #[test]
     #[ignore]
     fn manual_thread() {
-        crate::tracing::initialize("info", tracing::level_filters::LevelFilter::OFF);
+        crate::tracing::initialize(
+            &crate::tracing::Config::new("info")
+                .with_stderr_threshold(tracing::level_filters::LevelFilter::OFF),
+        );
 
         // Should print panic trace log but not kill the process.
         let handle = std::thread::spawn(|| panic!("you should see this message"));
@@ -42,7 +269,10 @@ mod tests {
     #[tokio::test(flavor = "multi_thread")]
     #[ignore]
     async fn manual_tokio() {
-        crate::tracing::initialize("info", tracing::level_filters::LevelFilter::OFF);
+        crate::tracing::initialize(
+            &crate::tracing::Config::new("info")
+                .with_stderr_threshold(tracing::level_filters::LevelFilter::OFF),
+        );
 
         let handle = tokio::task::spawn(async { panic!("you should see this message") });
         assert!(handle.await.is_err());
@@ -16,18 +16,122 @@
 //! And when we issue requests to another process we can simply fetch the
 //! current identifier specific to our task and send that along with the
 //! request.
+use {
+    futures::future::BoxFuture,
+    http::{header::HeaderName, HeaderValue, Request, Response},
+    std::{
+        future::Future,
+        task::{Context, Poll},
+    },
+    tokio::task::JoinHandle,
+    tower::{Layer, Service},
+    tracing::Instrument,
+    uuid::Uuid,
+};
+
 tokio::task_local! {
     pub static REQUEST_ID: String;
 }
 
 /// Tries to read the `request_id` from this task's storage.
 /// Returns `None` if task local storage was not initialized or is empty.
-pub fn get_task_local_storage() -> Option<String> {
-    let mut id = None;
-    let _ = REQUEST_ID.try_with(|cell| {
-        id = Some(cell.clone());
-    });
-    id
+pub fn get_task_local() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Runs the `future` with the `request_id` stored in its task local storage and
+/// attaches it to all logs emitted by the `future` with a tracing span.
+pub fn set_task_local<F: Future>(id: String, future: F) -> impl Future<Output = F::Output> {
+    let span = tracing::info_span!("request", id);
+    REQUEST_ID.scope(id, future).instrument(span)
+}
+
+/// Like `tokio::spawn` but the spawned task inherits the `request_id` of the
+/// current task if there is one.
+pub fn spawn_with_request_id<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match get_task_local() {
+        Some(id) => tokio::spawn(set_task_local(id, future)),
+        None => tokio::spawn(future),
+    }
+}
+
+/// `tower::Layer` that runs every request with a request id stored in task
+/// local storage.
+///
+/// The id gets taken from the configured request header (`X-Request-Id` by
+/// default) or a new UUIDv4 gets
+/// generated if the header is missing. The id also gets written to the same
+/// header of the response so callers can correlate their requests with our
+/// logs.
+#[derive(Clone, Debug)]
+pub struct RequestIdLayer {
+    header: HeaderName,
+}
+
+impl RequestIdLayer {
+    pub fn new(header: HeaderName) -> Self {
+        Self { header }
+    }
+}
+
+impl Default for RequestIdLayer {
+    fn default() -> Self {
+        Self::new(HeaderName::from_static("x-request-id"))
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdService {
+            inner,
+            header: self.header.clone(),
+        }
+    }
+}
+
+/// Service created by [`RequestIdLayer`].
+#[derive(Clone, Debug)]
+pub struct RequestIdService<S> {
+    inner: S,
+    header: HeaderName,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RequestIdService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+    type Response = S::Response;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let id = request
+            .headers()
+            .get(&self.header)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let header = self.header.clone();
+        let response = set_task_local(id.clone(), self.inner.call(request));
+        Box::pin(async move {
+            let mut response = response.await?;
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                response.headers_mut().insert(header, value);
+            }
+            Ok(response)
+        })
+    }
 }
 
 /// Takes a `tower::Service` and embeds it in a `make_service` function that
@@ -57,10 +161,10 @@ macro_rules! make_service_with_task_local_storage {
                                         .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
                                 )
                             };
-                            let span = tracing::info_span!("request", id);
-                            let handle_request = observe::request_id::REQUEST_ID
-                                .scope(id, hyper::service::Service::call(&mut warp_svc, req));
-                            tracing::Instrument::instrument(handle_request, span)
+                            observe::request_id::set_task_local(
+                                id,
+                                hyper::service::Service::call(&mut warp_svc, req),
+                            )
                         });
                     Ok::<_, std::convert::Infallible>(svc)
                 }
@@ -68,3 +172,57 @@ macro_rules! make_service_with_task_local_storage {
         }
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spawned_tasks_inherit_request_id() {
+        let id = set_task_local("1234".to_string(), async {
+            spawn_with_request_id(async {
+                spawn_with_request_id(async { get_task_local() })
+                    .await
+                    .unwrap()
+            })
+            .await
+            .unwrap()
+        })
+        .await;
+        assert_eq!(id.as_deref(), Some("1234"));
+    }
+
+    #[tokio::test]
+    async fn spawned_tasks_without_request_id() {
+        let id = spawn_with_request_id(async { get_task_local() })
+            .await
+            .unwrap();
+        assert_eq!(id, None);
+    }
+
+    #[tokio::test]
+    async fn request_id_layer_uses_header() {
+        let request = Request::builder()
+            .header("X-Request-Id", "1234")
+            .body(())
+            .unwrap();
+        let service = RequestIdLayer::default().layer(tower::service_fn(|_: Request<()>| async {
+            Ok::<_, std::convert::Infallible>(Response::new(get_task_local()))
+        }));
+        let response = tower::ServiceExt::oneshot(service, request).await.unwrap();
+        assert_eq!(response.body().as_deref(), Some("1234"));
+        assert_eq!(response.headers()["X-Request-Id"], "1234");
+    }
+
+    #[tokio::test]
+    async fn request_id_layer_generates_id() {
+        let request = Request::builder().body(()).unwrap();
+        let service = RequestIdLayer::default().layer(tower::service_fn(|_: Request<()>| async {
+            Ok::<_, std::convert::Infallible>(Response::new(get_task_local()))
+        }));
+        let response = tower::ServiceExt::oneshot(service, request).await.unwrap();
+        let id = response.body().clone().unwrap();
+        assert!(Uuid::parse_str(&id).is_ok());
+        assert_eq!(response.headers()["X-Request-Id"], id.as_str());
+    }
+}
@@ -0,0 +1,116 @@
+//! Graceful shutdown on termination signals.
+//!
+//! Binaries can race their main loop against [`wait_for_shutdown`]:
+//!
+//! ```ignore
+//! tokio::select! {
+//!     _ = run_main_loop() => {}
+//!     _ = observe::shutdown::wait_for_shutdown() => {}
+//! }
+//! ```
+
+use {
+    futures::future,
+    std::sync::Mutex,
+    tokio::signal::unix::{self, SignalKind},
+};
+
+/// A signal that triggers a shutdown.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Signal {
+    /// `SIGTERM`, for example sent by container orchestrators.
+    Terminate,
+    /// `SIGINT`, for example sent by pressing Ctrl+C.
+    Interrupt,
+    /// `SIGHUP`, sent when the controlling terminal gets closed.
+    Hangup,
+}
+
+impl Signal {
+    fn kind(self) -> SignalKind {
+        match self {
+            Self::Terminate => SignalKind::terminate(),
+            Self::Interrupt => SignalKind::interrupt(),
+            Self::Hangup => SignalKind::hangup(),
+        }
+    }
+}
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+static CALLBACKS: Mutex<Vec<Callback>> = Mutex::new(Vec::new());
+
+/// Registers a callback that runs once a shutdown gets triggered, for example
+/// to flush buffered data. Callbacks run in the order they were registered.
+pub fn on_shutdown(callback: impl FnOnce() + Send + 'static) {
+    CALLBACKS.lock().unwrap().push(Box::new(callback));
+}
+
+/// Waits for `SIGTERM` or `SIGINT`. See [`wait_for_signals`].
+pub async fn wait_for_shutdown() {
+    wait_for_signals(&[Signal::Terminate, Signal::Interrupt]).await
+}
+
+/// Waits until one of the `signals` is received, then runs the registered
+/// shutdown callbacks and flushes buffered logs before returning.
+///
+/// Receiving a second signal afterwards exits the process immediately in case
+/// the graceful shutdown gets stuck.
+///
+/// # Panics
+///
+/// Panics if `signals` is empty or the signal handlers can't be installed.
+pub async fn wait_for_signals(signals: &[Signal]) {
+    assert!(!signals.is_empty(), "no shutdown signals configured");
+    let mut streams = signals
+        .iter()
+        .map(|signal| unix::signal(signal.kind()).expect("failed to install signal handler"))
+        .collect::<Vec<_>>();
+
+    let received = recv_any(&mut streams).await;
+    tracing::info!(signal = ?signals[received], "received shutdown signal");
+    let signals = signals.to_vec();
+    tokio::spawn(async move {
+        let received = recv_any(&mut streams).await;
+        tracing::warn!(signal = ?signals[received], "received second shutdown signal; exiting");
+        crate::tracing::flush();
+        std::process::exit(1);
+    });
+
+    run_callbacks();
+}
+
+/// Returns the index of the stream that received a signal first.
+async fn recv_any(streams: &mut [unix::Signal]) -> usize {
+    let (_, index, _) =
+        future::select_all(streams.iter_mut().map(|stream| Box::pin(stream.recv()))).await;
+    index
+}
+
+fn run_callbacks() {
+    let callbacks = std::mem::take(&mut *CALLBACKS.lock().unwrap());
+    for callback in callbacks {
+        callback();
+    }
+    crate::tracing::flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    #[test]
+    fn callbacks_run_once() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        on_shutdown(|| {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+        });
+
+        run_callbacks();
+        run_callbacks();
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    }
+}
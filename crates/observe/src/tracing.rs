@@ -1,56 +1,635 @@
+mod buffer;
+mod file;
+mod redaction;
+mod sampling;
+mod sink;
+mod span_timing;
+
 use {
-    std::{panic::PanicInfo, sync::Once},
+    anyhow::{Context, Result},
+    serde_json::{Map, Value},
+    std::{
+        backtrace::{Backtrace, BacktraceStatus},
+        fmt::{self, Debug},
+        io::Write as _,
+        panic::PanicInfo,
+        str::FromStr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+            Once,
+        },
+    },
     time::macros::format_description,
-    tracing::level_filters::LevelFilter,
-    tracing_subscriber::fmt::{time::UtcTime, writer::MakeWriterExt as _},
+    tracing::{field::Field, level_filters::LevelFilter, span::Record, Event, Subscriber},
+    tracing_subscriber::{
+        field::RecordFields,
+        fmt::{
+            format::{FormatEvent, FormatFields, Writer},
+            time::{FormatTime, UtcTime},
+            writer::MakeWriterExt as _,
+            FmtContext,
+            FormattedFields,
+            MakeWriter,
+        },
+        prelude::*,
+        registry::LookupSpan,
+        reload,
+        EnvFilter,
+        Layer,
+        Registry,
+    },
+};
+pub use {
+    buffer::LogBuffer,
+    file::{FileConfig, Rotation},
+    redaction::Redaction,
+    sampling::Sampling,
+    sink::Sink,
+    span_timing::SpanTiming,
 };
 
+/// Configuration of the tracing setup that is shared between the binaries.
+#[derive(Clone, Debug)]
+pub struct Config {
+    env_filter: String,
+    stderr_threshold: LevelFilter,
+    format: LogFormat,
+    stdout: bool,
+    file: Option<FileConfig>,
+    capture: Option<LogBuffer>,
+    sinks: Vec<Sink>,
+    span_timing: Option<SpanTiming>,
+    sampling: Option<Sampling>,
+    redaction: Option<Redaction>,
+    timer: Timer,
+    single_line_backtraces: bool,
+    ansi: Option<bool>,
+}
+
+impl Config {
+    /// Creates a new configuration with the specified filter.
+    /// `env_filter` has similar syntax to env_logger. It is documented at
+    /// https://docs.rs/tracing-subscriber/0.2.15/tracing_subscriber/filter/struct.EnvFilter.html
+    ///
+    /// By default logs are written to stdout in the plain format and only
+    /// errors are written to stderr.
+    pub fn new(env_filter: &str) -> Self {
+        Self {
+            env_filter: env_filter.to_owned(),
+            stderr_threshold: LevelFilter::ERROR,
+            format: LogFormat::default(),
+            stdout: true,
+            file: None,
+            capture: None,
+            sinks: Vec::new(),
+            span_timing: None,
+            sampling: None,
+            redaction: None,
+            timer: Timer::default(),
+            single_line_backtraces: false,
+            ansi: None,
+        }
+    }
+
+    /// Log events at or above this level get written to stderr instead of
+    /// stdout. [`LevelFilter::OFF`] writes everything to stdout.
+    pub fn with_stderr_threshold(mut self, stderr_threshold: LevelFilter) -> Self {
+        self.stderr_threshold = stderr_threshold;
+        self
+    }
+
+    /// Sets the format in which log events get written.
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets whether logs get written to stdout and stderr.
+    pub fn with_stdout(mut self, stdout: bool) -> Self {
+        self.stdout = stdout;
+        self
+    }
+
+    /// Additionally writes logs to rotating files.
+    pub fn with_file(mut self, file: FileConfig) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    /// Additionally writes logs to the `buffer`.
+    pub fn with_capture(mut self, buffer: LogBuffer) -> Self {
+        self.capture = Some(buffer);
+        self
+    }
+
+    /// Additionally writes logs to `sink` in its own format and filtered by
+    /// its own filter. Can be called multiple times to add several sinks.
+    pub fn with_sink(mut self, sink: Sink) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Additionally records how long the spans watched by `span_timing` were
+    /// open. Log output is unaffected.
+    pub fn with_span_timing(mut self, span_timing: SpanTiming) -> Self {
+        self.span_timing = Some(span_timing);
+        self
+    }
+
+    /// Only keeps a random share of the log events of the targets configured
+    /// in `sampling`.
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = Some(sampling);
+        self
+    }
+
+    /// Redacts addresses, hashes and order uids in all log outputs, see
+    /// [`Redaction`]. Disabled by default.
+    pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = Some(redaction);
+        self
+    }
+
+    /// Formats the timestamps of log events with `timer` instead of the
+    /// current UTC time, e.g. to get stable log output in tests.
+    pub fn with_timer(mut self, timer: impl FormatTime + Send + Sync + 'static) -> Self {
+        self.timer = Timer(Some(Arc::new(timer)));
+        self
+    }
+
+    /// Sets whether the backtraces of panics get logged on a single line
+    /// instead of one line per frame.
+    pub fn with_single_line_backtraces(mut self, single_line_backtraces: bool) -> Self {
+        self.single_line_backtraces = single_line_backtraces;
+        self
+    }
+
+    /// Forces colored (ANSI) plain log output on or off. By default colors
+    /// are only used if stdout is a terminal, so logs redirected to files or
+    /// pipes don't contain escape sequences.
+    pub fn with_ansi(mut self, ansi: bool) -> Self {
+        self.ansi = Some(ansi);
+        self
+    }
+
+    fn ansi(&self) -> bool {
+        self.ansi.unwrap_or_else(|| atty::is(atty::Stream::Stdout))
+    }
+}
+
+/// The format in which log events get written.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LogFormat {
+    /// Human readable log lines.
+    #[default]
+    Plain,
+    /// Newline delimited JSON objects containing the `timestamp`, `level`,
+    /// `target` and `message` of the event as well as the fields of the
+    /// event and all its parent spans.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Self::Plain),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown log format {s:?}, expected 'plain' or 'json'"
+            )),
+        }
+    }
+}
+
+/// Handle to change the log filter of the process at runtime.
+///
+/// This allows increasing the verbosity of a running instance without having
+/// to restart it. For example an admin endpoint could be wired up like this:
+///
+/// ```ignore
+/// let (handle, _guard) = observe::tracing::initialize(&config);
+/// let route = warp::path!("log_filter")
+///     .and(warp::put())
+///     .and(warp::body::bytes())
+///     .map(move |body: bytes::Bytes| {
+///         let filter = String::from_utf8_lossy(&body);
+///         match handle.set_filter(&filter) {
+///             Ok(()) => warp::http::StatusCode::OK,
+///             Err(_) => warp::http::StatusCode::BAD_REQUEST,
+///         }
+///     });
+/// ```
+#[derive(Clone)]
+pub struct ReloadHandle(reload::Handle<EnvFilter, Registry>);
+
+impl ReloadHandle {
+    /// Replaces the current log filter. `filter` uses the same syntax as
+    /// [`Config::new`]. If the filter is invalid the current filter stays
+    /// active.
+    pub fn set_filter(&self, filter: &str) -> Result<()> {
+        let filter = EnvFilter::try_new(filter).context("invalid log filter")?;
+        self.0.reload(filter).context("failed to reload log filter")
+    }
+}
+
+/// Flushes buffered log output when dropped.
+///
+/// Keep it alive for as long as logs get written so the last log lines don't
+/// get lost when the process shuts down.
+pub struct FlushGuard(());
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        flush();
+    }
+}
+
+/// Flushes all buffered log output.
+pub(crate) fn flush() {
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+}
+
 /// Initializes tracing setup that is shared between the binaries.
-/// `env_filter` has similar syntax to env_logger. It is documented at
-/// https://docs.rs/tracing-subscriber/0.2.15/tracing_subscriber/filter/struct.EnvFilter.html
-pub fn initialize(env_filter: &str, stderr_threshold: LevelFilter) {
-    set_tracing_subscriber(env_filter, stderr_threshold);
-    std::panic::set_hook(Box::new(tracing_panic_hook));
+///
+/// Also replaces the panic hook with one that only logs panics, which keeps
+/// them on a single line for log ingestion. Use
+/// [`crate::panic_hook::install_chained`] to keep another hook running.
+///
+/// Returns a handle that can be used to change the log filter at runtime and a
+/// guard that flushes buffered logs when dropped. Both can be ignored.
+pub fn initialize(config: &Config) -> (ReloadHandle, FlushGuard) {
+    let handle = set_tracing_subscriber(config);
+    SINGLE_LINE_BACKTRACES.store(config.single_line_backtraces, Ordering::Relaxed);
+    std::panic::set_hook(Box::new(log_panic));
+    (handle, FlushGuard(()))
 }
 
 /// Like [`initialize`], but can be called multiple times in a row. Later calls
 /// are ignored.
 ///
-/// Useful for tests.
-pub fn initialize_reentrant(env_filter: &str) {
+/// Useful for tests. The returned guard should be held for the duration of the
+/// test.
+pub fn initialize_reentrant(config: &Config) -> FlushGuard {
     // The tracing subscriber below is global object so initializing it again in the
     // same process by a different thread would fail.
     static ONCE: Once = Once::new();
-    ONCE.call_once(|| set_tracing_subscriber(env_filter, LevelFilter::ERROR));
+    ONCE.call_once(|| {
+        set_tracing_subscriber(config);
+    });
+    FlushGuard(())
 }
 
-fn set_tracing_subscriber(env_filter: &str, stderr_threshold: LevelFilter) {
-    // This is what kibana uses to separate multi line log messages.
-    let subscriber_builder = tracing_subscriber::fmt::fmt()
-        .with_timer(UtcTime::new(format_description!(
-            "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z"
-        )))
-        .with_env_filter(env_filter)
-        .with_ansi(atty::is(atty::Stream::Stdout));
-    match stderr_threshold.into_level() {
-        Some(threshold) => subscriber_builder
-            .with_writer(
+fn set_tracing_subscriber(config: &Config) -> ReloadHandle {
+    let ansi = config.ansi();
+    let redaction = &config.redaction;
+    let timer = &config.timer;
+    let stdout_layer = config
+        .stdout
+        .then(|| match config.stderr_threshold.into_level() {
+            Some(threshold) => fmt_layer(
+                config.format,
                 std::io::stderr
                     .with_max_level(threshold)
                     .or_else(std::io::stdout),
-            )
-            .init(),
-        None => subscriber_builder.init(),
+                ansi,
+                redaction.clone(),
+                timer.clone(),
+            ),
+            None => fmt_layer(
+                config.format,
+                std::io::stdout,
+                ansi,
+                redaction.clone(),
+                timer.clone(),
+            ),
+        });
+    let file_layer = config.file.as_ref().map(|file| {
+        fmt_layer(
+            config.format,
+            file::FileWriter::new(file),
+            false,
+            redaction.clone(),
+            timer.clone(),
+        )
+    });
+    let capture_layer = config.capture.clone().map(|buffer| {
+        fmt_layer(
+            config.format,
+            buffer,
+            false,
+            redaction.clone(),
+            timer.clone(),
+        )
+    });
+    let sink_layers = config
+        .sinks
+        .iter()
+        .map(|sink| sink.layer(redaction.clone(), timer.clone()))
+        .collect::<Vec<_>>();
+    let span_timing_layer = config
+        .span_timing
+        .as_ref()
+        .map(|span_timing| span_timing.layer());
+    let (env_filter, handle) = reload::Layer::new(EnvFilter::new(&config.env_filter));
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(config.sampling.clone())
+        .with(stdout_layer)
+        .with(file_layer)
+        .with(capture_layer)
+        .with(sink_layers)
+        .with(span_timing_layer)
+        .init();
+    ReloadHandle(handle)
+}
+
+fn fmt_layer<S, W>(
+    format: LogFormat,
+    writer: W,
+    ansi: bool,
+    redaction: Option<Redaction>,
+    timer: Timer,
+) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + 'static,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let writer = redaction::RedactingMakeWriter {
+        inner: writer,
+        redaction,
+    };
+    match format {
+        LogFormat::Plain => tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_timer(timer)
+            .with_ansi(ansi)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_ansi(false)
+            .fmt_fields(JsonFields)
+            .event_format(JsonFormat { timer })
+            .boxed(),
+    }
+}
+
+/// The source of the timestamps of log events, see [`Config::with_timer`].
+/// Defaults to the current UTC time.
+#[derive(Clone, Default)]
+struct Timer(Option<Arc<dyn FormatTime + Send + Sync>>);
+
+impl FormatTime for Timer {
+    fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(timer) => timer.format_time(w),
+            None => utc_timer().format_time(w),
+        }
+    }
+}
+
+impl Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(_) => f.write_str("Timer(custom)"),
+            None => f.write_str("Timer(utc)"),
+        }
+    }
+}
+
+fn utc_timer() -> UtcTime<&'static [time::format_description::FormatItem<'static>]> {
+    // This is what kibana uses to separate multi line log messages.
+    UtcTime::new(format_description!(
+        "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z"
+    ))
+}
+
+/// Formats events as single line JSON objects.
+///
+/// The builtin JSON format of `tracing_subscriber` nests span fields in
+/// separate objects. We want all fields in the top level object instead so
+/// they are easy to index by log aggregators.
+struct JsonFormat<T> {
+    timer: T,
+}
+
+impl<S, N, T> FormatEvent<S, N> for JsonFormat<T>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    T: FormatTime,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut record = Map::new();
+
+        let mut timestamp = String::new();
+        self.timer.format_time(&mut Writer::new(&mut timestamp))?;
+        record.insert("timestamp".into(), timestamp.into());
+        record.insert("level".into(), event.metadata().level().to_string().into());
+        record.insert("target".into(), event.metadata().target().into());
+
+        // Spans are visited from the root so that fields of inner spans take
+        // precedence over fields with the same name of outer spans.
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                let extensions = span.extensions();
+                let Some(fields) = extensions.get::<FormattedFields<N>>() else {
+                    continue;
+                };
+                // Span fields were recorded with `JsonFields` so they are
+                // already stored as a JSON object.
+                if let Ok(Value::Object(fields)) = serde_json::from_str(&fields.fields) {
+                    record.extend(fields);
+                }
+            }
+        }
+
+        event.record(&mut JsonVisitor(&mut record));
+
+        let line = serde_json::to_string(&record).map_err(|_| fmt::Error)?;
+        writeln!(writer, "{line}")
+    }
+}
+
+/// Records span fields as a JSON object so that [`JsonFormat`] can merge them
+/// into the object of the event.
+struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut record = Map::new();
+        fields.record(&mut JsonVisitor(&mut record));
+        let json = serde_json::to_string(&record).map_err(|_| fmt::Error)?;
+        write!(writer, "{json}")
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &Record<'_>,
+    ) -> fmt::Result {
+        // The default implementation appends the new fields as text which
+        // would break the JSON object.
+        let mut record = match serde_json::from_str(&current.fields) {
+            Ok(Value::Object(record)) => record,
+            _ => Map::new(),
+        };
+        fields.record(&mut JsonVisitor(&mut record));
+        current.fields = serde_json::to_string(&record).map_err(|_| fmt::Error)?;
+        Ok(())
+    }
+}
+
+/// Collects the fields of an event into a JSON object.
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl tracing::field::Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().into(), format!("{value:?}").into());
     }
 }
 
-/// Panic hook that prints roughly the same message as the default panic hook
-/// but uses tracing:error instead of stderr.
+/// Whether [`log_panic`] logs backtraces on a single line. Set by
+/// [`initialize`].
+static SINGLE_LINE_BACKTRACES: AtomicBool = AtomicBool::new(false);
+
+/// Panic hook that logs panics with tracing:error instead of printing them to
+/// stderr.
 ///
 /// Useful when we want panic messages to have the proper log format for Kibana.
-fn tracing_panic_hook(panic: &PanicInfo) {
+/// The panic message, location and thread are logged as separate fields. The
+/// backtrace is only captured and logged in the `backtrace` field if enabled
+/// with `RUST_BACKTRACE` (or `RUST_LIB_BACKTRACE`).
+pub(crate) fn log_panic(panic: &PanicInfo) {
     let thread = std::thread::current();
-    let name = thread.name().unwrap_or("<unnamed>");
-    let backtrace = std::backtrace::Backtrace::force_capture();
-    tracing::error!("thread '{name}' {panic}\nstack backtrace:\n{backtrace}");
+    let thread = thread.name().unwrap_or("<unnamed>");
+    let payload = panic.payload();
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        *message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "Box<dyn Any>"
+    };
+    let location = panic.location().map(ToString::to_string);
+    let backtrace = Backtrace::capture();
+    if backtrace.status() != BacktraceStatus::Captured {
+        tracing::error!(thread, location, "{message}");
+    } else if SINGLE_LINE_BACKTRACES.load(Ordering::Relaxed) {
+        tracing::error!(thread, location, backtrace = ?backtrace, "{message}");
+    } else {
+        tracing::error!(thread, location, backtrace = %backtrace, "{message}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain_logs(config: &Config) -> String {
+        let buffer = LogBuffer::default();
+        let subscriber = tracing_subscriber::registry().with(fmt_layer(
+            config.format,
+            buffer.clone(),
+            config.ansi(),
+            None,
+            config.timer.clone(),
+        ));
+        tracing::subscriber::with_default(subscriber, || tracing::info!(field = 1, "message"));
+        buffer.contents()
+    }
+
+    #[test]
+    fn ansi_override() {
+        assert!(!plain_logs(&Config::new("info").with_ansi(false)).contains('\x1b'));
+        assert!(plain_logs(&Config::new("info").with_ansi(true)).contains('\x1b'));
+    }
+
+    #[tracing::instrument(fields(solver = "outer", orders = tracing::field::Empty))]
+    fn solve(auction_id: u64) {
+        tracing::Span::current().record("orders", 2);
+        let _span = tracing::info_span!("order", solver = "inner").entered();
+        tracing::info!(target: "json_test", order = "0x01", "solved");
+    }
+
+    #[test]
+    fn json_format_flattens_span_fields() {
+        let buffer = LogBuffer::default();
+        let subscriber = tracing_subscriber::registry().with(fmt_layer(
+            LogFormat::Json,
+            buffer.clone(),
+            false,
+            None,
+            Timer::default(),
+        ));
+        tracing::subscriber::with_default(subscriber, || solve(1));
+
+        let logs = buffer.contents();
+        assert_eq!(logs.lines().count(), 1);
+        let record: Map<String, Value> = serde_json::from_str(&logs).unwrap();
+        assert!(record["timestamp"].is_string());
+        assert_eq!(record["level"], "INFO");
+        assert_eq!(record["target"], "json_test");
+        assert_eq!(record["message"], "solved");
+        assert_eq!(record["order"], "0x01");
+        assert_eq!(record["auction_id"], 1);
+        // Fields recorded after the span got created are included.
+        assert_eq!(record["orders"], 2);
+        // Fields of inner spans take precedence.
+        assert_eq!(record["solver"], "inner");
+    }
+
+    struct FixedTime;
+
+    impl FormatTime for FixedTime {
+        fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+            write!(w, "2023-01-01T00:00:00.000Z")
+        }
+    }
+
+    #[test]
+    fn custom_timer() {
+        let config = Config::new("info")
+            .with_format(LogFormat::Json)
+            .with_timer(FixedTime);
+        let logs = plain_logs(&config);
+        assert!(logs.contains(r#""timestamp":"2023-01-01T00:00:00.000Z""#));
+        assert_eq!(logs, plain_logs(&config));
+
+        let config = config.with_format(LogFormat::Plain).with_ansi(false);
+        assert!(plain_logs(&config).starts_with("2023-01-01T00:00:00.000Z "));
+    }
 }
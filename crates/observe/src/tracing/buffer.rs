@@ -0,0 +1,50 @@
+//! In-memory log output.
+
+use {
+    std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+    },
+    tracing_subscriber::fmt::MakeWriter,
+};
+
+/// Shared buffer that log lines can be written to, e.g. to assert that some
+/// logs have been emitted in tests.
+#[derive(Clone, Debug, Default)]
+pub struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl LogBuffer {
+    /// Returns all logs written since the buffer was created or last cleared.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+
+    /// Returns whether any log contains `pattern`.
+    pub fn contains(&self, pattern: &str) -> bool {
+        self.contents().contains(pattern)
+    }
+
+    /// Discards all logs written so far.
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = &'a LogBuffer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
+
+impl Write for &LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
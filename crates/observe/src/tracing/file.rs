@@ -0,0 +1,305 @@
+//! Writing logs to files in a directory.
+
+use {
+    std::{
+        fs::{self, File, OpenOptions},
+        io::{self, Write},
+        path::{Path, PathBuf},
+        sync::Mutex,
+    },
+    time::{Date, OffsetDateTime},
+    tracing_subscriber::fmt::MakeWriter,
+};
+
+/// Configuration for writing logs to files.
+#[derive(Clone, Debug)]
+pub struct FileConfig {
+    /// The directory the log files get written to. It gets created if it
+    /// doesn't exist yet.
+    pub directory: PathBuf,
+    /// The name of the current log file. Rotated files get a suffix appended.
+    pub file_name: String,
+    pub rotation: Rotation,
+}
+
+/// When to start writing to a new log file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rotation {
+    /// Starts a new file once the current file would exceed the given number
+    /// of bytes. Only the most recently rotated file gets kept with a `.1`
+    /// suffix so the logs take up at most twice the configured size.
+    Size(u64),
+    /// Starts a new file every day (in UTC). The date gets appended to the
+    /// file name, e.g. `services.log.2023-01-01`.
+    Daily,
+}
+
+/// Writes logs to files according to a [`FileConfig`].
+///
+/// Failing writes (e.g. because the disk is full) drop the log line instead of
+/// returning an error so that logging never takes down the process. Files get
+/// reopened on the next write so logging recovers once the problem is fixed.
+pub struct FileWriter(Mutex<Inner>);
+
+enum Inner {
+    Size(SizeRotating),
+    Daily(DailyRotating),
+}
+
+impl FileWriter {
+    pub fn new(config: &FileConfig) -> Self {
+        // The writers also create the directory on demand but doing it up front
+        // surfaces configuration errors on start up.
+        if let Err(err) = fs::create_dir_all(&config.directory) {
+            // Tracing is not set up yet so we can't use it to report the error.
+            eprintln!(
+                "failed to create log directory {}: {err}",
+                config.directory.display()
+            );
+        }
+        let inner = match config.rotation {
+            Rotation::Size(max_size) => Inner::Size(SizeRotating {
+                path: config.directory.join(&config.file_name),
+                max_size,
+                file: None,
+                size: 0,
+            }),
+            Rotation::Daily => Inner::Daily(DailyRotating {
+                directory: config.directory.clone(),
+                file_name: config.file_name.clone(),
+                date: None,
+                file: None,
+            }),
+        };
+        Self(Mutex::new(inner))
+    }
+}
+
+impl<'a> MakeWriter<'a> for FileWriter {
+    type Writer = &'a FileWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
+
+impl Write for &FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = self.0.lock().unwrap();
+        let _ = match &mut *inner {
+            Inner::Size(writer) => writer.write(buf),
+            Inner::Daily(writer) => writer.write(buf),
+        };
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut inner = self.0.lock().unwrap();
+        let _ = match &mut *inner {
+            Inner::Size(writer) => writer.file.as_mut().map_or(Ok(()), |file| file.flush()),
+            Inner::Daily(writer) => writer.file.as_mut().map_or(Ok(()), |file| file.flush()),
+        };
+        Ok(())
+    }
+}
+
+struct SizeRotating {
+    path: PathBuf,
+    max_size: u64,
+    file: Option<File>,
+    /// Number of bytes in the current file.
+    size: u64,
+}
+
+impl SizeRotating {
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate();
+        }
+        let mut file = match self.file.take() {
+            Some(file) => file,
+            None => {
+                let file = open(&self.path)?;
+                self.size = file.metadata()?.len();
+                file
+            }
+        };
+        // On errors the file gets dropped and reopened on the next write in
+        // case it got removed in the meantime.
+        file.write_all(buf)?;
+        self.file = Some(file);
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) {
+        self.file = None;
+        self.size = 0;
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        // If renaming fails we keep appending to the current file.
+        let _ = fs::rename(&self.path, rotated);
+    }
+}
+
+struct DailyRotating {
+    directory: PathBuf,
+    file_name: String,
+    /// The date the current file is for.
+    date: Option<Date>,
+    file: Option<File>,
+}
+
+impl DailyRotating {
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.write_on(OffsetDateTime::now_utc().date(), buf)
+    }
+
+    fn write_on(&mut self, date: Date, buf: &[u8]) -> io::Result<()> {
+        if self.date != Some(date) {
+            self.file = None;
+            self.date = Some(date);
+        }
+        let mut file = match self.file.take() {
+            Some(file) => file,
+            None => open(&self.directory.join(format!("{}.{date}", self.file_name)))?,
+        };
+        // Like for size based rotation the file gets reopened on the next
+        // write if writing fails.
+        file.write_all(buf)?;
+        self.file = Some(file);
+        Ok(())
+    }
+}
+
+/// Opens the file for appending, creating it and its directory if they don't
+/// exist.
+fn open(path: &Path) -> io::Result<File> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, time::macros::date};
+
+    /// A directory that doesn't exist yet and gets removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!("observe-{}", uuid::Uuid::new_v4())))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn size_rotating(path: PathBuf, max_size: u64) -> SizeRotating {
+        SizeRotating {
+            path,
+            max_size,
+            file: None,
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn rotates_by_size_keeping_one_file() {
+        let dir = TempDir::new();
+        let path = dir.0.join("test.log");
+        let rotated = dir.0.join("test.log.1");
+        let mut writer = size_rotating(path.clone(), 10);
+
+        writer.write(b"first\n").unwrap();
+        writer.write(b"second\n").unwrap();
+        assert_eq!(fs::read_to_string(&rotated).unwrap(), "first\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\n");
+
+        writer.write(b"third\n").unwrap();
+        assert_eq!(fs::read_to_string(&rotated).unwrap(), "second\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third\n");
+    }
+
+    #[test]
+    fn continues_size_of_existing_file() {
+        let dir = TempDir::new();
+        let path = dir.0.join("test.log");
+        fs::create_dir_all(&dir.0).unwrap();
+        fs::write(&path, "existing\n").unwrap();
+
+        let mut writer = size_rotating(path.clone(), 10);
+        writer.write(b"new\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "existing\nnew\n");
+        writer.write(b"rotated\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "rotated\n");
+    }
+
+    #[test]
+    fn creates_missing_directory_on_write() {
+        let dir = TempDir::new();
+        let path = dir.0.join("nested").join("test.log");
+
+        let mut writer = size_rotating(path.clone(), 1024);
+        writer.write(b"line\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line\n");
+
+        // The directory also gets recreated if it gets removed while logging.
+        fs::remove_dir_all(&dir.0).unwrap();
+        writer.file = None;
+        writer.write(b"line\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line\n");
+    }
+
+    #[test]
+    fn failing_writes_drop_log_lines() {
+        let dir = TempDir::new();
+        // The directory can't be created because a file is in the way.
+        fs::write(&dir.0, "").unwrap();
+        let writer = FileWriter::new(&FileConfig {
+            directory: dir.0.join("logs"),
+            file_name: "test.log".to_owned(),
+            rotation: Rotation::Size(1024),
+        });
+
+        assert_eq!((&writer).write(b"line\n").unwrap(), 5);
+        (&writer).flush().unwrap();
+        let _ = fs::remove_file(&dir.0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn full_disk_drops_log_lines() {
+        // Writing to `/dev/full` always fails with "no space left on device".
+        let mut writer = size_rotating(PathBuf::from("/dev/full"), 1024);
+        assert!(writer.write(b"line\n").is_err());
+        assert!(writer.file.is_none());
+
+        let writer = FileWriter(Mutex::new(Inner::Size(writer)));
+        assert_eq!((&writer).write(b"line\n").unwrap(), 5);
+    }
+
+    #[test]
+    fn rotates_daily() {
+        let dir = TempDir::new();
+        let mut writer = DailyRotating {
+            directory: dir.0.clone(),
+            file_name: "test.log".to_owned(),
+            date: None,
+            file: None,
+        };
+
+        writer.write_on(date!(2023 - 01 - 01), b"first\n").unwrap();
+        writer.write_on(date!(2023 - 01 - 01), b"second\n").unwrap();
+        writer.write_on(date!(2023 - 01 - 02), b"third\n").unwrap();
+
+        let read = |name: &str| fs::read_to_string(dir.0.join(name)).unwrap();
+        assert_eq!(read("test.log.2023-01-01"), "first\nsecond\n");
+        assert_eq!(read("test.log.2023-01-02"), "third\n");
+    }
+}
@@ -0,0 +1,225 @@
+//! Redaction of addresses, hashes and order uids in log output.
+
+use {
+    std::{
+        borrow::Cow,
+        collections::hash_map::DefaultHasher,
+        fmt::{self, Debug, Formatter},
+        hash::{Hash, Hasher},
+        io,
+        sync::Arc,
+    },
+    tracing::Metadata,
+    tracing_subscriber::fmt::MakeWriter,
+};
+
+/// Replaces hex values that look like addresses (20 bytes), hashes (32 bytes)
+/// or order uids (56 bytes) in log output with a salted short hash, e.g. for
+/// privacy sensitive deployments.
+///
+/// The same value always gets replaced by the same hash so log lines about it
+/// can still be correlated. Values are recognized by their `0x` prefixed hex
+/// pattern and redacted in all outputs and formats, including messages. The
+/// abbreviated form addresses and hashes get displayed in (e.g. `0xc02a…6cc2`)
+/// is redacted as well, but gets a different hash than the full value.
+#[derive(Clone)]
+pub struct Redaction {
+    salt: Arc<str>,
+}
+
+/// Lengths of the hex values that get redacted: addresses, hashes and order
+/// uids.
+const HEX_LENGTHS: [usize; 3] = [40, 64, 112];
+
+/// The number of hex digits on either side of the ellipsis of abbreviated
+/// values, like the `Display` output of addresses and hashes.
+const ABBREVIATED_HEX_LENGTH: usize = 4;
+
+impl Redaction {
+    /// Creates a redaction hashing values with `salt`. The salt should be kept
+    /// secret since addresses can otherwise be recovered by hashing known
+    /// ones.
+    pub fn new(salt: &str) -> Self {
+        Self { salt: salt.into() }
+    }
+
+    /// Returns `text` with all recognized hex values replaced.
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let bytes = text.as_bytes();
+        let mut redacted = String::new();
+        let mut copied = 0;
+        let mut position = 0;
+        while let Some(offset) = text[position..].find("0x") {
+            let start = position + offset;
+            let digits = hex_digits(&bytes[start + 2..]);
+            let mut end = start + 2 + digits;
+            let standalone = start == 0 || !bytes[start - 1].is_ascii_alphanumeric();
+            let abbreviated = digits == ABBREVIATED_HEX_LENGTH
+                && text[end..].starts_with('…')
+                && hex_digits(&bytes[end + '…'.len_utf8()..]) == ABBREVIATED_HEX_LENGTH;
+            if abbreviated {
+                end += '…'.len_utf8() + ABBREVIATED_HEX_LENGTH;
+            }
+            if standalone && (abbreviated || HEX_LENGTHS.contains(&digits)) {
+                redacted.push_str(&text[copied..start]);
+                redacted.push_str(&self.hash(&text[start..end]));
+                copied = end;
+            }
+            position = end;
+        }
+        if copied == 0 {
+            return Cow::Borrowed(text);
+        }
+        redacted.push_str(&text[copied..]);
+        Cow::Owned(redacted)
+    }
+
+    fn hash(&self, value: &str) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.salt.hash(&mut hasher);
+        value.to_ascii_lowercase().hash(&mut hasher);
+        format!("<redacted:{:08x}>", hasher.finish() as u32)
+    }
+}
+
+/// The number of hex digits `bytes` starts with.
+fn hex_digits(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .take_while(|byte| byte.is_ascii_hexdigit())
+        .count()
+}
+
+impl Debug for Redaction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // Don't leak the salt.
+        f.debug_struct("Redaction").finish_non_exhaustive()
+    }
+}
+
+/// Redacts everything written to the wrapped writer if a [`Redaction`] is
+/// configured.
+pub(super) struct RedactingMakeWriter<W> {
+    pub inner: W,
+    pub redaction: Option<Redaction>,
+}
+
+impl<'a, W> MakeWriter<'a> for RedactingMakeWriter<W>
+where
+    W: MakeWriter<'a>,
+{
+    type Writer = RedactingWriter<'a, W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            inner: self.inner.make_writer(),
+            redaction: self.redaction.as_ref(),
+        }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        RedactingWriter {
+            inner: self.inner.make_writer_for(meta),
+            redaction: self.redaction.as_ref(),
+        }
+    }
+}
+
+pub(super) struct RedactingWriter<'a, W> {
+    inner: W,
+    redaction: Option<&'a Redaction>,
+}
+
+impl<W> io::Write for RedactingWriter<'_, W>
+where
+    W: io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(redaction) = self.redaction else {
+            return self.inner.write(buf);
+        };
+        // Events get formatted into a buffer first and written at once, so
+        // values don't get split across writes.
+        let text = String::from_utf8_lossy(buf);
+        self.inner.write_all(redaction.redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::tracing::{fmt_layer, LogBuffer, LogFormat, Timer},
+        tracing_subscriber::prelude::*,
+    };
+
+    const ADDRESS: &str = "0x9008d19f58aabd9ed0d60971565aa8510560ab41";
+
+    fn logs(format: LogFormat, redaction: Option<Redaction>) -> String {
+        let buffer = LogBuffer::default();
+        let subscriber = tracing_subscriber::registry().with(fmt_layer(
+            format,
+            buffer.clone(),
+            false,
+            redaction,
+            Timer::default(),
+        ));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(owner = ADDRESS, "order placed");
+        });
+        buffer.contents()
+    }
+
+    #[test]
+    fn redacts_addresses_in_all_formats() {
+        let redaction = Redaction::new("salt");
+        let hash = redaction.hash(ADDRESS);
+        for format in [LogFormat::Plain, LogFormat::Json] {
+            assert!(logs(format, None).contains(ADDRESS));
+            let redacted = logs(format, Some(redaction.clone()));
+            assert!(!redacted.contains(ADDRESS));
+            assert!(redacted.contains(&hash));
+        }
+    }
+
+    #[test]
+    fn only_redacts_recognized_values() {
+        let redaction = Redaction::new("salt");
+        assert_eq!(
+            redaction.redact("gas 0x1234 block 0xabc"),
+            "gas 0x1234 block 0xabc"
+        );
+        // Part of a longer hex value.
+        let calldata = format!("0x{}", "ab".repeat(40));
+        assert_eq!(redaction.redact(&calldata), calldata);
+
+        // The same value gets the same hash regardless of its case.
+        let hash = redaction.hash(ADDRESS);
+        let uppercase = format!("0x{}", ADDRESS[2..].to_uppercase());
+        assert_eq!(
+            redaction.redact(&format!("{ADDRESS} and {uppercase}")),
+            format!("{hash} and {hash}")
+        );
+        assert_ne!(Redaction::new("other").hash(ADDRESS), hash);
+    }
+
+    #[test]
+    fn redacts_abbreviated_values() {
+        let redaction = Redaction::new("salt");
+        // How `ADDRESS` gets displayed as an `H160`.
+        let abbreviated = "0x9008…ab41";
+
+        let redacted = redaction.redact(&format!("owner {abbreviated}."));
+        assert_eq!(redacted, format!("owner {}.", redaction.hash(abbreviated)));
+
+        // Only abbreviations with 4 digits on either side are recognized.
+        for text in ["0x9008…ab4", "0x900…ab41", "0x9008…ab41f", "a0x9008…ab41"] {
+            assert_eq!(redaction.redact(text), text);
+        }
+    }
+}
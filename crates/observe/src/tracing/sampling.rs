@@ -0,0 +1,140 @@
+//! Sampling of log events of noisy targets.
+
+use {
+    anyhow::{anyhow, ensure, Context as _, Result},
+    rand::{rngs::StdRng, Rng, SeedableRng},
+    std::{
+        str::FromStr,
+        sync::{Arc, Mutex},
+    },
+    tracing::{Event, Subscriber},
+    tracing_subscriber::{layer::Context, Layer},
+};
+
+/// Only keeps a random share of the log events of specific targets.
+///
+/// Configured as a comma separated list of `target=rate` directives where
+/// `rate` is the share of events in `[0, 1]` to keep, for example
+/// `orderbook::api::request_summary=0.01`. A directive applies to the target
+/// and all of its child modules; the most specific directive wins. Events of
+/// other targets are unaffected.
+///
+/// Events get dropped before they are formatted by any output. Sampling
+/// happens in addition to the log filter so events have to pass both.
+#[derive(Clone, Debug)]
+pub struct Sampling {
+    rates: Vec<(String, f64)>,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl Sampling {
+    /// Samples events with `rng`. Useful for deterministic tests.
+    pub fn with_rng(mut self, rng: StdRng) -> Self {
+        self.rng = Arc::new(Mutex::new(rng));
+        self
+    }
+
+    fn rate(&self, target: &str) -> Option<f64> {
+        self.rates
+            .iter()
+            .filter(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .map_or(false, |rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, rate)| *rate)
+    }
+}
+
+impl FromStr for Sampling {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let rates = s
+            .split(',')
+            .map(str::trim)
+            .filter(|directive| !directive.is_empty())
+            .map(|directive| {
+                let (target, rate) = directive.split_once('=').ok_or_else(|| {
+                    anyhow!("sampling directive {directive:?} is not target=rate")
+                })?;
+                let rate: f64 = rate
+                    .parse()
+                    .with_context(|| format!("invalid sampling rate in {directive:?}"))?;
+                ensure!(
+                    (0. ..=1.).contains(&rate),
+                    "sampling rate in {directive:?} outside of [0, 1] range"
+                );
+                Ok((target.to_owned(), rate))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            rates,
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+        })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Sampling {
+    fn event_enabled(&self, event: &Event<'_>, _: Context<'_, S>) -> bool {
+        match self.rate(event.metadata().target()) {
+            Some(rate) => self.rng.lock().unwrap().gen_bool(rate),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::tracing::LogBuffer, tracing_subscriber::prelude::*};
+
+    fn sampled_lines(sampling: &str, seed: u64) -> String {
+        let buffer = LogBuffer::default();
+        let sampling = sampling
+            .parse::<Sampling>()
+            .unwrap()
+            .with_rng(StdRng::seed_from_u64(seed));
+        let subscriber = tracing_subscriber::registry().with(sampling).with(
+            tracing_subscriber::fmt::layer()
+                .without_time()
+                .with_ansi(false)
+                .with_writer(buffer.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..1000 {
+                tracing::info!(target: "noisy::summary", i, "sampled");
+                tracing::info!(target: "noisy_other", i, "kept");
+            }
+        });
+        buffer.contents()
+    }
+
+    #[test]
+    fn samples_matching_targets() {
+        let logs = sampled_lines("noisy=0.1", 42);
+        let sampled = logs.matches("sampled").count();
+        assert!((50..150).contains(&sampled), "{sampled}");
+        assert_eq!(logs.matches("kept").count(), 1000);
+
+        // The same seed samples the same events.
+        assert_eq!(sampled_lines("noisy=0.1", 42), logs);
+    }
+
+    #[test]
+    fn most_specific_directive_wins() {
+        let logs = sampled_lines("noisy=0,noisy::summary=1", 0);
+        assert_eq!(logs.matches("sampled").count(), 1000);
+
+        let logs = sampled_lines("noisy::summary=0", 0);
+        assert_eq!(logs.matches("sampled").count(), 0);
+    }
+
+    #[test]
+    fn rejects_invalid_directives() {
+        assert!("noisy".parse::<Sampling>().is_err());
+        assert!("noisy=1.5".parse::<Sampling>().is_err());
+        assert!("noisy=often".parse::<Sampling>().is_err());
+    }
+}
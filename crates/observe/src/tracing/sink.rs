@@ -0,0 +1,138 @@
+//! Additional log outputs with their own format and filter.
+
+use {
+    super::{fmt_layer, LogFormat, Redaction, Timer},
+    std::{
+        fmt::{self, Debug, Formatter},
+        sync::Arc,
+    },
+    tracing::{Metadata, Subscriber},
+    tracing_subscriber::{
+        fmt::{writer::BoxMakeWriter, MakeWriter},
+        registry::LookupSpan,
+        EnvFilter,
+        Layer,
+    },
+};
+
+/// An additional output log events get written to, e.g. the unix socket of a
+/// local syslog daemon next to JSON logs on stdout.
+///
+/// Every sink writes events in its own format and only the events matching its
+/// own filter. The filter applies in addition to the one of the
+/// [`super::Config`] so events have to pass both. Sinks are independent of each
+/// other: if writing to a sink fails (e.g. because its socket got closed) only
+/// that sink misses the event.
+#[derive(Clone)]
+pub struct Sink {
+    writer: Arc<BoxMakeWriter>,
+    format: LogFormat,
+    filter: String,
+}
+
+impl Sink {
+    /// Creates a sink writing the events matching `filter` to `writer`.
+    /// `filter` has the same syntax as the filter of [`super::Config::new`].
+    pub fn new<W>(writer: W, format: LogFormat, filter: &str) -> Self
+    where
+        W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+    {
+        Self {
+            writer: Arc::new(BoxMakeWriter::new(writer)),
+            format,
+            filter: filter.to_owned(),
+        }
+    }
+
+    pub(super) fn layer<S>(
+        &self,
+        redaction: Option<Redaction>,
+        timer: Timer,
+    ) -> Box<dyn Layer<S> + Send + Sync>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a> + 'static,
+    {
+        let writer = SharedWriter(self.writer.clone());
+        fmt_layer(self.format, writer, false, redaction, timer)
+            .with_filter(EnvFilter::new(&self.filter))
+            .boxed()
+    }
+}
+
+impl Debug for Sink {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Sink")
+            .field("format", &self.format)
+            .field("filter", &self.filter)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Lets the layers of clones of a [`Sink`] share its writer.
+struct SharedWriter(Arc<BoxMakeWriter>);
+
+impl<'a> MakeWriter<'a> for SharedWriter {
+    type Writer = <BoxMakeWriter as MakeWriter<'a>>::Writer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.0.make_writer()
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        self.0.make_writer_for(meta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::tracing::LogBuffer, std::io, tracing_subscriber::prelude::*};
+
+    struct ClosedSocket;
+
+    impl io::Write for ClosedSocket {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for ClosedSocket {
+        type Writer = ClosedSocket;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            ClosedSocket
+        }
+    }
+
+    #[test]
+    fn sinks_only_receive_matching_events() {
+        let plain = LogBuffer::default();
+        let json = LogBuffer::default();
+        let sinks = [
+            Sink::new(json.clone(), LogFormat::Json, "b=warn"),
+            Sink::new(ClosedSocket, LogFormat::Plain, "info"),
+            Sink::new(plain.clone(), LogFormat::Plain, "a=info"),
+        ];
+        let layers = sinks
+            .iter()
+            .map(|sink| sink.layer(None, Timer::default()))
+            .collect::<Vec<_>>();
+        let subscriber = tracing_subscriber::registry().with(layers);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "a", "first");
+            tracing::warn!(target: "b", "second");
+            tracing::info!(target: "b", "third");
+        });
+
+        assert!(plain.contains("first"));
+        assert!(!plain.contains("second"));
+        assert!(!plain.contains("third"));
+        assert!(json.contains(r#""message":"second""#));
+        assert!(!json.contains("first"));
+        assert!(!json.contains("third"));
+    }
+}
@@ -0,0 +1,126 @@
+//! Latency metrics for tracing spans.
+
+use {
+    anyhow::Result,
+    prometheus::{HistogramOpts, HistogramVec},
+    std::{
+        collections::HashSet,
+        fmt::{self, Debug, Formatter},
+        sync::Arc,
+        time::Instant,
+    },
+    tracing::{span, Subscriber},
+    tracing_subscriber::{filter::filter_fn, layer::Context, registry::LookupSpan, Layer},
+};
+
+/// Records how long the watched spans were open in the `span_duration_seconds`
+/// histogram labelled by the `target` and `name` of the span.
+///
+/// Only spans whose name is watched are timed so that the number of time
+/// series stays bounded. Other spans get filtered out once per callsite so
+/// they don't incur any overhead. Note that spans also have to be enabled by
+/// the log filter in order to be timed.
+#[derive(Clone)]
+pub struct SpanTiming {
+    histogram: HistogramVec,
+    spans: Arc<HashSet<String>>,
+}
+
+impl SpanTiming {
+    /// Registers the histogram in `registry` and watches the spans with the
+    /// specified names.
+    pub fn new(
+        registry: &prometheus::Registry,
+        spans: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Self> {
+        let histogram = HistogramVec::new(
+            HistogramOpts::new("span_duration_seconds", "Time tracing spans were open."),
+            &["target", "name"],
+        )?;
+        registry.register(Box::new(histogram.clone()))?;
+        Ok(Self {
+            histogram,
+            spans: Arc::new(spans.into_iter().map(Into::into).collect()),
+        })
+    }
+
+    pub(crate) fn layer<S>(&self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let spans = self.spans.clone();
+        Timer {
+            histogram: self.histogram.clone(),
+        }
+        .with_filter(filter_fn(move |metadata| {
+            metadata.is_span() && spans.contains(metadata.name())
+        }))
+    }
+}
+
+impl Debug for SpanTiming {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("SpanTiming")
+            .field("spans", &self.spans)
+            .finish()
+    }
+}
+
+struct Timer {
+    histogram: HistogramVec,
+}
+
+/// When a timed span was created.
+struct Start(Instant);
+
+impl<S> Layer<S> for Timer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Start(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(start) = span.extensions().get::<Start>().map(|start| start.0) else {
+            return;
+        };
+        self.histogram
+            .with_label_values(&[span.metadata().target(), span.name()])
+            .observe(start.elapsed().as_secs_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tracing_subscriber::prelude::*};
+
+    #[test]
+    fn times_watched_spans() {
+        let registry = prometheus::Registry::new();
+        let timing = SpanTiming::new(&registry, ["watched"]).unwrap();
+        let subscriber = tracing_subscriber::registry().with(timing.layer());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("watched").in_scope(|| ());
+            tracing::info_span!("watched").in_scope(|| ());
+            tracing::info_span!("ignored").in_scope(|| ());
+        });
+
+        let families = registry.gather();
+        let metrics = families[0].get_metric();
+        assert_eq!(metrics.len(), 1);
+        let labels = metrics[0]
+            .get_label()
+            .iter()
+            .map(|label| (label.get_name(), label.get_value()))
+            .collect::<Vec<_>>();
+        assert_eq!(labels, [("name", "watched"), ("target", module_path!())]);
+        assert_eq!(metrics[0].get_histogram().get_sample_count(), 2);
+    }
+}
@@ -169,6 +169,7 @@ mod tests {
                 nonce: 8,
             },
             competition: SolverCompetitionDB {
+                version: SolverCompetitionDB::VERSION,
                 gas_price: 1.,
                 auction_start_block: 2,
                 liquidity_collected_block: 3,
@@ -64,8 +64,9 @@ use {
 pub async fn start(args: impl Iterator<Item = String>) {
     let args = Arguments::parse_from(args);
     observe::tracing::initialize(
-        args.shared.logging.log_filter.as_str(),
-        args.shared.logging.log_stderr_threshold,
+        &observe::tracing::Config::new(args.shared.logging.log_filter.as_str())
+            .with_stderr_threshold(args.shared.logging.log_stderr_threshold)
+            .with_format(args.shared.logging.log_format),
     );
     tracing::info!("running order book with validated arguments:\n{}", args);
     observe::panic_hook::install();
@@ -348,7 +349,7 @@ pub async fn run(args: Arguments) {
         http_factory.create(),
         chain_id,
     )
-    .map(Arc::new);
+    .map(|api| Arc::new(api.with_version(args.shared.one_inch_api_version)));
 
     let mut price_estimator_factory = PriceEstimatorFactory::new(
         &args.price_estimation,
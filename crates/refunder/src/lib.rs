@@ -23,8 +23,9 @@ const DELAY_FROM_LAST_LOOP_BEFORE_UNHEALTHY: Duration = LOOP_INTERVAL.saturating
 pub async fn start(args: impl Iterator<Item = String>) {
     let args = Arguments::parse_from(args);
     observe::tracing::initialize(
-        args.logging.log_filter.as_str(),
-        args.logging.log_stderr_threshold,
+        &observe::tracing::Config::new(args.logging.log_filter.as_str())
+            .with_stderr_threshold(args.logging.log_stderr_threshold)
+            .with_format(args.logging.log_format),
     );
     observe::panic_hook::install();
     tracing::info!("running refunder with validated arguments:\n{}", args);
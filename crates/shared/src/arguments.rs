@@ -4,6 +4,7 @@
 use {
     crate::{
         gas_price_estimation::GasEstimatorType,
+        oneinch_api::{ApiVersion, ReferrerFee, Slippage},
         price_estimation::PriceEstimators,
         rate_limiter::RateLimitingStrategy,
         sources::{
@@ -18,7 +19,7 @@ use {
     ethcontract::{H160, H256, U256},
     std::{
         fmt::{self, Display, Formatter},
-        num::{NonZeroU64, ParseFloatError},
+        num::{NonZeroU64, NonZeroUsize, ParseFloatError},
         str::FromStr,
         time::Duration,
     },
@@ -36,6 +37,10 @@ macro_rules! logging_args_with_default_filter {
 
             #[clap(long, env, default_value = "error")]
             pub log_stderr_threshold: LevelFilter,
+
+            /// The format in which logs get written. Either "plain" or "json".
+            #[clap(long, env, default_value = "plain")]
+            pub log_format: observe::tracing::LogFormat,
         }
     };
 }
@@ -262,10 +267,28 @@ pub struct Arguments {
     #[structopt(long, env, default_value = "https://api.1inch.io/")]
     pub one_inch_url: Url,
 
+    /// The version of the 1Inch REST API to use, e.g. "v5.0".
+    #[clap(long, env, default_value = "v5.0")]
+    pub one_inch_api_version: ApiVersion,
+
     /// Which address should receive the rewards for referring trades to 1Inch.
     #[structopt(long, env)]
     pub one_inch_referrer_address: Option<H160>,
 
+    /// The fee in basis points of the sell amount that the 1Inch referrer
+    /// address receives. No fee is requested if unset.
+    #[clap(long, env)]
+    pub one_inch_referrer_fee: Option<ReferrerFee>,
+
+    /// The slippage in percent that swaps built with the 1Inch API tolerate.
+    #[clap(long, env, default_value = "1")]
+    pub one_inch_slippage: Slippage,
+
+    /// The maximum number of concurrent requests to the 1Inch API. Further
+    /// requests wait until earlier ones completed.
+    #[clap(long, env, default_value = "10")]
+    pub one_inch_max_concurrent_requests: NonZeroUsize,
+
     /// The list of disabled 0x sources.
     #[clap(long, env, use_value_delimiter = true)]
     pub disabled_zeroex_sources: Vec<String>,
@@ -404,6 +427,7 @@ impl Display for Arguments {
             "log_stderr_threshold: {}",
             self.logging.log_stderr_threshold
         )?;
+        writeln!(f, "log_format: {:?}", self.logging.log_format)?;
         writeln!(f, "node_url: {}", self.node_url)?;
         display_option(f, "chain_id", &self.chain_id)?;
         display_option(f, "simulation_node_url", &self.simulation_node_url)?;
@@ -439,11 +463,19 @@ impl Display for Arguments {
             &self.disabled_one_inch_protocols,
         )?;
         writeln!(f, "one_inch_url: {}", self.one_inch_url)?;
+        writeln!(f, "one_inch_api_version: {}", self.one_inch_api_version)?;
         display_option(
             f,
             "one_inch_referrer_address",
             &self.one_inch_referrer_address.map(|a| format!("{a:?}")),
         )?;
+        display_option(f, "one_inch_referrer_fee", &self.one_inch_referrer_fee)?;
+        writeln!(f, "one_inch_slippage: {}", self.one_inch_slippage)?;
+        writeln!(
+            f,
+            "one_inch_max_concurrent_requests: {}",
+            self.one_inch_max_concurrent_requests
+        )?;
         display_list(f, "disabled_zeroex_sources", &self.disabled_zeroex_sources)?;
         writeln!(
             f,
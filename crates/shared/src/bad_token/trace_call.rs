@@ -692,7 +692,7 @@ mod tests {
     #[tokio::test]
     #[ignore]
     async fn mainnet_univ3() {
-        observe::tracing::initialize_reentrant("shared=debug");
+        observe::tracing::initialize_reentrant(&observe::tracing::Config::new("shared=debug"));
         let http = create_env_test_transport();
         let web3 = Web3::new(http);
         let base_tokens = vec![testlib::tokens::WETH];
@@ -8,7 +8,7 @@ use {
 };
 
 /// The maximum number of hops to use when trading with AMMs along a path.
-const DEFAULT_MAX_HOPS: usize = 2;
+pub const DEFAULT_MAX_HOPS: usize = 2;
 
 type PathCandidate = Vec<H160>;
 
@@ -247,6 +247,7 @@ mod tests {
         super::*,
         crate::sources::uniswap_v2::pool_fetching::Pool,
         ethcontract::H160,
+        itertools::Itertools,
         maplit::{hashmap, hashset},
         model::TokenPair,
     };
@@ -327,6 +328,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn path_candidates_never_repeat_tokens() {
+        let sell_token = H160::from_low_u64_be(1);
+        let buy_token = H160::from_low_u64_be(2);
+        let base_tokens = hashset! {
+            sell_token,
+            buy_token,
+            H160::from_low_u64_be(3),
+            H160::from_low_u64_be(4),
+        };
+
+        for max_hops in 0..5 {
+            for path in path_candidates(sell_token, buy_token, &base_tokens, max_hops) {
+                assert!(path.iter().all_unique(), "{path:?} repeats a token");
+                assert_eq!(path.first(), Some(&sell_token));
+                assert_eq!(path.last(), Some(&buy_token));
+            }
+        }
+    }
+
     #[test]
     fn test_estimate_amount_returns_none_if_it_contains_pair_without_pool() {
         let sell_token = H160::from_low_u64_be(1);
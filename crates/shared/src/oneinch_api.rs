@@ -11,11 +11,17 @@ use {
     derivative::Derivative,
     ethcontract::{Bytes, H160, U256},
     number::u256_decimal,
-    reqwest::{Client, IntoUrl, Url},
+    reqwest::{
+        header::{HeaderMap, RETRY_AFTER},
+        Client,
+        IntoUrl,
+        Url,
+    },
     serde::{de::DeserializeOwned, Deserialize},
     std::{
         fmt::{self, Display, Formatter},
         future::Future,
+        str::FromStr,
         sync::Arc,
         time::{Duration, Instant},
     },
@@ -93,8 +99,8 @@ fn addr2str(addr: H160) -> String {
 }
 
 impl SellOrderQuoteQuery {
-    fn into_url(self, base_url: &Url, chain_id: u64) -> Url {
-        let endpoint = format!("v5.0/{chain_id}/quote");
+    fn into_url(self, base_url: &Url, version: ApiVersion, chain_id: u64) -> Url {
+        let endpoint = format!("{version}/{chain_id}/quote");
         let mut url = crate::url::join(base_url, &endpoint);
 
         url.query_pairs_mut()
@@ -169,6 +175,13 @@ impl SellOrderQuoteQuery {
             referrer_address,
         }
     }
+
+    /// Sets the fee that gets sent to the referrer address. No fee gets
+    /// requested if it is `None`.
+    pub fn with_referrer_fee(mut self, fee: Option<ReferrerFee>) -> Self {
+        self.fee = fee.map(|fee| fee.percentage());
+        self
+    }
 }
 
 /// A sell order quote from 1Inch.
@@ -240,10 +253,53 @@ impl Display for Slippage {
     }
 }
 
+impl FromStr for Slippage {
+    type Err = anyhow::Error;
+
+    /// Parses a slippage percentage.
+    fn from_str(s: &str) -> Result<Self> {
+        Self::percentage(s.parse()?)
+    }
+}
+
+/// A fee in basis points of the sell amount that gets sent to the referrer
+/// address of a swap.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct ReferrerFee(u16);
+
+impl ReferrerFee {
+    /// Creates a referrer fee from the specified basis points.
+    pub fn from_bps(bps: u16) -> Result<Self> {
+        // 1Inch API only accepts a fee from 0 to 3 percent.
+        ensure!(bps <= 300, "referrer fee outside of [0, 300] bps range");
+        Ok(Self(bps))
+    }
+
+    /// Returns the fee as a percentage which is how the 1Inch API expects it.
+    pub fn percentage(&self) -> f64 {
+        f64::from(self.0) / 100.
+    }
+}
+
+impl Display for ReferrerFee {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}bps", self.0)
+    }
+}
+
+impl FromStr for ReferrerFee {
+    type Err = anyhow::Error;
+
+    /// Parses a referrer fee in basis points.
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_bps(s.parse()?)
+    }
+}
+
 impl SwapQuery {
     /// Encodes the swap query as
-    fn into_url(self, base_url: &Url, chain_id: u64) -> Url {
-        let endpoint = format!("v5.0/{chain_id}/swap");
+    fn into_url(self, base_url: &Url, version: ApiVersion, chain_id: u64) -> Url {
+        let endpoint = format!("{version}/{chain_id}/swap");
         let mut url = crate::url::join(base_url, &endpoint);
         url.query_pairs_mut()
             .append_pair("fromTokenAddress", &addr2str(self.quote.from_token_address))
@@ -340,6 +396,13 @@ impl SwapQuery {
             allow_partial_fill: Some(false),
         }
     }
+
+    /// Sets the fee that gets sent to the referrer address. No fee gets
+    /// requested if it is `None`.
+    pub fn with_referrer_fee(mut self, fee: Option<ReferrerFee>) -> Self {
+        self.quote = self.quote.with_referrer_fee(fee);
+        self
+    }
 }
 
 /// A 1Inch API response.
@@ -382,6 +445,9 @@ impl From<serde_json::Error> for OneInchError {
 pub struct RestError {
     pub status_code: u32,
     pub description: String,
+    /// The delay requested by the `Retry-After` header of the response.
+    #[serde(skip)]
+    pub retry_after: Option<Duration>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Default)]
@@ -482,11 +548,41 @@ pub trait OneInchClient: Send + Sync + 'static {
     async fn get_liquidity_sources(&self) -> Result<Protocols, OneInchError>;
 }
 
+/// Versions of the 1Inch API that the client can target.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ApiVersion {
+    V4_0,
+    #[default]
+    V5_0,
+}
+
+impl Display for ApiVersion {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::V4_0 => "v4.0",
+            Self::V5_0 => "v5.0",
+        })
+    }
+}
+
+impl FromStr for ApiVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "v4.0" => Ok(Self::V4_0),
+            "v5.0" => Ok(Self::V5_0),
+            _ => anyhow::bail!("unsupported 1Inch API version {s}"),
+        }
+    }
+}
+
 /// 1Inch API Client implementation.
 #[derive(Debug)]
 pub struct OneInchClientImpl {
     client: Client,
     base_url: Url,
+    version: ApiVersion,
     chain_id: u64,
 }
 
@@ -505,10 +601,18 @@ impl OneInchClientImpl {
         Ok(Self {
             client,
             base_url: base_url.into_url()?,
+            version: Default::default(),
             chain_id,
         })
     }
 
+    /// Targets a specific version of the 1Inch API instead of the default one.
+    /// Note that response formats can differ between versions.
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+
     #[cfg(test)]
     pub fn test() -> Self {
         OneInchClientImpl::new(OneInchClientImpl::DEFAULT_URL, Client::new(), 1).unwrap()
@@ -518,24 +622,26 @@ impl OneInchClientImpl {
 #[async_trait::async_trait]
 impl OneInchClient for OneInchClientImpl {
     async fn get_swap(&self, query: SwapQuery) -> Result<Swap, OneInchError> {
-        logged_query(&self.client, query.into_url(&self.base_url, self.chain_id)).await
+        let url = query.into_url(&self.base_url, self.version, self.chain_id);
+        logged_query(&self.client, url).await
     }
 
     async fn get_sell_order_quote(
         &self,
         query: SellOrderQuoteQuery,
     ) -> Result<SellOrderQuote, OneInchError> {
-        logged_query(&self.client, query.into_url(&self.base_url, self.chain_id)).await
+        let url = query.into_url(&self.base_url, self.version, self.chain_id);
+        logged_query(&self.client, url).await
     }
 
     async fn get_spender(&self) -> Result<Spender, OneInchError> {
-        let endpoint = format!("v5.0/{}/approve/spender", self.chain_id);
+        let endpoint = format!("{}/{}/approve/spender", self.version, self.chain_id);
         let url = crate::url::join(&self.base_url, &endpoint);
         logged_query(&self.client, url).await
     }
 
     async fn get_liquidity_sources(&self) -> Result<Protocols, OneInchError> {
-        let endpoint = format!("v5.0/{}/liquidity-sources", self.chain_id);
+        let endpoint = format!("{}/{}/liquidity-sources", self.version, self.chain_id);
         let url = crate::url::join(&self.base_url, &endpoint);
         logged_query(&self.client, url).await
     }
@@ -548,6 +654,7 @@ where
     tracing::trace!(%url, "Query 1inch API");
     let response = client.get(url).send().await?;
     let status_code = response.status();
+    let retry_after = retry_after(response.headers());
     let response = response.text().await?;
     tracing::trace!(%response, ?status_code, "Received 1Inch API response");
 
@@ -555,26 +662,43 @@ where
         Ok(RestResponse::Ok(result)) => Ok(result),
         Ok(RestResponse::Err(err)) => {
             tracing::warn!(?err, "1inch API error");
-            Err(err.into())
+            Err(RestError { retry_after, ..err }.into())
         }
         Err(err) => {
             tracing::warn!(?err, "failed to parse response");
             Err(OneInchError::Api(RestError {
                 status_code: status_code.as_u16().into(),
                 description: "failed to parse response".to_owned(),
+                retry_after,
             }))
         }
     }
 }
 
+/// Parses the delay in seconds of a `Retry-After` header. The HTTP date form
+/// isn't supported.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
 /// A cache for 1Inch API auxiliary data.
 #[derive(Debug, Clone)]
 pub struct Cache(Arc<CacheInner>);
 
-#[derive(Debug)]
+#[derive(Derivative)]
+#[derivative(Debug)]
 pub struct CacheInner {
     protocols: CacheEntry<Protocols>,
     spender: CacheEntry<Spender>,
+    #[derivative(Debug = "ignore")]
+    now: Box<dyn Fn() -> Instant + Send + Sync>,
 }
 
 #[derive(Debug)]
@@ -585,9 +709,19 @@ struct CacheEntry<T> {
 
 impl Cache {
     pub fn new(max_age: Duration) -> Self {
+        Self::with_clock(max_age, Instant::now)
+    }
+
+    /// Like [`Cache::new`] but gets the current time from `now` instead of
+    /// the system clock. This allows tests to advance time without sleeping.
+    pub fn with_clock(
+        max_age: Duration,
+        now: impl Fn() -> Instant + Send + Sync + 'static,
+    ) -> Self {
         Self(Arc::new(CacheInner {
             protocols: CacheEntry::new(max_age),
             spender: CacheEntry::new(max_age),
+            now: Box::new(now),
         }))
     }
 
@@ -603,7 +737,7 @@ impl Cache {
         let protocols = self
             .0
             .protocols
-            .get_or_update(move || {
+            .get_or_update(&self.0.now, move || {
                 tracing::debug!("updating cached liquidity sources");
                 api.get_liquidity_sources()
             })
@@ -623,7 +757,7 @@ impl Cache {
     pub async fn spender(&self, api: &dyn OneInchClient) -> Result<Spender, OneInchError> {
         self.0
             .spender
-            .get_or_update(move || {
+            .get_or_update(&self.0.now, move || {
                 tracing::debug!("updating cached spender address");
                 api.get_spender()
             })
@@ -645,7 +779,11 @@ impl<T> CacheEntry<T> {
         }
     }
 
-    async fn get_or_update<F, Fut>(&self, f: F) -> Result<T, OneInchError>
+    async fn get_or_update<F, Fut>(
+        &self,
+        now: &dyn Fn() -> Instant,
+        f: F,
+    ) -> Result<T, OneInchError>
     where
         T: Clone,
         F: FnOnce() -> Fut,
@@ -654,13 +792,13 @@ impl<T> CacheEntry<T> {
         let mut store = self.store.lock().await;
 
         if let Some((cached, at)) = store.as_ref() {
-            if at.elapsed() < self.max_age {
+            if now().saturating_duration_since(*at) < self.max_age {
                 return Ok(cached.clone());
             }
         }
 
         let fresh = f().await?;
-        *store = Some((fresh.clone(), Instant::now()));
+        *store = Some((fresh.clone(), now()));
 
         Ok(fresh)
     }
@@ -681,6 +819,13 @@ mod tests {
         assert!(Slippage::percentage(1337.).is_err());
     }
 
+    #[test]
+    fn referrer_fee_valid_range() {
+        assert_eq!(ReferrerFee::from_bps(25).unwrap().percentage(), 0.25);
+        assert!(ReferrerFee::from_bps(300).is_ok());
+        assert!(ReferrerFee::from_bps(301).is_err());
+    }
+
     #[test]
     fn amounts_valid_range() {
         assert!(Amount::<42, 1337>::new(41).is_err());
@@ -714,7 +859,7 @@ mod tests {
             burn_chi: None,
             allow_partial_fill: None,
         }
-        .into_url(&base_url, 1);
+        .into_url(&base_url, ApiVersion::V5_0, 1);
 
         assert_eq!(
             url.as_str(),
@@ -755,7 +900,7 @@ mod tests {
             allow_partial_fill: Some(false),
             dest_receiver: Some(addr!("41111a111217dc0aa78b774fa6a738024120c302")),
         }
-        .into_url(&base_url, 1);
+        .into_url(&base_url, ApiVersion::V5_0, 1);
 
         assert_eq!(
             url.as_str(),
@@ -891,7 +1036,8 @@ mod tests {
             swap_error,
             RestResponse::Err(RestError {
                 status_code: 500,
-                description: "Internal server error".into()
+                description: "Internal server error".into(),
+                retry_after: None,
             })
         );
     }
@@ -1009,7 +1155,7 @@ mod tests {
             connector_tokens: None,
             referrer_address: None,
         }
-        .into_url(&base_url, 1);
+        .into_url(&base_url, ApiVersion::V5_0, 1);
 
         assert_eq!(
             url.as_str(),
@@ -1040,7 +1186,7 @@ mod tests {
             parts: Some(Amount::new(3).unwrap()),
             referrer_address: Some(addr!("9008D19f58AAbD9eD0D60971565AA8510560ab41")),
         }
-        .into_url(&base_url, 1);
+        .into_url(&base_url, ApiVersion::V5_0, 1);
 
         assert_eq!(
             url.as_str(),
@@ -1168,7 +1314,8 @@ mod tests {
             swap_error,
             RestResponse::Err(RestError {
                 status_code: 500,
-                description: "Internal server error".into()
+                description: "Internal server error".into(),
+                retry_after: None,
             })
         );
     }
@@ -7,7 +7,7 @@ use {
     },
     anyhow::{Context, Result},
     ethcontract::{H160, U256},
-    futures::future::BoxFuture,
+    futures::future::{BoxFuture, FutureExt},
     itertools::Itertools,
     model::order::{BuyTokenDestination, OrderKind, SellTokenSource},
     num::BigRational,
@@ -202,6 +202,56 @@ impl From<&str> for NativePriceEstimator {
     }
 }
 
+/// A fee charged by a fee-on-transfer token whenever it gets transferred.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TransferFee {
+    pub token: H160,
+    /// The fee in basis points of the transferred amount.
+    pub bps: u16,
+}
+
+impl FromStr for TransferFee {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (token, bps) = s
+            .split_once(':')
+            .context("expected transfer fee as <token>:<bps>")?;
+        let token =
+            H160::from_str(token).with_context(|| format!("failed to convert to H160: {token}"))?;
+        let bps = bps
+            .parse()
+            .with_context(|| format!("failed to parse basis points: {bps}"))?;
+        anyhow::ensure!(bps <= 10_000, "transfer fee exceeds 100%");
+        Ok(Self { token, bps })
+    }
+}
+
+/// The minimum reserve of a token a pool needs to have to be considered by the
+/// baseline price estimator.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MinReserve {
+    pub token: H160,
+    /// The amount in the token's atoms.
+    pub amount: u128,
+}
+
+impl FromStr for MinReserve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (token, amount) = s
+            .split_once(':')
+            .context("expected minimum reserve as <token>:<amount>")?;
+        let token =
+            H160::from_str(token).with_context(|| format!("failed to convert to H160: {token}"))?;
+        let amount = amount
+            .parse()
+            .with_context(|| format!("failed to parse amount: {amount}"))?;
+        Ok(Self { token, amount })
+    }
+}
+
 /// Shared price estimation configuration arguments.
 #[derive(clap::Parser)]
 #[group(skip)]
@@ -262,6 +312,53 @@ pub struct Arguments {
     #[clap(long, env, value_parser = U256::from_dec_str)]
     pub amount_to_estimate_prices_with: Option<U256>,
 
+    /// The maximum number of pools the baseline price estimator routes a trade
+    /// through. A value of 1 only considers direct pools between the traded
+    /// tokens.
+    #[clap(long, env, default_value = "3")]
+    pub baseline_max_hops: usize,
+
+    /// How long the baseline price estimator caches fetched pools. Cached pools
+    /// are additionally discarded whenever a new block is observed. A value of
+    /// 0 (the default) disables the cache.
+    #[clap(
+        long,
+        env,
+        default_value = "0",
+        value_parser = crate::arguments::duration_from_seconds,
+    )]
+    pub baseline_pool_cache_ttl: Duration,
+
+    /// Fees of fee-on-transfer tokens that the baseline price estimator takes
+    /// into account. Expected as a comma separated list of `<token>:<bps>`.
+    #[clap(long, env, use_value_delimiter = true)]
+    pub baseline_transfer_fees: Vec<TransferFee>,
+
+    /// Pools with less than the configured reserve of a token get ignored by
+    /// the baseline price estimator. This keeps dust pools with unrealistic
+    /// prices out of routes. Expected as a comma separated list of
+    /// `<token>:<amount>` with the amount in the token's atoms.
+    #[clap(long, env, use_value_delimiter = true)]
+    pub baseline_min_reserves: Vec<MinReserve>,
+
+    /// Makes the baseline estimator only consider the direct pool of a token
+    /// pair when its reserves are at least this many times larger than those
+    /// of every other pool of either token. Disabled if unset.
+    #[clap(long, env)]
+    pub baseline_direct_pool_dominance: Option<u32>,
+
+    /// Additional amounts in native token atoms the baseline estimator
+    /// estimates native prices with. The price used for converting the gas
+    /// costs of a query gets interpolated between the amounts closest to the
+    /// query's size.
+    #[clap(long, env, use_value_delimiter = true, value_parser = U256::from_dec_str)]
+    pub baseline_native_price_probe_amounts: Vec<U256>,
+
+    /// Lets the baseline estimator ignore gas costs for queries it can't
+    /// compute the native price of the out token for instead of failing them.
+    #[clap(long, env, action = clap::ArgAction::Set, default_value = "false")]
+    pub baseline_gasless_fallback: bool,
+
     /// The API endpoint to call the Quasimodo solver for price estimation
     #[clap(long, env)]
     pub quasimodo_solver_url: Option<Url>,
@@ -357,6 +454,33 @@ impl Display for Arguments {
             "amount_to_estimate_prices_with",
             &self.amount_to_estimate_prices_with,
         )?;
+        writeln!(f, "baseline_max_hops: {}", self.baseline_max_hops)?;
+        writeln!(
+            f,
+            "baseline_pool_cache_ttl: {:?}",
+            self.baseline_pool_cache_ttl
+        )?;
+        writeln!(
+            f,
+            "baseline_transfer_fees: {:?}",
+            self.baseline_transfer_fees
+        )?;
+        writeln!(f, "baseline_min_reserves: {:?}", self.baseline_min_reserves)?;
+        display_option(
+            f,
+            "baseline_direct_pool_dominance",
+            &self.baseline_direct_pool_dominance,
+        )?;
+        writeln!(
+            f,
+            "baseline_native_price_probe_amounts: {:?}",
+            self.baseline_native_price_probe_amounts
+        )?;
+        writeln!(
+            f,
+            "baseline_gasless_fallback: {}",
+            self.baseline_gasless_fallback
+        )?;
         display_option(f, "quasimodo_solver_url", &self.quasimodo_solver_url)?;
         display_option(f, "yearn_solver_url", &self.yearn_solver_url)?;
         writeln!(f, "yearn_solver_path: {}", self.yearn_solver_path)?;
@@ -491,6 +615,24 @@ impl Estimate {
         amounts_to_price(sell_amount, buy_amount)
     }
 
+    /// Like [`Estimate::price_in_sell_token_rational`] but rounded to an
+    /// integer with the specified rounding mode so that callers can round
+    /// conservatively.
+    pub fn price_in_sell_token_with_rounding(
+        &self,
+        query: &Query,
+        rounding: RoundingMode,
+    ) -> Option<U256> {
+        let price = self.price_in_sell_token_rational(query)?;
+        let price = match rounding {
+            RoundingMode::Floor => price.floor(),
+            RoundingMode::Ceil => price.ceil(),
+            RoundingMode::Nearest => price.round(),
+        };
+        // Can't overflow since the buy amount is at least 1.
+        Some(number::conversions::big_rational_to_u256(&price).unwrap())
+    }
+
     /// The price for the estimate denominated in sell token.
     ///
     /// The resulting price is how many units of sell_token needs to be sold for
@@ -500,6 +642,21 @@ impl Estimate {
         sell_amount.to_f64_lossy() / buy_amount.to_f64_lossy()
     }
 
+    /// Returns (sell_amount, buy_amount) in whole tokens instead of base
+    /// units, e.g. 1.5 instead of 1500000 for a token with 6 decimals.
+    ///
+    /// The conversion is lossy so this is only meant for logging and
+    /// debugging, never for computations.
+    pub fn human_readable(&self, query: &Query, sell_decimals: u8, buy_decimals: u8) -> (f64, f64) {
+        let (sell_amount, buy_amount) = self.amounts(query);
+        let scale =
+            |amount: U256, decimals: u8| amount.to_f64_lossy() / 10f64.powi(decimals.into());
+        (
+            scale(sell_amount, sell_decimals),
+            scale(buy_amount, buy_decimals),
+        )
+    }
+
     /// The price of the estimate denominated in buy token.
     ///
     /// The resulting price is how many units of buy_token are bought for one
@@ -510,11 +667,31 @@ impl Estimate {
     }
 }
 
+/// How to round a price to an integer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RoundingMode {
+    /// Rounds down.
+    Floor,
+    /// Rounds up.
+    Ceil,
+    /// Rounds to the nearest integer. Halfway cases get rounded up.
+    Nearest,
+}
+
 pub type PriceEstimateResult = Result<Estimate, PriceEstimationError>;
 
 #[mockall::automock]
 pub trait PriceEstimating: Send + Sync + 'static {
     fn estimate(&self, query: Arc<Query>) -> BoxFuture<'_, PriceEstimateResult>;
+
+    /// Estimates multiple queries at once. The results are returned in the
+    /// same order as the queries.
+    ///
+    /// Estimators can override this to share work (e.g. fetching liquidity)
+    /// between queries. By default every query gets estimated on its own.
+    fn estimate_batch(&self, queries: Vec<Arc<Query>>) -> BoxFuture<'_, Vec<PriceEstimateResult>> {
+        futures::future::join_all(queries.into_iter().map(|query| self.estimate(query))).boxed()
+    }
 }
 
 pub fn amounts_to_price(sell_amount: U256, buy_amount: U256) -> Option<BigRational> {
@@ -527,6 +704,22 @@ pub fn amounts_to_price(sell_amount: U256, buy_amount: U256) -> Option<BigRation
     ))
 }
 
+/// Picks the best estimate for `query`: the one with the highest buy amount
+/// for sell orders and the lowest sell amount for buy orders. Ties are broken
+/// in favour of the estimate using less gas.
+pub fn best_estimate(
+    query: &Query,
+    estimates: impl IntoIterator<Item = Estimate>,
+) -> Option<Estimate> {
+    estimates.into_iter().min_by(|a, b| {
+        let amounts = match query.kind {
+            OrderKind::Buy => a.out_amount.cmp(&b.out_amount),
+            OrderKind::Sell => b.out_amount.cmp(&a.out_amount),
+        };
+        amounts.then(a.gas.cmp(&b.gas))
+    })
+}
+
 pub const HEALTHY_PRICE_ESTIMATION_TIME: Duration = Duration::from_millis(5_000);
 
 pub async fn rate_limited<T>(
@@ -623,4 +816,96 @@ mod tests {
             estimator(PriceEstimatorKind::BalancerSor, address(1))
         );
     }
+
+    #[test]
+    fn price_in_sell_token_rounding() {
+        let query = Query {
+            kind: OrderKind::Sell,
+            in_amount: NonZeroU256::try_from(10).unwrap(),
+            ..Default::default()
+        };
+        let estimate = Estimate {
+            out_amount: 3.into(),
+            ..Default::default()
+        };
+        let price = |rounding| {
+            estimate
+                .price_in_sell_token_with_rounding(&query, rounding)
+                .unwrap()
+        };
+
+        // 10 / 3 isn't divisible, so rounding down and up differ by one.
+        assert_eq!(price(RoundingMode::Floor), 3.into());
+        assert_eq!(price(RoundingMode::Ceil), 4.into());
+        assert_eq!(price(RoundingMode::Nearest), 3.into());
+
+        // Zero buy amounts don't have a price.
+        let estimate = Estimate::default();
+        assert!(estimate
+            .price_in_sell_token_with_rounding(&query, RoundingMode::Floor)
+            .is_none());
+    }
+
+    #[test]
+    fn human_readable_amounts() {
+        // Selling 2 WETH (18 decimals) for 3200 USDC (6 decimals).
+        let query = Query {
+            kind: OrderKind::Sell,
+            in_amount: NonZeroU256::try_from(2_000_000_000_000_000_000u128).unwrap(),
+            ..Default::default()
+        };
+        let estimate = Estimate {
+            out_amount: 3_200_000_000u64.into(),
+            ..Default::default()
+        };
+
+        let (sell_amount, buy_amount) = estimate.human_readable(&query, 18, 6);
+        assert_eq!(sell_amount, 2.);
+        assert_eq!(buy_amount, 3200.);
+        assert_eq!(buy_amount / sell_amount, 1600.);
+    }
+
+    #[test]
+    fn best_estimate_per_order_kind() {
+        let estimate = |out_amount: u64, gas| Estimate {
+            out_amount: out_amount.into(),
+            gas,
+            ..Default::default()
+        };
+        let estimates = [estimate(100, 10), estimate(200, 10), estimate(150, 10)];
+        let query = |kind| Query {
+            kind,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            best_estimate(&query(OrderKind::Sell), estimates),
+            Some(estimate(200, 10))
+        );
+        assert_eq!(
+            best_estimate(&query(OrderKind::Buy), estimates),
+            Some(estimate(100, 10))
+        );
+        assert_eq!(best_estimate(&query(OrderKind::Sell), []), None);
+    }
+
+    #[test]
+    fn best_estimate_breaks_ties_by_gas() {
+        let estimate = |gas| Estimate {
+            out_amount: 100.into(),
+            gas,
+            ..Default::default()
+        };
+
+        for kind in [OrderKind::Sell, OrderKind::Buy] {
+            let query = Query {
+                kind,
+                ..Default::default()
+            };
+            assert_eq!(
+                best_estimate(&query, [estimate(20), estimate(10), estimate(30)]),
+                Some(estimate(10))
+            );
+        }
+    }
 }
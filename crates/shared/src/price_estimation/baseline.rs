@@ -1,6 +1,12 @@
 use {
     crate::{
-        baseline_solver::{self, estimate_buy_amount, estimate_sell_amount, BaseTokens},
+        baseline_solver::{
+            self,
+            estimate_buy_amount,
+            estimate_sell_amount,
+            BaseTokens,
+            BaselineSolvable,
+        },
         conversions::U256Ext,
         price_estimation::{
             gas,
@@ -11,28 +17,75 @@ use {
             Query,
         },
         recent_block_cache::Block,
+        request_sharing::{BoxRequestSharing, RequestSharing},
         sources::uniswap_v2::pool_fetching::{Pool, PoolFetching},
     },
-    anyhow::Result,
+    anyhow::{ensure, Context as _, Result},
     ethcontract::{H160, U256},
+    ethrpc::current_block::CurrentBlockStream,
     futures::FutureExt as _,
     gas_estimation::GasPriceEstimating,
-    model::{order::OrderKind, TokenPair},
+    itertools::Itertools as _,
+    model::{
+        order::{OrderKind, BUY_ETH_ADDRESS},
+        TokenPair,
+    },
     num::BigRational,
     number::nonzero::U256 as NonZeroU256,
-    std::{collections::HashMap, sync::Arc},
+    prometheus::{Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts},
+    std::{
+        collections::{HashMap, HashSet},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+            Mutex,
+        },
+        time::{Duration, Instant},
+    },
 };
 
 pub struct BaselinePriceEstimator {
+    inner: Arc<Inner>,
+    /// Shares the result of concurrent estimates for identical queries.
+    sharing: BoxRequestSharing<Arc<Query>, PriceEstimateResult>,
+}
+
+struct Inner {
     pool_fetcher: Arc<dyn PoolFetching>,
     gas_estimator: Arc<dyn GasPriceEstimating>,
     base_tokens: Arc<BaseTokens>,
+    /// The wrapped native token. Trades between it and the native asset
+    /// ([`BUY_ETH_ADDRESS`]) get estimated 1:1.
     native_token: H160,
+    /// The amount of native token sold for estimating native token prices,
+    /// which are used for converting gas costs. It should be small compared to
+    /// the liquidity of the native token pools to avoid slippage.
     native_token_price_estimation_amount: NonZeroU256,
+    /// Whether a too large `native_token_price_estimation_amount` was already
+    /// warned about. Later occurrences only get logged at debug level.
+    native_price_probe_warned: AtomicBool,
+    /// See [`BaselinePriceEstimatorBuilder::with_native_price_probe_amounts`].
+    /// Sorted and empty unless additional amounts are configured.
+    native_price_probe_amounts: Vec<NonZeroU256>,
     solver: H160,
+    /// The maximum number of pools a trade gets routed through.
+    max_hops: usize,
+    pool_cache: PoolCache,
+    /// Fees in basis points charged by fee-on-transfer tokens.
+    transfer_fees: HashMap<H160, u16>,
+    /// Pools with a smaller reserve of one of these tokens get ignored.
+    min_reserves: HashMap<H160, u128>,
+    /// See [`BaselinePriceEstimatorBuilder::with_direct_pool_dominance`].
+    direct_pool_dominance: Option<u32>,
+    /// See [`BaselinePriceEstimatorBuilder::with_gasless_fallback`].
+    gasless_fallback: bool,
+    gas_model: Arc<dyn GasModel>,
+    pool_metrics: Option<PoolMetrics>,
 }
 
 impl BaselinePriceEstimator {
+    /// Creates an estimator attributed to `solver` with defaults for all other
+    /// parameters. Shorthand for [`BaselinePriceEstimator::builder`].
     pub fn new(
         pool_fetcher: Arc<dyn PoolFetching>,
         gas_estimator: Arc<dyn GasPriceEstimating>,
@@ -40,66 +93,488 @@ impl BaselinePriceEstimator {
         native_token: H160,
         native_token_price_estimation_amount: NonZeroU256,
         solver: H160,
-    ) -> Self {
-        Self {
+        block_stream: CurrentBlockStream,
+    ) -> Result<Self> {
+        Self::builder(
+            pool_fetcher,
+            gas_estimator,
+            base_tokens,
+            native_token,
+            native_token_price_estimation_amount,
+            block_stream,
+        )
+        .with_solver(solver)
+        .build()
+    }
+
+    /// Returns a builder for an estimator with the required components. All
+    /// other parameters start out with defaults.
+    pub fn builder(
+        pool_fetcher: Arc<dyn PoolFetching>,
+        gas_estimator: Arc<dyn GasPriceEstimating>,
+        base_tokens: Arc<BaseTokens>,
+        native_token: H160,
+        native_token_price_estimation_amount: NonZeroU256,
+        block_stream: CurrentBlockStream,
+    ) -> BaselinePriceEstimatorBuilder {
+        BaselinePriceEstimatorBuilder {
             pool_fetcher,
             gas_estimator,
             base_tokens,
             native_token,
             native_token_price_estimation_amount,
-            solver,
+            block_stream,
+            native_price_probe_amounts: Vec::new(),
+            solver: H160::zero(),
+            max_hops: DEFAULT_MAX_POOLS,
+            pool_cache_ttl: Duration::ZERO,
+            transfer_fees: HashMap::new(),
+            min_reserves: HashMap::new(),
+            direct_pool_dominance: None,
+            gasless_fallback: false,
+            gas_model: Arc::new(DefaultGasModel),
+            pool_metrics: None,
+        }
+    }
+}
+
+/// The default maximum number of pools a trade gets routed through. It matches
+/// the baseline solver, whose [`baseline_solver::DEFAULT_MAX_HOPS`] counts
+/// intermediate tokens instead of pools.
+const DEFAULT_MAX_POOLS: usize = baseline_solver::DEFAULT_MAX_HOPS + 1;
+
+/// Builds a [`BaselinePriceEstimator`]. See [`BaselinePriceEstimator::builder`].
+pub struct BaselinePriceEstimatorBuilder {
+    pool_fetcher: Arc<dyn PoolFetching>,
+    gas_estimator: Arc<dyn GasPriceEstimating>,
+    base_tokens: Arc<BaseTokens>,
+    native_token: H160,
+    native_token_price_estimation_amount: NonZeroU256,
+    block_stream: CurrentBlockStream,
+    native_price_probe_amounts: Vec<NonZeroU256>,
+    solver: H160,
+    max_hops: usize,
+    pool_cache_ttl: Duration,
+    transfer_fees: HashMap<H160, u16>,
+    min_reserves: HashMap<H160, u128>,
+    direct_pool_dominance: Option<u32>,
+    gasless_fallback: bool,
+    gas_model: Arc<dyn GasModel>,
+    pool_metrics: Option<PoolMetrics>,
+}
+
+impl BaselinePriceEstimatorBuilder {
+    /// The solver the estimates get attributed to.
+    pub fn with_solver(mut self, solver: H160) -> Self {
+        self.solver = solver;
+        self
+    }
+
+    /// Additional amounts of the native token to estimate native token prices
+    /// with. Native prices are then interpolated between the prices of the
+    /// amounts closest to the size of the query, which accounts for slippage
+    /// of very small or large trades. Queries larger than all amounts use the
+    /// price of the largest one.
+    pub fn with_native_price_probe_amounts(mut self, amounts: Vec<NonZeroU256>) -> Self {
+        self.native_price_probe_amounts = amounts;
+        self
+    }
+
+    /// The maximum number of pools a trade gets routed through. A value of 1
+    /// only considers direct pools between the traded tokens.
+    pub fn with_max_hops(mut self, max_hops: usize) -> Self {
+        self.max_hops = max_hops;
+        self
+    }
+
+    /// How long fetched pools get cached. Zero disables the cache.
+    pub fn with_pool_cache_ttl(mut self, pool_cache_ttl: Duration) -> Self {
+        self.pool_cache_ttl = pool_cache_ttl;
+        self
+    }
+
+    /// Fees in basis points charged by fee-on-transfer tokens.
+    pub fn with_transfer_fees(mut self, transfer_fees: HashMap<H160, u16>) -> Self {
+        self.transfer_fees = transfer_fees;
+        self
+    }
+
+    /// Minimum reserves in atoms per token. Pools with a smaller reserve of
+    /// one of these tokens get ignored so that dust pools with unrealistic
+    /// prices can't win a route. Pools exactly at the minimum are kept.
+    pub fn with_min_reserves(mut self, min_reserves: HashMap<H160, u128>) -> Self {
+        self.min_reserves = min_reserves;
+        self
+    }
+
+    /// Only considers the direct path between two tokens if their direct pool
+    /// holds at least `factor` times the reserves of every other pool of
+    /// either token. Such a pool sets the price of the pair, so routing
+    /// through other pools can't do meaningfully better and enumerating
+    /// multi-hop paths is skipped. `None` (the default) always enumerates all
+    /// paths.
+    pub fn with_direct_pool_dominance(mut self, factor: Option<u32>) -> Self {
+        self.direct_pool_dominance = factor;
+        self
+    }
+
+    /// Estimates queries without considering gas costs if the price for
+    /// converting them into the out token can't be computed, for example
+    /// because there are no pools with the native token. Disabled by default,
+    /// in which case such queries fail with
+    /// [`PriceEstimationError::NoLiquidity`].
+    pub fn with_gasless_fallback(mut self, enabled: bool) -> Self {
+        self.gasless_fallback = enabled;
+        self
+    }
+
+    pub fn with_gas_model(mut self, gas_model: Arc<dyn GasModel>) -> Self {
+        self.gas_model = gas_model;
+        self
+    }
+
+    /// Registers metrics about the fetched pools with `registry`, see
+    /// [`PoolMetrics`].
+    pub fn with_pool_metrics(mut self, registry: &prometheus::Registry) -> Self {
+        self.pool_metrics = Some(PoolMetrics::new(registry));
+        self
+    }
+
+    /// Builds the estimator. Fails if the base tokens don't include the native
+    /// token, which indicates that they were configured for another network.
+    pub fn build(self) -> Result<BaselinePriceEstimator> {
+        ensure!(
+            self.base_tokens.tokens().contains(&self.native_token),
+            "base tokens don't include the native token {:?}",
+            self.native_token
+        );
+        Ok(self.build_unchecked())
+    }
+
+    fn build_unchecked(self) -> BaselinePriceEstimator {
+        let native_price_probe_amounts = if self.native_price_probe_amounts.is_empty() {
+            Vec::new()
+        } else {
+            let mut amounts = self.native_price_probe_amounts;
+            amounts.push(self.native_token_price_estimation_amount);
+            amounts.sort();
+            amounts.dedup();
+            amounts
+        };
+        let inner = Inner {
+            pool_fetcher: self.pool_fetcher,
+            gas_estimator: self.gas_estimator,
+            base_tokens: self.base_tokens,
+            native_token: self.native_token,
+            native_token_price_estimation_amount: self.native_token_price_estimation_amount,
+            native_price_probe_warned: AtomicBool::new(false),
+            native_price_probe_amounts,
+            solver: self.solver,
+            max_hops: self.max_hops,
+            pool_cache: PoolCache {
+                ttl: self.pool_cache_ttl,
+                block_stream: self.block_stream,
+                cached: Default::default(),
+            },
+            transfer_fees: self.transfer_fees,
+            min_reserves: self.min_reserves,
+            direct_pool_dominance: self.direct_pool_dominance,
+            gasless_fallback: self.gasless_fallback,
+            gas_model: self.gas_model,
+            pool_metrics: self.pool_metrics,
+        };
+        BaselinePriceEstimator {
+            inner: Arc::new(inner),
+            sharing: RequestSharing::labelled("baseline".into()),
         }
     }
 }
 
-type Pools = HashMap<TokenPair, Vec<Pool>>;
+/// The share of the native token liquidity in percent above which the amount
+/// for estimating native token prices is considered too large.
+const MAX_NATIVE_PRICE_PROBE_PERCENT: u64 = 1;
+
+type PoolMap = HashMap<TokenPair, Vec<TaxedPool>>;
+
+/// Pools fetched with [`BaselinePriceEstimator::fetch_pools`].
+#[derive(Clone, Debug)]
+pub struct Pools(PoolMap);
+
+/// A price estimate together with the route it was computed for.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EstimateWithRoute {
+    pub estimate: Estimate,
+    /// The tokens the trade gets routed through, starting with the sell token
+    /// and ending with the buy token. Empty if both tokens are the same.
+    pub route: Vec<H160>,
+}
 
 impl PriceEstimating for BaselinePriceEstimator {
     fn estimate(&self, query: Arc<Query>) -> futures::future::BoxFuture<'_, PriceEstimateResult> {
+        self.sharing
+            .shared_or_else(query, |query| {
+                let inner = self.inner.clone();
+                let query = query.clone();
+                async move {
+                    let _timer = observe::time_block!(Metrics::get().estimate_seconds, "single");
+                    inner
+                        .estimate_with_route(query, None, Block::Recent)
+                        .await
+                        .map(|estimate| estimate.estimate)
+                }
+                .boxed()
+            })
+            .boxed()
+    }
+
+    fn estimate_batch(
+        &self,
+        queries: Vec<Arc<Query>>,
+    ) -> futures::future::BoxFuture<'_, Vec<PriceEstimateResult>> {
         async move {
-            let gas_price = async {
-                let gas_price = self
-                    .gas_estimator
-                    .estimate()
-                    .await
-                    .map_err(PriceEstimationError::ProtocolInternal)?;
-                Ok(gas_price.effective_gas_price())
-            };
-            let pools = async {
-                self.pools_for_query(&query)
-                    .await
-                    .map_err(PriceEstimationError::ProtocolInternal)
+            let _timer = observe::time_block!(Metrics::get().estimate_seconds, "batch");
+            // Fetch the pools and gas price once for all queries so that pools
+            // relevant for multiple queries don't get fetched repeatedly.
+            let fetched = futures::future::try_join(
+                self.inner.gas_price(),
+                self.inner.pools_for_queries(&queries, Block::Recent),
+            )
+            .await;
+            let (gas_price, pools) = match fetched {
+                Ok(fetched) => fetched,
+                Err(err) => return queries.iter().map(|_| Err(err.clone())).collect(),
             };
-
-            let (gas_price, pools) = futures::future::try_join(gas_price, pools).await?;
-            let (path, out_amount) = self.estimate_price_helper(&query, true, &pools, gas_price)?;
-            let gas = estimate_gas(path.len());
-            Ok(Estimate {
-                out_amount,
-                gas,
-                solver: self.solver,
-            })
+            queries
+                .iter()
+                .map(|query| {
+                    self.inner
+                        .estimate_with_pools(query, &pools, gas_price, None)
+                        .map(|estimate| estimate.estimate)
+                })
+                .collect()
         }
         .boxed()
     }
 }
 
 impl BaselinePriceEstimator {
-    async fn pools_for_query(&self, query: &Query) -> Result<Pools> {
-        let pairs = self
-            .base_tokens
-            .relevant_pairs(TokenPair::new(query.buy_token, query.sell_token).into_iter());
-        let pools = self.pool_fetcher.fetch(pairs, Block::Recent).await?;
-        Ok(pools_vec_to_map(pools))
+    /// Like [`PriceEstimating::estimate`] but additionally returns the route
+    /// of the estimated trade.
+    pub async fn estimate_with_route(
+        &self,
+        query: Arc<Query>,
+    ) -> Result<EstimateWithRoute, PriceEstimationError> {
+        self.inner
+            .estimate_with_route(query, None, Block::Recent)
+            .await
+    }
+
+    /// Like [`PriceEstimating::estimate`] but uses the pools at the specified
+    /// block. This allows reproducing estimates against historic chain state,
+    /// for example when debugging price regressions on a forked node. Note that
+    /// the gas price is always the current one.
+    pub async fn estimate_at_block(
+        &self,
+        query: Arc<Query>,
+        at_block: Block,
+    ) -> Result<Estimate, PriceEstimationError> {
+        self.inner
+            .estimate_with_route(query, None, at_block)
+            .await
+            .map(|estimate| estimate.estimate)
+    }
+
+    /// Like [`BaselinePriceEstimator::estimate_with_route`] but uses the
+    /// specified price for converting gas costs instead of deriving it from
+    /// the native token pools. The price is in native token per unit of the
+    /// buy token for sell orders and of the sell token for buy orders.
+    pub async fn estimate_with_native_price(
+        &self,
+        query: Arc<Query>,
+        native_price: BigRational,
+    ) -> Result<EstimateWithRoute, PriceEstimationError> {
+        self.inner
+            .estimate_with_route(query, Some(native_price), Block::Recent)
+            .await
+    }
+
+    /// Fetches the pools relevant for the queries so that estimates for them
+    /// can be computed synchronously with
+    /// [`BaselinePriceEstimator::estimate_with_pools`]. This allows reusing
+    /// the same pools for many queries.
+    pub async fn fetch_pools(&self, queries: &[Arc<Query>]) -> Result<Pools, PriceEstimationError> {
+        self.inner
+            .pools_for_queries(queries, Block::Recent)
+            .await
+            .map(Pools)
+    }
+
+    /// Computes an estimate with already fetched pools and gas price. Queries
+    /// the pools weren't fetched for are unlikely to find any liquidity.
+    pub fn estimate_with_pools(
+        &self,
+        query: &Query,
+        pools: &Pools,
+        gas_price: f64,
+    ) -> Result<Estimate, PriceEstimationError> {
+        self.inner
+            .estimate_with_pools(query, &pools.0, gas_price, None)
+            .map(|estimate| estimate.estimate)
+    }
+}
+
+impl Inner {
+    async fn estimate_with_route(
+        &self,
+        query: Arc<Query>,
+        native_price: Option<BigRational>,
+        at_block: Block,
+    ) -> Result<EstimateWithRoute, PriceEstimationError> {
+        if let Some(estimate) = self.wrap_estimate(&query) {
+            return Ok(estimate);
+        }
+        let (gas_price, pools) = futures::future::try_join(
+            self.gas_price(),
+            self.pools_for_queries(std::slice::from_ref(&query), at_block),
+        )
+        .await?;
+        self.estimate_with_pools(&query, &pools, gas_price, native_price)
+    }
+
+    async fn gas_price(&self) -> Result<f64, PriceEstimationError> {
+        let gas_price = self
+            .gas_estimator
+            .estimate()
+            .await
+            .context("gas price estimation")
+            .map_err(PriceEstimationError::ProtocolInternal)?;
+        Ok(gas_price.effective_gas_price())
+    }
+
+    /// Fetches the pools relevant for any of the queries.
+    async fn pools_for_queries(
+        &self,
+        queries: &[Arc<Query>],
+        at_block: Block,
+    ) -> Result<PoolMap, PriceEstimationError> {
+        let pairs = self.base_tokens.relevant_pairs(
+            queries
+                .iter()
+                .filter_map(|query| TokenPair::new(query.buy_token, query.sell_token)),
+        );
+        if let Some(metrics) = &self.pool_metrics {
+            for pair in &pairs {
+                metrics
+                    .fetches
+                    .with_label_values(&[self.pair_kind(pair)])
+                    .inc();
+            }
+        }
+        let pools = self
+            .pool_cache
+            .fetch(pairs, at_block, self.pool_fetcher.as_ref())
+            .await
+            .context("pool fetching")
+            .map_err(PriceEstimationError::ProtocolInternal)?;
+        if let Some(metrics) = &self.pool_metrics {
+            metrics.pools_per_query.observe(pools.len() as f64);
+        }
+        Ok(self.pools_to_map(pools))
+    }
+
+    /// Classifies pairs by how many of their tokens are base tokens. Used as
+    /// a metric label since raw token addresses have unbounded cardinality.
+    fn pair_kind(&self, pair: &TokenPair) -> &'static str {
+        let (token0, token1) = pair.get();
+        let base_tokens = self.base_tokens.tokens();
+        match (base_tokens.contains(&token0), base_tokens.contains(&token1)) {
+            (true, true) => "base",
+            (true, false) | (false, true) => "mixed",
+            (false, false) => "other",
+        }
+    }
+
+    fn pools_to_map(&self, pools: Vec<Pool>) -> PoolMap {
+        let fee = |token| self.transfer_fees.get(&token).copied().unwrap_or_default();
+        let sufficient = |token: H160, reserve: u128| {
+            self.min_reserves
+                .get(&token)
+                .map_or(true, |min_reserve| reserve >= *min_reserve)
+        };
+        pools
+            .into_iter()
+            .filter(|pool| {
+                let (token0, token1) = pool.tokens.get();
+                sufficient(token0, pool.reserves.0) && sufficient(token1, pool.reserves.1)
+            })
+            .fold(PoolMap::new(), |mut pools, pool| {
+                let (token0, token1) = pool.tokens.get();
+                pools.entry(pool.tokens).or_default().push(TaxedPool {
+                    pool,
+                    fees: (fee(token0), fee(token1)),
+                });
+                pools
+            })
+    }
+
+    fn estimate_with_pools(
+        &self,
+        query: &Query,
+        pools: &PoolMap,
+        gas_price: f64,
+        native_price: Option<BigRational>,
+    ) -> Result<EstimateWithRoute, PriceEstimationError> {
+        if let Some(estimate) = self.wrap_estimate(query) {
+            return Ok(estimate);
+        }
+        // Note that the sub-queries for native token prices compute their own
+        // routes which are only used for the gas costs. The returned route is
+        // the one of the actual trade.
+        let (route, out_amount) =
+            self.estimate_price_helper(query, true, pools, gas_price, native_price)?;
+        let gas = self.gas_model.estimate_gas(route.len());
+        Ok(EstimateWithRoute {
+            estimate: Estimate {
+                out_amount,
+                gas,
+                solver: self.solver,
+            },
+            route,
+        })
+    }
+
+    /// Returns a 1:1 estimate including the cost of wrapping or unwrapping
+    /// if the query trades between the native asset and the wrapped native
+    /// token. There are no pools for this pair.
+    fn wrap_estimate(&self, query: &Query) -> Option<EstimateWithRoute> {
+        let gas = if query.sell_token == self.native_token && query.buy_token == BUY_ETH_ADDRESS {
+            gas::GAS_PER_WETH_UNWRAP
+        } else if query.sell_token == BUY_ETH_ADDRESS && query.buy_token == self.native_token {
+            gas::GAS_PER_WETH_WRAP
+        } else {
+            return None;
+        };
+        Some(EstimateWithRoute {
+            estimate: Estimate {
+                out_amount: query.in_amount.get(),
+                gas,
+                solver: self.solver,
+            },
+            route: vec![query.sell_token, query.buy_token],
+        })
     }
 
     /// Returns the path and the out amount.
+    ///
+    /// If `native_price` is set it is used as the price of the token gas costs
+    /// get converted into instead of deriving it from pools.
     fn estimate_price_helper(
         &self,
         query: &Query,
         consider_gas_costs: bool,
-        pools: &Pools,
+        pools: &PoolMap,
         gas_price: f64,
+        native_price: Option<BigRational>,
     ) -> Result<(Vec<H160>, U256), PriceEstimationError> {
         if query.sell_token == query.buy_token {
             return Ok((Vec::new(), query.in_amount.get()));
@@ -108,25 +583,7 @@ impl BaselinePriceEstimator {
             OrderKind::Buy => {
                 // Do not consider gas costs below to avoid infinite recursion.
                 let sell_token_price_in_native_token = if consider_gas_costs {
-                    Some(if query.sell_token == self.native_token {
-                        num::one()
-                    } else {
-                        let buy_amount = self
-                            .best_execution_sell_order(
-                                self.native_token,
-                                query.sell_token,
-                                self.native_token_price_estimation_amount,
-                                gas_price,
-                                None,
-                                pools,
-                            )?
-                            .1;
-                        super::amounts_to_price(
-                            self.native_token_price_estimation_amount.get(),
-                            buy_amount,
-                        )
-                        .ok_or(PriceEstimationError::NoLiquidity)?
-                    })
+                    self.gas_cost_price(query, query.sell_token, gas_price, pools, native_price)?
                 } else {
                     None
                 };
@@ -143,25 +600,7 @@ impl BaselinePriceEstimator {
             OrderKind::Sell => {
                 // Do not consider gas costs below to avoid infinite recursion.
                 let buy_token_price_in_native_token = if consider_gas_costs {
-                    Some(if query.buy_token == self.native_token {
-                        num::one()
-                    } else {
-                        let buy_amount = self
-                            .best_execution_sell_order(
-                                self.native_token,
-                                query.buy_token,
-                                self.native_token_price_estimation_amount,
-                                gas_price,
-                                None,
-                                pools,
-                            )?
-                            .1;
-                        super::amounts_to_price(
-                            self.native_token_price_estimation_amount.get(),
-                            buy_amount,
-                        )
-                        .ok_or(PriceEstimationError::NoLiquidity)?
-                    })
+                    self.gas_cost_price(query, query.buy_token, gas_price, pools, native_price)?
                 } else {
                     None
                 };
@@ -178,6 +617,173 @@ impl BaselinePriceEstimator {
         }
     }
 
+    /// Returns the price of `token` in native token for converting the gas
+    /// costs of `query`, preferring the explicitly passed `native_price`.
+    ///
+    /// Returns `None` if gas costs should be ignored because the price can't
+    /// be computed and the gasless fallback is enabled.
+    fn gas_cost_price(
+        &self,
+        query: &Query,
+        token: H160,
+        gas_price: f64,
+        pools: &PoolMap,
+        native_price: Option<BigRational>,
+    ) -> Result<Option<BigRational>, PriceEstimationError> {
+        if let Some(native_price) = native_price {
+            return Ok(Some(native_price));
+        }
+        if token == self.native_token {
+            return Ok(Some(num::one()));
+        }
+        match self.query_native_price(query, token, gas_price, pools) {
+            Ok(price) => Ok(Some(price)),
+            Err(PriceEstimationError::NoLiquidity) if self.gasless_fallback => {
+                tracing::warn!(
+                    ?token,
+                    "no native price for converting gas costs; estimating without gas costs"
+                );
+                Metrics::get().gasless_fallback.inc();
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns the price of `token` in native token for converting the gas
+    /// costs of `query`. `token` is the token the out amount of the query is
+    /// denominated in.
+    ///
+    /// With multiple native price probe amounts the price is interpolated
+    /// based on the size of the query's out amount in native token.
+    fn query_native_price(
+        &self,
+        query: &Query,
+        token: H160,
+        gas_price: f64,
+        pools: &PoolMap,
+    ) -> Result<BigRational, PriceEstimationError> {
+        let price = self.native_price(token, gas_price, pools)?;
+        let (Some(smallest), Some(largest)) = (
+            self.native_price_probe_amounts.first(),
+            self.native_price_probe_amounts.last(),
+        ) else {
+            return Ok(price);
+        };
+
+        let out_amount = self
+            .estimate_price_helper(query, false, pools, gas_price, None)?
+            .1;
+        let size = out_amount.to_big_rational() * &price;
+        if size <= smallest.get().to_big_rational() {
+            return self.native_price_for_amount(token, *smallest, gas_price, pools);
+        }
+        if size >= largest.get().to_big_rational() {
+            tracing::debug!(
+                %out_amount,
+                largest = %largest.get(),
+                "query larger than all native price probe amounts; extrapolating"
+            );
+            return self.native_price_for_amount(token, *largest, gas_price, pools);
+        }
+        let (lower, upper) = self
+            .native_price_probe_amounts
+            .iter()
+            .tuple_windows()
+            .find(|(_, upper)| size <= upper.get().to_big_rational())
+            .expect("size is between the smallest and largest amount");
+        let lower_price = self.native_price_for_amount(token, *lower, gas_price, pools)?;
+        let upper_price = self.native_price_for_amount(token, *upper, gas_price, pools)?;
+        let lower = lower.get().to_big_rational();
+        let upper = upper.get().to_big_rational();
+        Ok(&lower_price + (&upper_price - &lower_price) * (size - &lower) / (upper - lower))
+    }
+
+    /// Returns the price of `token` in native token by selling
+    /// `native_token_price_estimation_amount` of the native token for it.
+    fn native_price(
+        &self,
+        token: H160,
+        gas_price: f64,
+        pools: &PoolMap,
+    ) -> Result<BigRational, PriceEstimationError> {
+        let amount = self.native_token_price_estimation_amount;
+        let (path, buy_amount) = self.best_execution_sell_order(
+            self.native_token,
+            token,
+            amount,
+            gas_price,
+            None,
+            pools,
+        )?;
+        if self.native_price_probe_too_large(&path, pools) {
+            tracing::debug!(
+                amount = %amount.get(),
+                ?path,
+                "native token price estimation amount is large compared to the liquidity"
+            );
+            if !self.native_price_probe_warned.swap(true, Ordering::Relaxed) {
+                tracing::warn!(
+                    amount = %amount.get(),
+                    "native token price estimation amount is large compared to the liquidity, \
+                     gas costs are skewed by slippage; further occurrences are logged at debug \
+                     level"
+                );
+            }
+        }
+        super::amounts_to_price(amount.get(), buy_amount).ok_or(PriceEstimationError::NoLiquidity)
+    }
+
+    /// Returns the price of `token` in native token by selling `amount` of the
+    /// native token for it.
+    fn native_price_for_amount(
+        &self,
+        token: H160,
+        amount: NonZeroU256,
+        gas_price: f64,
+        pools: &PoolMap,
+    ) -> Result<BigRational, PriceEstimationError> {
+        let buy_amount = self
+            .best_execution_sell_order(self.native_token, token, amount, gas_price, None, pools)?
+            .1;
+        super::amounts_to_price(amount.get(), buy_amount).ok_or(PriceEstimationError::NoLiquidity)
+    }
+
+    /// Whether the amount used for estimating native token prices exceeds
+    /// [`MAX_NATIVE_PRICE_PROBE_PERCENT`] of the native token reserves of the
+    /// pools the native token gets sold into first on `path`. The more of the
+    /// reserves the amount is, the more slippage the native price estimate
+    /// incurs and the more the gas costs are skewed.
+    fn native_price_probe_too_large(&self, path: &[H160], pools: &PoolMap) -> bool {
+        let Some(pair) = path
+            .get(1)
+            .and_then(|next| TokenPair::new(self.native_token, *next))
+        else {
+            return false;
+        };
+        let liquidity = pools
+            .get(&pair)
+            .into_iter()
+            .flatten()
+            .map(|taxed| {
+                if taxed.pool.tokens.get().0 == self.native_token {
+                    taxed.pool.reserves.0
+                } else {
+                    taxed.pool.reserves.1
+                }
+            })
+            .fold(U256::zero(), |sum, reserve| {
+                sum.saturating_add(reserve.into())
+            });
+        // Without liquidity the estimate fails anyway.
+        !liquidity.is_zero()
+            && self
+                .native_token_price_estimation_amount
+                .get()
+                .saturating_mul(100.into())
+                > liquidity.saturating_mul(MAX_NATIVE_PRICE_PROBE_PERCENT.into())
+    }
+
     /// Returns path and out (buy) amount.
     /// If buy_token_price_in_native_token is set then it will be used to take
     /// gas cost into account.
@@ -188,9 +794,9 @@ impl BaselinePriceEstimator {
         sell_amount: NonZeroU256,
         gas_price: f64,
         buy_token_price_in_native_token: Option<BigRational>,
-        pools: &Pools,
+        pools: &PoolMap,
     ) -> Result<(Vec<H160>, U256), PriceEstimationError> {
-        let path_comparison = |buy_estimate: baseline_solver::Estimate<U256, Pool>| {
+        let path_comparison = |buy_estimate: baseline_solver::Estimate<U256, TaxedPool>| {
             if let Some(buy_token_price_in_native_token) = &buy_token_price_in_native_token {
                 let buy_amount_in_native_token =
                     buy_estimate.value.to_big_rational() * buy_token_price_in_native_token;
@@ -229,9 +835,9 @@ impl BaselinePriceEstimator {
         buy_amount: NonZeroU256,
         gas_price: f64,
         sell_token_price_in_native_token: Option<BigRational>,
-        pools: &Pools,
+        pools: &PoolMap,
     ) -> Result<(Vec<H160>, U256), PriceEstimationError> {
-        let path_comparison = |sell_estimate: baseline_solver::Estimate<U256, Pool>| {
+        let path_comparison = |sell_estimate: baseline_solver::Estimate<U256, TaxedPool>| {
             if let Some(sell_token_price_in_native_token) = &sell_token_price_in_native_token {
                 let sell_amount_in_native_token =
                     sell_estimate.value.to_big_rational() * sell_token_price_in_native_token;
@@ -267,16 +873,31 @@ impl BaselinePriceEstimator {
         amount: NonZeroU256,
         comparison: CompareFn,
         resulting_amount: AmountFn,
-        pools: &Pools,
+        pools: &PoolMap,
     ) -> Result<(Vec<H160>, Amount), PriceEstimationError>
     where
-        AmountFn: Fn(U256, &[H160], &HashMap<TokenPair, Vec<Pool>>) -> Option<Amount>,
-        CompareFn: Fn(U256, &[H160], &HashMap<TokenPair, Vec<Pool>>) -> O,
+        AmountFn: Fn(U256, &[H160], &PoolMap) -> Option<Amount>,
+        CompareFn: Fn(U256, &[H160], &PoolMap) -> O,
         O: Ord,
     {
         debug_assert!(sell_token != buy_token);
 
-        let path_candidates = self.base_tokens.path_candidates(sell_token, buy_token);
+        if self.max_hops >= 1 && self.direct_pool_dominates(sell_token, buy_token, pools) {
+            let path = vec![sell_token, buy_token];
+            if let Some(resulting_amount) = resulting_amount(amount.get(), &path, pools) {
+                return Ok((path, resulting_amount));
+            }
+        }
+
+        // A path with `n` intermediate tokens trades through `n + 1` pools.
+        let path_candidates = match self.max_hops.checked_sub(1) {
+            Some(intermediate_tokens) => self.base_tokens.path_candidates_with_hops(
+                sell_token,
+                buy_token,
+                intermediate_tokens,
+            ),
+            None => HashSet::new(),
+        };
         let best_path = path_candidates
             .iter()
             .max_by_key(|path| comparison(amount.get(), path, pools))
@@ -285,16 +906,273 @@ impl BaselinePriceEstimator {
             .ok_or(PriceEstimationError::NoLiquidity)?;
         Ok((best_path.clone(), resulting_amount))
     }
+
+    /// Whether the direct pools of the two tokens dominate all other pools of
+    /// either token, see
+    /// [`BaselinePriceEstimatorBuilder::with_direct_pool_dominance`].
+    fn direct_pool_dominates(&self, token_a: H160, token_b: H160, pools: &PoolMap) -> bool {
+        let Some(factor) = self.direct_pool_dominance else {
+            return false;
+        };
+        let reserve = |taxed: &TaxedPool, token: H160| {
+            let (token0, token1) = taxed.pool.tokens.get();
+            if token0 == token {
+                Some(taxed.pool.reserves.0)
+            } else if token1 == token {
+                Some(taxed.pool.reserves.1)
+            } else {
+                None
+            }
+        };
+        let Some(pair) = TokenPair::new(token_a, token_b) else {
+            return false;
+        };
+        let Some(direct) = pools.get(&pair) else {
+            return false;
+        };
+        [token_a, token_b].into_iter().all(|token| {
+            let direct_reserve = direct
+                .iter()
+                .filter_map(|taxed| reserve(taxed, token))
+                .max()
+                .unwrap_or_default();
+            let other_reserve = pools
+                .iter()
+                .filter(|(other, _)| **other != pair)
+                .flat_map(|(_, pools)| pools)
+                .filter_map(|taxed| reserve(taxed, token))
+                .max()
+                .unwrap_or_default();
+            direct_reserve > 0
+                && U256::from(direct_reserve) >= U256::from(other_reserve) * U256::from(factor)
+        })
+    }
 }
 
-fn pools_vec_to_map(pools: Vec<Pool>) -> Pools {
-    pools.into_iter().fold(Pools::new(), |mut pools, pool| {
-        pools.entry(pool.tokens).or_default().push(pool);
-        pools
-    })
+/// Caches the pools fetched for token pairs for a short time so that
+/// estimates for tokens sharing pools (e.g. pools with base tokens) don't fetch
+/// them repeatedly.
+///
+/// Cached pools expire after the configured TTL and when a new block is
+/// observed. A TTL of zero disables the cache.
+struct PoolCache {
+    ttl: Duration,
+    block_stream: CurrentBlockStream,
+    cached: Mutex<CachedPools>,
+}
+
+#[derive(Default)]
+struct CachedPools {
+    /// The block at which the cached pools were fetched.
+    block: u64,
+    pools: HashMap<TokenPair, (Instant, Vec<Pool>)>,
+}
+
+impl PoolCache {
+    async fn fetch(
+        &self,
+        pairs: HashSet<TokenPair>,
+        at_block: Block,
+        fetcher: &dyn PoolFetching,
+    ) -> Result<Vec<Pool>> {
+        // Only the pools of the most recent block get cached.
+        if self.ttl.is_zero() || at_block != Block::Recent {
+            return fetcher.fetch(pairs, at_block).await;
+        }
+
+        let block = self.block_stream.borrow().number;
+        let now = Instant::now();
+        let (mut pools, missing) = {
+            let mut cached = self.cached.lock().unwrap();
+            if cached.block != block {
+                cached.block = block;
+                cached.pools.clear();
+            }
+            let mut pools = Vec::new();
+            let mut missing = HashSet::new();
+            for pair in pairs {
+                match cached.pools.get(&pair) {
+                    Some((fetched_at, entry)) if now.duration_since(*fetched_at) < self.ttl => {
+                        pools.extend_from_slice(entry)
+                    }
+                    _ => {
+                        missing.insert(pair);
+                    }
+                }
+            }
+            (pools, missing)
+        };
+        if missing.is_empty() {
+            return Ok(pools);
+        }
+
+        let fetched = fetcher.fetch(missing.clone(), Block::Recent).await?;
+        {
+            let mut cached = self.cached.lock().unwrap();
+            // Don't cache pools that are already outdated because a new block
+            // was observed in the meantime.
+            if cached.block == block {
+                // Pairs without pools get cached, too, so they don't get
+                // fetched again.
+                for pair in missing {
+                    cached.pools.insert(pair, (now, Vec::new()));
+                }
+                for pool in &fetched {
+                    if let Some((_, pools)) = cached.pools.get_mut(&pool.tokens) {
+                        pools.push(*pool);
+                    }
+                }
+            }
+        }
+        pools.extend(fetched);
+        Ok(pools)
+    }
+}
+
+/// A pool that accounts for the fees of fee-on-transfer tokens.
+///
+/// Fees get charged on every transfer into and out of the pool, so the pool
+/// receives less than the sent amount and the receiver gets less than the pool
+/// sends. Tokens without fees behave exactly like the underlying pool.
+#[derive(Clone, Copy, Debug)]
+struct TaxedPool {
+    pool: Pool,
+    /// The fees in basis points of the tokens of the pool in the same order as
+    /// the pool's token pair.
+    fees: (u16, u16),
+}
+
+impl TaxedPool {
+    const BPS_BASE: u64 = 10_000;
+
+    fn fee(&self, token: H160) -> u16 {
+        if token == self.pool.tokens.get().0 {
+            self.fees.0
+        } else {
+            self.fees.1
+        }
+    }
+
+    /// Returns the amount received when transferring `amount` of `token`.
+    fn after_fee(&self, token: H160, amount: U256) -> Option<U256> {
+        match self.fee(token) {
+            0 => Some(amount),
+            bps => amount
+                .checked_mul((Self::BPS_BASE - u64::from(bps)).into())?
+                .checked_div(Self::BPS_BASE.into()),
+        }
+    }
+
+    /// Returns the amount of `token` that needs to be transferred so that
+    /// `amount` gets received.
+    fn before_fee(&self, token: H160, amount: U256) -> Option<U256> {
+        match self.fee(token) {
+            0 => Some(amount),
+            bps => amount
+                .checked_mul(Self::BPS_BASE.into())?
+                .checked_ceil_div(&(Self::BPS_BASE.checked_sub(u64::from(bps))?).into()),
+        }
+    }
+}
+
+impl BaselineSolvable for TaxedPool {
+    fn get_amount_out(&self, out_token: H160, (in_amount, in_token): (U256, H160)) -> Option<U256> {
+        let in_amount = self.after_fee(in_token, in_amount)?;
+        let out_amount = self.pool.get_amount_out(out_token, (in_amount, in_token))?;
+        self.after_fee(out_token, out_amount)
+    }
+
+    fn get_amount_in(&self, in_token: H160, (out_amount, out_token): (U256, H160)) -> Option<U256> {
+        let out_amount = self.before_fee(out_token, out_amount)?;
+        let in_amount = self.pool.get_amount_in(in_token, (out_amount, out_token))?;
+        self.before_fee(in_token, in_amount)
+    }
+
+    fn gas_cost(&self) -> usize {
+        self.pool.gas_cost()
+    }
+}
+
+#[derive(prometheus_metric_storage::MetricStorage)]
+#[metric(subsystem = "baseline_price_estimator")]
+struct Metrics {
+    /// Time it takes to compute estimates including fetching pools.
+    #[metric(labels("kind"))]
+    estimate_seconds: HistogramVec,
+    /// Number of queries estimated without gas costs because the native price
+    /// couldn't be computed, see
+    /// [`BaselinePriceEstimatorBuilder::with_gasless_fallback`].
+    #[metric(name = "gasless_fallback_total")]
+    gasless_fallback: IntCounter,
+}
+
+impl Metrics {
+    fn get() -> &'static Self {
+        Metrics::instance(observe::metrics::get_storage_registry()).unwrap()
+    }
+}
+
+/// Metrics about the pools fetched for estimates. Unlike [`Metrics`] these are
+/// opt-in since they are only needed for analyzing the pool fetching load.
+struct PoolMetrics {
+    /// Pairs pools get fetched for, labelled by [`Inner::pair_kind`].
+    fetches: IntCounterVec,
+    /// Number of pools fetched for a query or batch of queries.
+    pools_per_query: Histogram,
+}
+
+impl PoolMetrics {
+    fn new(registry: &prometheus::Registry) -> Self {
+        let metrics = Self {
+            fetches: IntCounterVec::new(
+                Opts::new(
+                    "baseline_pool_fetches_total",
+                    "Number of token pairs pools got fetched for by pair kind.",
+                ),
+                &["pair"],
+            )
+            .unwrap(),
+            pools_per_query: Histogram::with_opts(HistogramOpts::new(
+                "baseline_pools_per_query",
+                "Number of pools fetched per query.",
+            ))
+            .unwrap(),
+        };
+        for result in [
+            registry.register(Box::new(metrics.fetches.clone())),
+            registry.register(Box::new(metrics.pools_per_query.clone())),
+        ] {
+            if let Err(err) = result {
+                tracing::warn!(?err, "failed to register baseline pool metrics");
+            }
+        }
+        metrics
+    }
+}
+
+/// Estimates the gas used by settling a single trade along a route.
+///
+/// This allows using chain specific models, for example for L2s where
+/// calldata is priced differently.
+pub trait GasModel: Send + Sync {
+    /// Returns the gas used by a trade along a route visiting `path_len`
+    /// tokens.
+    fn estimate_gas(&self, path_len: usize) -> u64;
+}
+
+/// The gas model for Ethereum mainnet, see [`estimate_gas`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultGasModel;
+
+impl GasModel for DefaultGasModel {
+    fn estimate_gas(&self, path_len: usize) -> u64 {
+        estimate_gas(path_len)
+    }
 }
 
-fn estimate_gas(path_len: usize) -> u64 {
+/// Returns the gas used by a trade along a route visiting `path_len` tokens on
+/// Ethereum mainnet. A route without any hops uses no gas.
+pub fn estimate_gas(path_len: usize) -> u64 {
     let hops = match path_len.checked_sub(1) {
         Some(len) => len,
         None => return 0,
@@ -313,27 +1191,53 @@ mod tests {
             gas_price_estimation::FakeGasPriceEstimator,
             sources::uniswap_v2::pool_fetching::{test_util::FakePoolFetcher, Pool},
         },
+        ethrpc::current_block::{mock_single_block, BlockInfo},
         gas_estimation::gas_price::GasPrice1559,
-        std::sync::Mutex,
+        std::sync::atomic::{AtomicUsize, Ordering},
     };
 
-    #[tokio::test]
-    async fn return_error_if_no_token_found() {
-        let token_a = H160::from_low_u64_be(1);
-        let token_b = H160::from_low_u64_be(2);
-        let pool_fetcher = Arc::new(FakePoolFetcher(vec![]));
-        let gas_estimator = Arc::new(FakeGasPriceEstimator(Arc::new(Mutex::new(
-            Default::default(),
-        ))));
-        let base_tokens = Arc::new(BaseTokens::new(H160::zero(), &[]));
-        let estimator = BaselinePriceEstimator::new(
+    /// Returns a builder with fake gas prices and a fixed block that estimates
+    /// native prices with 10 atoms of `native_token`.
+    fn builder(
+        pool_fetcher: Arc<dyn PoolFetching>,
+        base_tokens: BaseTokens,
+        native_token: H160,
+    ) -> BaselinePriceEstimatorBuilder {
+        BaselinePriceEstimator::builder(
             pool_fetcher,
-            gas_estimator,
-            base_tokens,
-            token_a,
-            NonZeroU256::try_from(1).unwrap(),
-            H160([1; 20]),
-        );
+            Arc::new(FakeGasPriceEstimator::default()),
+            Arc::new(base_tokens),
+            native_token,
+            NonZeroU256::try_from(10).unwrap(),
+            mock_single_block(Default::default()),
+        )
+    }
+
+    fn query(sell_token: H160, buy_token: H160, in_amount: u128, kind: OrderKind) -> Arc<Query> {
+        Arc::new(Query {
+            verification: None,
+            sell_token,
+            buy_token,
+            in_amount: NonZeroU256::try_from(in_amount).unwrap(),
+            kind,
+        })
+    }
+
+    fn pool(address: u64, token_a: H160, token_b: H160, reserves: (u128, u128)) -> Pool {
+        Pool::uniswap(
+            H160::from_low_u64_be(address),
+            TokenPair::new(token_a, token_b).unwrap(),
+            reserves,
+        )
+    }
+
+    #[tokio::test]
+    async fn return_error_if_no_token_found() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let pool_fetcher = Arc::new(FakePoolFetcher(vec![]));
+        let base_tokens = BaseTokens::new(H160::zero(), &[]);
+        let estimator = builder(pool_fetcher, base_tokens, token_a).build_unchecked();
 
         assert!(estimator
             .estimate(Arc::new(Query {
@@ -359,18 +1263,8 @@ mod tests {
         );
 
         let pool_fetcher = Arc::new(FakePoolFetcher(vec![pool]));
-        let gas_estimator = Arc::new(FakeGasPriceEstimator(Arc::new(Mutex::new(
-            Default::default(),
-        ))));
-        let base_tokens = Arc::new(BaseTokens::new(H160::zero(), &[]));
-        let estimator = BaselinePriceEstimator::new(
-            pool_fetcher,
-            gas_estimator,
-            base_tokens,
-            token_a,
-            NonZeroU256::try_from(1).unwrap(),
-            H160([1; 20]),
-        );
+        let base_tokens = BaseTokens::new(H160::zero(), &[]);
+        let estimator = builder(pool_fetcher, base_tokens, token_a).build_unchecked();
 
         assert!(estimator
             .estimate(Arc::new(Query {
@@ -400,18 +1294,8 @@ mod tests {
         );
 
         let pool_fetcher = Arc::new(FakePoolFetcher(vec![pool]));
-        let gas_estimator = Arc::new(FakeGasPriceEstimator(Arc::new(Mutex::new(
-            Default::default(),
-        ))));
-        let base_tokens = Arc::new(BaseTokens::new(base_token, &[]));
-        let estimator = BaselinePriceEstimator::new(
-            pool_fetcher,
-            gas_estimator,
-            base_tokens,
-            token_b,
-            NonZeroU256::try_from(1).unwrap(),
-            H160([1; 20]),
-        );
+        let base_tokens = BaseTokens::new(base_token, &[]);
+        let estimator = builder(pool_fetcher, base_tokens, token_b).build_unchecked();
 
         assert!(estimator
             .estimate(Arc::new(Query {
@@ -470,18 +1354,8 @@ mod tests {
         ];
 
         let pool_fetcher = Arc::new(FakePoolFetcher(pools.clone()));
-        let gas_estimator = Arc::new(FakeGasPriceEstimator(Arc::new(Mutex::new(
-            Default::default(),
-        ))));
-        let base_tokens = Arc::new(BaseTokens::new(H160::zero(), &[]));
-        let estimator = BaselinePriceEstimator::new(
-            pool_fetcher,
-            gas_estimator,
-            base_tokens,
-            token_a,
-            NonZeroU256::try_from(10).unwrap(),
-            H160([1; 20]),
-        );
+        let base_tokens = BaseTokens::new(H160::zero(), &[]);
+        let estimator = builder(pool_fetcher, base_tokens, token_a).build_unchecked();
 
         let query = Arc::new(Query {
             verification: None,
@@ -538,18 +1412,8 @@ mod tests {
         ];
 
         let pool_fetcher = Arc::new(FakePoolFetcher(pools));
-        let gas_estimator = Arc::new(FakeGasPriceEstimator(Arc::new(Mutex::new(
-            Default::default(),
-        ))));
-        let base_tokens = Arc::new(BaseTokens::new(intermediate, &[]));
-        let estimator = BaselinePriceEstimator::new(
-            pool_fetcher,
-            gas_estimator,
-            base_tokens,
-            intermediate,
-            NonZeroU256::try_from(10).unwrap(),
-            H160([1; 20]),
-        );
+        let base_tokens = BaseTokens::new(intermediate, &[]);
+        let estimator = builder(pool_fetcher, base_tokens, intermediate).build_unchecked();
 
         for kind in &[OrderKind::Sell, OrderKind::Buy] {
             let intermediate = estimator
@@ -580,6 +1444,726 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn excludes_paths_with_too_many_hops() {
+        let token_a = H160::from_low_u64_be(1);
+        let intermediate_1 = H160::from_low_u64_be(2);
+        let intermediate_2 = H160::from_low_u64_be(3);
+        let token_b = H160::from_low_u64_be(4);
+
+        // The only route from token_a to token_b goes through 3 pools.
+        let pools = vec![
+            pool(1, token_a, intermediate_1, (1000, 1000)),
+            pool(2, intermediate_1, intermediate_2, (1000, 1000)),
+            pool(3, intermediate_2, token_b, (1000, 1000)),
+        ];
+
+        let estimator = |max_hops| {
+            builder(
+                Arc::new(FakePoolFetcher(pools.clone())),
+                BaseTokens::new(intermediate_1, &[intermediate_2]),
+                token_b,
+            )
+            .with_max_hops(max_hops)
+            .build_unchecked()
+        };
+        let query = query(token_a, token_b, 10, OrderKind::Sell);
+
+        let estimate = estimator(3).estimate(query.clone()).await.unwrap();
+        assert_eq!(estimate.gas, estimate_gas(4));
+
+        // The default also routes through up to 3 pools.
+        let default = builder(
+            Arc::new(FakePoolFetcher(pools.clone())),
+            BaseTokens::new(intermediate_1, &[intermediate_2]),
+            token_b,
+        )
+        .build_unchecked();
+        assert_eq!(default.estimate(query.clone()).await.unwrap(), estimate);
+
+        let result = estimator(2).estimate(query).await;
+        assert!(matches!(result, Err(PriceEstimationError::NoLiquidity)));
+    }
+
+    #[test]
+    fn builder_requires_native_token_in_base_tokens() {
+        let native_token = H160::from_low_u64_be(1);
+        let builder = |base_tokens| {
+            builder(
+                Arc::new(FakePoolFetcher::default()),
+                base_tokens,
+                native_token,
+            )
+        };
+
+        let estimator = builder(BaseTokens::new(native_token, &[]))
+            .with_max_hops(1)
+            .build()
+            .unwrap();
+        assert_eq!(estimator.inner.max_hops, 1);
+        assert_eq!(estimator.inner.solver, H160::zero());
+
+        let other_network = BaseTokens::new(H160::from_low_u64_be(2), &[]);
+        assert!(builder(other_network).build().is_err());
+    }
+
+    struct FailingGasPriceEstimator;
+
+    #[async_trait::async_trait]
+    impl GasPriceEstimating for FailingGasPriceEstimator {
+        async fn estimate_with_limits(&self, _: f64, _: Duration) -> Result<GasPrice1559> {
+            Err(anyhow::anyhow!("gas price unavailable"))
+        }
+    }
+
+    struct FailingPoolFetcher;
+
+    #[async_trait::async_trait]
+    impl PoolFetching for FailingPoolFetcher {
+        async fn fetch(&self, _: HashSet<TokenPair>, _: Block) -> Result<Vec<Pool>> {
+            Err(anyhow::anyhow!("node unavailable"))
+        }
+    }
+
+    #[tokio::test]
+    async fn protocol_errors_identify_failing_component() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let estimator = |pool_fetcher: Arc<dyn PoolFetching>,
+                         gas_estimator: Arc<dyn GasPriceEstimating>| {
+            BaselinePriceEstimator::builder(
+                pool_fetcher,
+                gas_estimator,
+                Arc::new(BaseTokens::new(token_b, &[])),
+                token_b,
+                NonZeroU256::try_from(10).unwrap(),
+                mock_single_block(Default::default()),
+            )
+            .build_unchecked()
+        };
+        let query = query(token_a, token_b, 10, OrderKind::Sell);
+
+        let cases: [(_, Arc<dyn PoolFetching>, Arc<dyn GasPriceEstimating>); 2] = [
+            (
+                "gas price estimation",
+                Arc::new(FakePoolFetcher::default()),
+                Arc::new(FailingGasPriceEstimator),
+            ),
+            (
+                "pool fetching",
+                Arc::new(FailingPoolFetcher),
+                Arc::new(FakeGasPriceEstimator::default()),
+            ),
+        ];
+        for (context, pool_fetcher, gas_estimator) in cases {
+            let result = estimator(pool_fetcher, gas_estimator)
+                .estimate(query.clone())
+                .await;
+            match result {
+                Err(PriceEstimationError::ProtocolInternal(err)) => {
+                    assert!(format!("{err:#}").starts_with(context));
+                }
+                _ => panic!("unexpected result {result:?}"),
+            }
+        }
+    }
+
+    /// Counts how often pools get fetched.
+    struct CountingPoolFetcher(FakePoolFetcher, AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl PoolFetching for CountingPoolFetcher {
+        async fn fetch(
+            &self,
+            token_pairs: HashSet<TokenPair>,
+            at_block: Block,
+        ) -> Result<Vec<Pool>> {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            self.0.fetch(token_pairs, at_block).await
+        }
+    }
+
+    /// Records the blocks pools get fetched at.
+    struct RecordingPoolFetcher(FakePoolFetcher, Mutex<Vec<Block>>);
+
+    #[async_trait::async_trait]
+    impl PoolFetching for RecordingPoolFetcher {
+        async fn fetch(
+            &self,
+            token_pairs: HashSet<TokenPair>,
+            at_block: Block,
+        ) -> Result<Vec<Pool>> {
+            self.1.lock().unwrap().push(at_block);
+            self.0.fetch(token_pairs, at_block).await
+        }
+    }
+
+    #[tokio::test]
+    async fn estimate_at_block_fetches_pools_at_block() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+
+        let pool_fetcher = Arc::new(RecordingPoolFetcher(
+            FakePoolFetcher(vec![pool(1, token_a, token_b, (1000, 1000))]),
+            Default::default(),
+        ));
+        let estimator = builder(pool_fetcher.clone(), BaseTokens::new(token_b, &[]), token_b)
+            .with_pool_cache_ttl(Duration::from_secs(3600))
+            .build_unchecked();
+        let query = query(token_a, token_b, 10, OrderKind::Sell);
+
+        let recent = estimator.estimate(query.clone()).await.unwrap();
+        // Pinned estimates bypass the cache of recent pools.
+        let pinned = estimator
+            .estimate_at_block(query, Block::Number(42))
+            .await
+            .unwrap();
+        assert_eq!(pinned, recent);
+        assert_eq!(
+            *pool_fetcher.1.lock().unwrap(),
+            [Block::Recent, Block::Number(42)]
+        );
+    }
+
+    #[tokio::test]
+    async fn estimate_batch_fetches_pools_once_and_isolates_errors() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let token_c = H160::from_low_u64_be(3);
+
+        let pool_fetcher = Arc::new(CountingPoolFetcher(
+            FakePoolFetcher(vec![pool(1, token_a, token_b, (1000, 1000))]),
+            AtomicUsize::new(0),
+        ));
+        let estimator =
+            builder(pool_fetcher.clone(), BaseTokens::new(token_b, &[]), token_b).build_unchecked();
+        let query = |buy_token| query(token_a, buy_token, 10, OrderKind::Sell);
+
+        let results = estimator
+            .estimate_batch(vec![query(token_b), query(token_c)])
+            .await;
+
+        assert_eq!(pool_fetcher.1.load(Ordering::SeqCst), 1);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(PriceEstimationError::NoLiquidity)));
+    }
+
+    #[tokio::test]
+    async fn shares_concurrent_identical_estimates() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+
+        let pool_fetcher = Arc::new(CountingPoolFetcher(
+            FakePoolFetcher(vec![pool(1, token_a, token_b, (1000, 1000))]),
+            AtomicUsize::new(0),
+        ));
+        let estimator =
+            builder(pool_fetcher.clone(), BaseTokens::new(token_b, &[]), token_b).build_unchecked();
+        let query = |buy_token| query(token_a, buy_token, 10, OrderKind::Sell);
+
+        let results =
+            futures::future::join_all((0..10).map(|_| estimator.estimate(query(token_b)))).await;
+        assert_eq!(pool_fetcher.1.load(Ordering::SeqCst), 1);
+        let estimate = results[0].as_ref().unwrap();
+        assert!(results
+            .iter()
+            .all(|result| result.as_ref().unwrap() == estimate));
+
+        // Errors get shared as well.
+        let results = futures::future::join_all(
+            (0..10).map(|_| estimator.estimate(query(H160::from_low_u64_be(3)))),
+        )
+        .await;
+        assert_eq!(pool_fetcher.1.load(Ordering::SeqCst), 2);
+        assert!(results
+            .iter()
+            .all(|result| matches!(result, Err(PriceEstimationError::NoLiquidity))));
+    }
+
+    #[tokio::test]
+    async fn estimates_wrapping_and_unwrapping_one_to_one() {
+        let native_token = H160::from_low_u64_be(1);
+        let pool_fetcher = Arc::new(CountingPoolFetcher(
+            FakePoolFetcher::default(),
+            AtomicUsize::new(0),
+        ));
+        let estimator = builder(
+            pool_fetcher.clone(),
+            BaseTokens::new(native_token, &[]),
+            native_token,
+        )
+        .build_unchecked();
+
+        for (sell_token, buy_token, gas) in [
+            (native_token, BUY_ETH_ADDRESS, gas::GAS_PER_WETH_UNWRAP),
+            (BUY_ETH_ADDRESS, native_token, gas::GAS_PER_WETH_WRAP),
+        ] {
+            for kind in [OrderKind::Sell, OrderKind::Buy] {
+                let estimate = estimator
+                    .estimate_with_route(query(sell_token, buy_token, 42, kind))
+                    .await
+                    .unwrap();
+                assert_eq!(estimate.estimate.out_amount, 42.into());
+                assert_eq!(estimate.estimate.gas, gas);
+                assert_eq!(estimate.route, [sell_token, buy_token]);
+            }
+        }
+        assert_eq!(pool_fetcher.1.load(Ordering::SeqCst), 0);
+
+        // Other trades with the native asset still need pools.
+        let result = estimator
+            .estimate(query(
+                H160::from_low_u64_be(2),
+                BUY_ETH_ADDRESS,
+                42,
+                OrderKind::Sell,
+            ))
+            .await;
+        assert!(matches!(result, Err(PriceEstimationError::NoLiquidity)));
+    }
+
+    #[tokio::test]
+    async fn caches_pools_until_ttl_expires_or_block_changes() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+
+        let pool_fetcher = Arc::new(CountingPoolFetcher(
+            FakePoolFetcher(vec![pool(1, token_a, token_b, (1000, 1000))]),
+            AtomicUsize::new(0),
+        ));
+        let (block_sender, block_stream) = tokio::sync::watch::channel(BlockInfo::default());
+        let estimator = |ttl| {
+            BaselinePriceEstimator::builder(
+                pool_fetcher.clone(),
+                Arc::new(FakeGasPriceEstimator::default()),
+                Arc::new(BaseTokens::new(token_b, &[])),
+                token_b,
+                NonZeroU256::try_from(10).unwrap(),
+                block_stream.clone(),
+            )
+            .with_pool_cache_ttl(ttl)
+            .build_unchecked()
+        };
+        let query = query(token_a, token_b, 10, OrderKind::Sell);
+        let fetches = || pool_fetcher.1.load(Ordering::SeqCst);
+
+        let cached = estimator(Duration::from_secs(3600));
+        let estimate = cached.estimate(query.clone()).await.unwrap();
+        assert_eq!(cached.estimate(query.clone()).await.unwrap(), estimate);
+        assert_eq!(fetches(), 1);
+
+        block_sender.send_modify(|block| block.number += 1);
+        cached.estimate(query.clone()).await.unwrap();
+        assert_eq!(fetches(), 2);
+
+        let uncached = estimator(Duration::ZERO);
+        uncached.estimate(query.clone()).await.unwrap();
+        uncached.estimate(query).await.unwrap();
+        assert_eq!(fetches(), 4);
+    }
+
+    #[tokio::test]
+    async fn transfer_fees_reduce_out_amount() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+
+        let pool = pool(1, token_a, token_b, (10u128.pow(21), 10u128.pow(21)));
+        let estimator = |transfer_fees| {
+            builder(
+                Arc::new(FakePoolFetcher(vec![pool])),
+                BaseTokens::new(token_b, &[]),
+                token_b,
+            )
+            .with_transfer_fees(transfer_fees)
+            .build_unchecked()
+        };
+        let in_amount = U256::exp10(18);
+        let query = Arc::new(Query {
+            verification: None,
+            sell_token: token_a,
+            buy_token: token_b,
+            in_amount: NonZeroU256::try_from(in_amount).unwrap(),
+            kind: OrderKind::Sell,
+        });
+
+        let fee_free = estimator(HashMap::new())
+            .estimate(query.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            fee_free.out_amount,
+            pool.get_amount_out(token_b, (in_amount, token_a)).unwrap()
+        );
+
+        // 1% of the sold tokens are lost when transferring them into the pool.
+        let with_fee = estimator(HashMap::from([(token_a, 100)]))
+            .estimate(query)
+            .await
+            .unwrap();
+        assert_eq!(
+            with_fee.out_amount,
+            pool.get_amount_out(token_b, (in_amount * 99 / 100, token_a))
+                .unwrap()
+        );
+        assert!(with_fee.out_amount < fee_free.out_amount);
+    }
+
+    #[tokio::test]
+    async fn min_reserves_exclude_dust_pools() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+
+        // The dust pool offers a much better price than the real one.
+        let dust = pool(1, token_a, token_b, (1_000, 100_000));
+        let real = pool(2, token_a, token_b, (1_000_000, 1_000_000));
+        let estimator = |min_reserves| {
+            builder(
+                Arc::new(FakePoolFetcher(vec![dust, real])),
+                BaseTokens::new(token_b, &[]),
+                token_b,
+            )
+            .with_min_reserves(min_reserves)
+            .build()
+            .unwrap()
+        };
+        let in_amount = U256::from(100);
+        let query = query(token_a, token_b, 100, OrderKind::Sell);
+        let out_amount = |pool: &Pool| pool.get_amount_out(token_b, (in_amount, token_a));
+
+        let unfiltered = estimator(HashMap::new())
+            .estimate(query.clone())
+            .await
+            .unwrap();
+        assert_eq!(Some(unfiltered.out_amount), out_amount(&dust));
+
+        // The real pool has exactly the minimum reserve and is kept.
+        let filtered = estimator(HashMap::from([(token_a, 1_000_000)]))
+            .estimate(query)
+            .await
+            .unwrap();
+        assert_eq!(Some(filtered.out_amount), out_amount(&real));
+    }
+
+    #[tokio::test]
+    async fn dominant_direct_pool_matches_full_enumeration() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let weth = H160::from_low_u64_be(3);
+
+        let pools = vec![
+            pool(1, token_a, token_b, (10u128.pow(12), 10u128.pow(12))),
+            pool(2, token_a, weth, (10u128.pow(6), 10u128.pow(6))),
+            pool(3, weth, token_b, (10u128.pow(6), 10u128.pow(6))),
+        ];
+        let estimator = |dominance| {
+            builder(
+                Arc::new(FakePoolFetcher(pools.clone())),
+                BaseTokens::new(weth, &[]),
+                weth,
+            )
+            .with_direct_pool_dominance(dominance)
+            .build()
+            .unwrap()
+        };
+
+        for kind in [OrderKind::Sell, OrderKind::Buy] {
+            let query = query(token_a, token_b, 1_000, kind);
+            let full = estimator(None)
+                .estimate_with_route(query.clone())
+                .await
+                .unwrap();
+            let short_circuited = estimator(Some(10))
+                .estimate_with_route(query)
+                .await
+                .unwrap();
+            assert_eq!(full.estimate, short_circuited.estimate);
+            assert_eq!(full.route, short_circuited.route);
+            assert_eq!(short_circuited.route, [token_a, token_b]);
+        }
+    }
+
+    #[test]
+    fn native_prices_depend_on_query_size_with_probe_amounts() {
+        let token_x = H160::from_low_u64_be(1);
+        let token_t = H160::from_low_u64_be(2);
+        let weth = H160::from_low_u64_be(3);
+
+        let pools = vec![
+            pool(1, token_x, token_t, (10u128.pow(12), 10u128.pow(12))),
+            pool(2, weth, token_t, (10u128.pow(6), 10u128.pow(6))),
+        ];
+        let estimator = |probe_amounts: Vec<u128>| {
+            BaselinePriceEstimator::builder(
+                Arc::new(FakePoolFetcher(Vec::new())),
+                Arc::new(FakeGasPriceEstimator::default()),
+                Arc::new(BaseTokens::new(weth, &[])),
+                weth,
+                NonZeroU256::try_from(1_000).unwrap(),
+                mock_single_block(Default::default()),
+            )
+            .with_native_price_probe_amounts(
+                probe_amounts
+                    .into_iter()
+                    .map(|amount| NonZeroU256::try_from(amount).unwrap())
+                    .collect(),
+            )
+            .build()
+            .unwrap()
+        };
+        let query = |in_amount: u128| Query {
+            verification: None,
+            sell_token: token_x,
+            buy_token: token_t,
+            in_amount: NonZeroU256::try_from(in_amount).unwrap(),
+            kind: OrderKind::Sell,
+        };
+
+        let single = estimator(Vec::new());
+        let pool_map = single.inner.pools_to_map(pools.clone());
+        let price = |estimator: &BaselinePriceEstimator, in_amount| {
+            estimator
+                .inner
+                .query_native_price(&query(in_amount), token_t, 0., &pool_map)
+                .unwrap()
+        };
+        let probe_price = |amount: u128| {
+            single
+                .inner
+                .native_price_for_amount(
+                    token_t,
+                    NonZeroU256::try_from(amount).unwrap(),
+                    0.,
+                    &pool_map,
+                )
+                .unwrap()
+        };
+        let (small_price, large_price) = (probe_price(1_000), probe_price(100_000));
+        // Larger probes incur more slippage so each token costs more.
+        assert!(large_price > small_price);
+
+        // A single amount results in the same price for all query sizes.
+        assert_eq!(price(&single, 100), small_price);
+        assert_eq!(price(&single, 10_000_000), small_price);
+
+        let piecewise = estimator(vec![100_000]);
+        assert_eq!(price(&piecewise, 100), small_price);
+        // Queries larger than all probes use the largest one.
+        assert_eq!(price(&piecewise, 10_000_000), large_price);
+        // Sizes between the probes are interpolated.
+        let medium = price(&piecewise, 10_000);
+        assert!(small_price < medium && medium < large_price);
+    }
+
+    #[tokio::test]
+    async fn gasless_fallback_estimates_without_native_pools() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let weth = H160::from_low_u64_be(3);
+
+        let estimator = |gasless_fallback| {
+            builder(
+                Arc::new(FakePoolFetcher(vec![pool(
+                    1,
+                    token_a,
+                    token_b,
+                    (1000, 1000),
+                )])),
+                BaseTokens::new(weth, &[]),
+                weth,
+            )
+            .with_gasless_fallback(gasless_fallback)
+            .build()
+            .unwrap()
+        };
+        let query = query(token_a, token_b, 10, OrderKind::Sell);
+
+        let result = estimator(false).estimate(query.clone()).await;
+        assert!(matches!(result, Err(PriceEstimationError::NoLiquidity)));
+
+        let fallbacks = Metrics::get().gasless_fallback.get();
+        let estimate = estimator(true).estimate(query).await.unwrap();
+        assert!(estimate.out_amount > U256::zero());
+        assert_eq!(Metrics::get().gasless_fallback.get(), fallbacks + 1);
+    }
+
+    #[tokio::test]
+    async fn pool_metrics_count_fetches_per_query() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+
+        let registry = prometheus::Registry::new();
+        let estimator = builder(
+            Arc::new(FakePoolFetcher(vec![pool(
+                1,
+                token_a,
+                token_b,
+                (1000, 1000),
+            )])),
+            BaseTokens::new(token_b, &[]),
+            token_b,
+        )
+        .with_pool_metrics(&registry)
+        .build()
+        .unwrap();
+        let query = |in_amount| query(token_a, token_b, in_amount, OrderKind::Sell);
+
+        estimator.estimate(query(10)).await.unwrap();
+        estimator.estimate(query(20)).await.unwrap();
+
+        let metric = |name| {
+            registry
+                .gather()
+                .into_iter()
+                .find(|family| family.get_name() == name)
+                .unwrap()
+                .get_metric()[0]
+                .clone()
+        };
+        let fetches = metric("baseline_pool_fetches_total");
+        assert_eq!(fetches.get_label()[0].get_value(), "mixed");
+        assert_eq!(fetches.get_counter().get_value(), 2.);
+        let pools = metric("baseline_pools_per_query");
+        assert_eq!(pools.get_histogram().get_sample_count(), 2);
+        assert_eq!(pools.get_histogram().get_sample_sum(), 2.);
+    }
+
+    #[tokio::test]
+    async fn estimates_with_fetched_pools() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+
+        let pool_fetcher = Arc::new(CountingPoolFetcher(
+            FakePoolFetcher(vec![pool(1, token_a, token_b, (1000, 1000))]),
+            AtomicUsize::new(0),
+        ));
+        let estimator = builder(pool_fetcher.clone(), BaseTokens::new(token_b, &[]), token_b)
+            .build()
+            .unwrap();
+        let queries = [10, 20].map(|in_amount| query(token_a, token_b, in_amount, OrderKind::Sell));
+
+        let pools = estimator.fetch_pools(&queries).await.unwrap();
+        for query in &queries {
+            assert_eq!(
+                estimator.estimate_with_pools(query, &pools, 0.).unwrap(),
+                estimator.estimate(query.clone()).await.unwrap()
+            );
+        }
+        // Once for the shared pools and once for every async estimate.
+        assert_eq!(pool_fetcher.1.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn detects_native_price_probe_exceeding_liquidity() {
+        let native_token = H160::from_low_u64_be(1);
+        let token = H160::from_low_u64_be(2);
+
+        let estimator = |amount: u128| {
+            BaselinePriceEstimator::builder(
+                Arc::new(FakePoolFetcher(vec![pool(
+                    1,
+                    native_token,
+                    token,
+                    (10_000, 10_000),
+                )])),
+                Arc::new(FakeGasPriceEstimator::default()),
+                Arc::new(BaseTokens::new(native_token, &[])),
+                native_token,
+                NonZeroU256::try_from(amount).unwrap(),
+                mock_single_block(Default::default()),
+            )
+            .build()
+            .unwrap()
+        };
+        let query = query(token, native_token, 10, OrderKind::Sell);
+
+        // 1% of the native token reserves is fine.
+        let small = estimator(100);
+        let pools = small.fetch_pools(&[query.clone()]).await.unwrap();
+        let path = [native_token, token];
+        assert!(!small.inner.native_price_probe_too_large(&path, &pools.0));
+
+        let large = estimator(1_000);
+        assert!(large.inner.native_price_probe_too_large(&path, &pools.0));
+
+        // The larger amount incurs more slippage so the native price of the
+        // token gets overestimated.
+        let price = |estimator: &BaselinePriceEstimator| {
+            estimator.inner.native_price(token, 0., &pools.0).unwrap()
+        };
+        assert!(price(&large) > price(&small));
+    }
+
+    #[tokio::test]
+    async fn estimate_with_route_returns_intermediate_token() {
+        let token_a = H160::from_low_u64_be(1);
+        let intermediate = H160::from_low_u64_be(2);
+        let token_b = H160::from_low_u64_be(3);
+
+        let pools = vec![
+            pool(1, token_a, intermediate, (1000, 1000)),
+            pool(2, intermediate, token_b, (1000, 1000)),
+        ];
+
+        let estimator = builder(
+            Arc::new(FakePoolFetcher(pools)),
+            BaseTokens::new(intermediate, &[]),
+            intermediate,
+        )
+        .build_unchecked();
+
+        for kind in [OrderKind::Sell, OrderKind::Buy] {
+            let estimate = estimator
+                .estimate_with_route(query(token_a, token_b, 10, kind))
+                .await
+                .unwrap();
+            assert_eq!(estimate.route, [token_a, intermediate, token_b]);
+            assert_eq!(estimate.estimate.gas, estimate_gas(3));
+        }
+    }
+
+    #[tokio::test]
+    async fn uses_configured_gas_model() {
+        struct HopGasModel;
+
+        impl GasModel for HopGasModel {
+            fn estimate_gas(&self, path_len: usize) -> u64 {
+                path_len as u64 * 1000
+            }
+        }
+
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+
+        let estimator = |gas_model: Arc<dyn GasModel>| {
+            builder(
+                Arc::new(FakePoolFetcher(vec![pool(
+                    1,
+                    token_a,
+                    token_b,
+                    (1000, 1000),
+                )])),
+                BaseTokens::new(token_b, &[]),
+                token_b,
+            )
+            .with_gas_model(gas_model)
+            .build_unchecked()
+        };
+        let query = query(token_a, token_b, 10, OrderKind::Sell);
+
+        let estimate = estimator(Arc::new(DefaultGasModel))
+            .estimate(query.clone())
+            .await
+            .unwrap();
+        assert_eq!(estimate.gas, estimate_gas(2));
+
+        let estimate = estimator(Arc::new(HopGasModel))
+            .estimate(query)
+            .await
+            .unwrap();
+        assert_eq!(estimate.gas, 2000);
+    }
+
     #[tokio::test]
     async fn price_estimate_takes_gas_costs_into_account() {
         let native = H160::from_low_u64_be(0);
@@ -627,14 +2211,15 @@ mod tests {
             max_priority_fee_per_gas: 10000.0,
         }))));
         let base_tokens = Arc::new(BaseTokens::new(native, &[intermediate]));
-        let estimator = BaselinePriceEstimator::new(
+        let estimator = BaselinePriceEstimator::builder(
             pool_fetcher,
             gas_estimator.clone(),
             base_tokens,
             native,
             NonZeroU256::try_from(1_000_000_000).unwrap(),
-            H160([1; 20]),
-        );
+            mock_single_block(Default::default()),
+        )
+        .build_unchecked();
 
         // Uses 1 hop because high gas price doesn't make the intermediate hop worth it.
         for order_kind in [OrderKind::Sell, OrderKind::Buy].iter() {
@@ -680,6 +2265,72 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn native_price_override_changes_gas_cost_tradeoff() {
+        let native = H160::from_low_u64_be(0);
+        let sell = H160::from_low_u64_be(1);
+        let intermediate = H160::from_low_u64_be(2);
+        let buy = H160::from_low_u64_be(3);
+
+        // Same pools as in `price_estimate_takes_gas_costs_into_account`.
+        let pools = vec![
+            pool(1, native, sell, (100_000_000_000, 2_000)),
+            pool(2, native, buy, (100_000_000_000, 1_000)),
+            pool(3, sell, buy, (1000, 800)),
+            pool(4, sell, intermediate, (1000, 1000)),
+            pool(5, intermediate, buy, (1000, 1000)),
+        ];
+        let gas_estimator = Arc::new(FakeGasPriceEstimator(Arc::new(Mutex::new(GasPrice1559 {
+            base_fee_per_gas: 0.0,
+            max_fee_per_gas: 10000.0,
+            max_priority_fee_per_gas: 10000.0,
+        }))));
+        let estimator = BaselinePriceEstimator::builder(
+            Arc::new(FakePoolFetcher(pools)),
+            gas_estimator.clone(),
+            Arc::new(BaseTokens::new(native, &[intermediate])),
+            native,
+            NonZeroU256::try_from(1_000_000_000).unwrap(),
+            mock_single_block(Default::default()),
+        )
+        .build_unchecked();
+        let query = |kind| query(sell, buy, 10, kind);
+
+        for kind in [OrderKind::Sell, OrderKind::Buy] {
+            // With the pool derived price the high gas price doesn't make the
+            // intermediate hop worth it but a much more valuable token does.
+            let estimate = estimator.estimate(query(kind)).await.unwrap();
+            assert_eq!(estimate.gas, estimate_gas(2));
+            let estimate = estimator
+                .estimate_with_native_price(
+                    query(kind),
+                    BigRational::from_integer(10_000_000_000u64.into()),
+                )
+                .await
+                .unwrap();
+            assert_eq!(estimate.estimate.gas, estimate_gas(3));
+        }
+
+        // Reduce gas price.
+        *gas_estimator.0.lock().unwrap() = GasPrice1559 {
+            base_fee_per_gas: 0.0,
+            max_fee_per_gas: 1.0,
+            max_priority_fee_per_gas: 1.0,
+        };
+
+        for kind in [OrderKind::Sell, OrderKind::Buy] {
+            // Now the intermediate hop is worth it unless the token is much
+            // less valuable.
+            let estimate = estimator.estimate(query(kind)).await.unwrap();
+            assert_eq!(estimate.gas, estimate_gas(3));
+            let estimate = estimator
+                .estimate_with_native_price(query(kind), num::one())
+                .await
+                .unwrap();
+            assert_eq!(estimate.estimate.gas, estimate_gas(2));
+        }
+    }
+
     #[tokio::test]
     async fn estimate_price_honours_parameter_consider_gas_costs() {
         let token_a = H160::from_low_u64_be(1);
@@ -704,17 +2355,20 @@ mod tests {
             TokenPair::new(token_a, token_c).unwrap(),
             (1004 * 10u128.pow(25), 10u128.pow(28)),
         );
-        let pools = pools_vec_to_map(vec![pool_ab, pool_bc, pool_ac]);
 
         let base_tokens = Arc::new(BaseTokens::new(token_b, &[]));
-        let estimator = BaselinePriceEstimator::new(
+        let estimator = BaselinePriceEstimator::builder(
             Arc::new(FakePoolFetcher::default()),
             Arc::new(FakeGasPriceEstimator::default()),
             base_tokens,
             token_a,
             NonZeroU256::try_from(10u128.pow(18)).unwrap(),
-            H160([1; 20]),
-        );
+            mock_single_block(Default::default()),
+        )
+        .build_unchecked();
+        let pools = estimator
+            .inner
+            .pools_to_map(vec![pool_ab, pool_bc, pool_ac]);
 
         let gas_price = 1000000000000000.0;
         let query = Query {
@@ -725,11 +2379,13 @@ mod tests {
             kind: OrderKind::Sell,
         };
         let out_amount_considering_gas_costs = estimator
-            .estimate_price_helper(&query, true, &pools, gas_price)
+            .inner
+            .estimate_price_helper(&query, true, &pools, gas_price, None)
             .unwrap()
             .1;
         let out_amount_disregarding_gas_costs = estimator
-            .estimate_price_helper(&query, false, &pools, gas_price)
+            .inner
+            .estimate_price_helper(&query, false, &pools, gas_price, None)
             .unwrap()
             .1;
         assert!(out_amount_considering_gas_costs != out_amount_disregarding_gas_costs);
@@ -417,14 +417,44 @@ impl PriceEstimatorCreating for BaselinePriceEstimator {
     type Params = H160;
 
     fn init(factory: &PriceEstimatorFactory, _name: &str, solver: Self::Params) -> Result<Self> {
-        Ok(BaselinePriceEstimator::new(
+        BaselinePriceEstimator::builder(
             factory.components.uniswap_v2_pools.clone(),
             factory.components.gas_price.clone(),
             factory.network.base_tokens.clone(),
             factory.network.native_token,
             factory.native_token_price_estimation_amount()?,
-            solver,
-        ))
+            factory.network.block_stream.clone(),
+        )
+        .with_solver(solver)
+        .with_max_hops(factory.args.baseline_max_hops)
+        .with_pool_cache_ttl(factory.args.baseline_pool_cache_ttl)
+        .with_transfer_fees(
+            factory
+                .args
+                .baseline_transfer_fees
+                .iter()
+                .map(|fee| (fee.token, fee.bps))
+                .collect(),
+        )
+        .with_min_reserves(
+            factory
+                .args
+                .baseline_min_reserves
+                .iter()
+                .map(|reserve| (reserve.token, reserve.amount))
+                .collect(),
+        )
+        .with_direct_pool_dominance(factory.args.baseline_direct_pool_dominance)
+        .with_native_price_probe_amounts(
+            factory
+                .args
+                .baseline_native_price_probe_amounts
+                .iter()
+                .map(|amount| NonZeroU256::try_from(*amount))
+                .collect::<Result<_>>()?,
+        )
+        .with_gasless_fallback(factory.args.baseline_gasless_fallback)
+        .build()
     }
 }
 impl PriceEstimatorCreating for ParaswapPriceEstimator {
@@ -483,8 +513,11 @@ impl PriceEstimatorCreating for OneInchPriceEstimator {
             factory.shared_args.disabled_one_inch_protocols.clone(),
             factory.rate_limiter(name),
             factory.shared_args.one_inch_referrer_address,
+            factory.shared_args.one_inch_referrer_fee,
             solver,
             factory.network.settlement,
+            factory.shared_args.one_inch_slippage,
+            factory.shared_args.one_inch_max_concurrent_requests,
         ))
     }
 
@@ -6,13 +6,13 @@ use {
         Query,
     },
     crate::{
-        oneinch_api::OneInchClient,
+        oneinch_api::{OneInchClient, ReferrerFee, Slippage},
         rate_limiter::RateLimiter,
         trade_finding::oneinch::OneInchTradeFinder,
     },
     futures::FutureExt as _,
     primitive_types::H160,
-    std::sync::Arc,
+    std::{num::NonZeroUsize, sync::Arc},
 };
 
 pub struct OneInchPriceEstimator(TradeEstimator);
@@ -23,16 +23,23 @@ impl OneInchPriceEstimator {
         disabled_protocols: Vec<String>,
         rate_limiter: Arc<RateLimiter>,
         referrer_address: Option<H160>,
+        referrer_fee: Option<ReferrerFee>,
         solver: H160,
         settlement_contract: H160,
+        slippage: Slippage,
+        max_concurrent_requests: NonZeroUsize,
     ) -> Self {
         Self(TradeEstimator::new(
             Arc::new(OneInchTradeFinder::new(
                 api,
                 disabled_protocols,
                 referrer_address,
+                referrer_fee,
                 solver,
                 settlement_contract,
+                slippage,
+                Default::default(),
+                max_concurrent_requests,
             )),
             rate_limiter,
             "oneinch".into(),
@@ -73,8 +80,11 @@ mod tests {
                     "test".into(),
                 )),
                 None,
+                None,
                 H160([1; 20]),
                 H160([2; 20]),
+                Slippage::ONE_PERCENT,
+                NonZeroUsize::new(10).unwrap(),
             )
         }
     }
@@ -160,6 +170,7 @@ mod tests {
                     Err(RestError {
                         status_code: 500,
                         description: "Internal Server Error".to_string(),
+                        retry_after: None,
                     }
                     .into())
                 }
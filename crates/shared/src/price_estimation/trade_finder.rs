@@ -105,6 +105,11 @@ impl Inner {
         match (&self.verifier, &query.verification) {
             (Some(verifier), Some(verification)) => {
                 let trade = self.finder.get_trade(&query).await?;
+                if trade.estimated {
+                    return Err(PriceEstimationError::EstimatorInternal(anyhow!(
+                        "estimated trades can't be verified"
+                    )));
+                }
                 let price_query = PriceQuery {
                     sell_token: query.sell_token,
                     buy_token: query.buy_token,
@@ -372,7 +377,7 @@ impl From<TradeError> for PriceEstimationError {
             TradeError::NoLiquidity => Self::NoLiquidity,
             TradeError::UnsupportedOrderType(order_type) => Self::UnsupportedOrderType(order_type),
             TradeError::DeadlineExceeded => Self::EstimatorInternal(anyhow!("timeout")),
-            TradeError::RateLimited => Self::RateLimited,
+            TradeError::RateLimited { .. } => Self::RateLimited,
             TradeError::Other(err) => Self::EstimatorInternal(err),
         }
     }
@@ -581,7 +581,9 @@ mod tests {
         let chain_id = web3.eth().chain_id().await.unwrap().as_u64();
 
         println!("Indexing events for chain {chain_id}");
-        observe::tracing::initialize_reentrant("warn,shared=debug,ethrpc=trace");
+        observe::tracing::initialize_reentrant(&observe::tracing::Config::new(
+            "warn,shared=debug,ethrpc=trace",
+        ));
 
         let pool_initializer = EmptyPoolInitializer::for_chain(chain_id);
         let token_infos = TokenInfoFetcher { web3: web3.clone() };
@@ -1,9 +1,11 @@
 //! A module for abstracting a component that can produce a quote with calldata
 //! for a specified token pair and amount.
 
+pub mod caching;
 pub mod external;
 pub mod oneinch;
 pub mod paraswap;
+pub mod racing;
 pub mod zeroex;
 
 use {
@@ -13,6 +15,7 @@ use {
     ethcontract::{contract::MethodBuilder, tokens::Tokenize, web3::Transport, Bytes, H160, U256},
     model::interaction::InteractionData,
     serde::Serialize,
+    std::time::Duration,
     thiserror::Error,
 };
 
@@ -25,6 +28,35 @@ use {
 pub trait TradeFinding: Send + Sync + 'static {
     async fn get_quote(&self, query: &Query) -> Result<Quote, TradeError>;
     async fn get_trade(&self, query: &Query) -> Result<Trade, TradeError>;
+
+    /// Finds trades for multiple queries concurrently. The results are in the
+    /// same order as the queries.
+    async fn get_trades(&self, queries: &[Query]) -> Vec<Result<Trade, TradeError>> {
+        futures::future::join_all(queries.iter().map(|query| self.get_trade(query))).await
+    }
+
+    /// Checks whether the trade finder is currently able to find trades so
+    /// that unhealthy trade finders can be avoided before real orders arrive.
+    ///
+    /// The default implementation requests a quote for a small canonical
+    /// mainnet trade (1 WETH to USDC). Trade finders for other networks or
+    /// with a cheaper way of checking their health should override it.
+    async fn health_check(&self) -> Result<(), TradeError> {
+        self.get_quote(&health_check_query()).await.map(|_| ())
+    }
+}
+
+/// The query used by the default [`TradeFinding::health_check`].
+fn health_check_query() -> Query {
+    Query {
+        verification: None,
+        // WETH
+        sell_token: addr!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+        // USDC
+        buy_token: addr!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+        in_amount: number::nonzero::U256::try_from(10u128.pow(18)).unwrap(),
+        kind: model::order::OrderKind::Sell,
+    }
 }
 
 /// A quote.
@@ -32,9 +64,21 @@ pub trait TradeFinding: Send + Sync + 'static {
 pub struct Quote {
     pub out_amount: U256,
     pub gas_estimate: u64,
+    /// How the gas estimate splits into the swap and the settlement overhead.
+    /// `None` if the trade finder only knows the total.
+    pub gas_breakdown: Option<GasBreakdown>,
     pub solver: H160,
 }
 
+/// The parts of a quote's gas estimate. They add up to the gas estimate.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GasBreakdown {
+    /// Gas used by the swap with the liquidity source itself.
+    pub swap_gas: u64,
+    /// Gas used by the settlement around the swap.
+    pub overhead_gas: u64,
+}
+
 /// A trade.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Trade {
@@ -42,6 +86,13 @@ pub struct Trade {
     pub gas_estimate: u64,
     pub interactions: Vec<Interaction>,
     pub solver: H160,
+    /// The liquidity protocols the trade gets routed through. Empty if the
+    /// trade finder doesn't report them.
+    pub protocols: Vec<String>,
+    /// Whether the interactions are only an approximation of the trade, e.g.
+    /// without the actual swap calldata. Such trades can't be executed or
+    /// simulated.
+    pub estimated: bool,
 }
 
 impl Trade {
@@ -73,6 +124,8 @@ impl Trade {
             gas_estimate,
             interactions,
             solver,
+            protocols: Vec::new(),
+            estimated: false,
         }
     }
 
@@ -132,7 +185,11 @@ pub enum TradeError {
     DeadlineExceeded,
 
     #[error("Rate limited")]
-    RateLimited,
+    RateLimited {
+        /// How long the rate limited API asked to wait before retrying, if
+        /// it said so.
+        retry_after: Option<Duration>,
+    },
 
     #[error(transparent)]
     Other(#[from] anyhow::Error),
@@ -148,7 +205,7 @@ impl From<PriceEstimationError> for TradeError {
             PriceEstimationError::UnsupportedToken { token, .. } => {
                 Self::UnsupportedOrderType(format!("{token:#x}"))
             }
-            PriceEstimationError::RateLimited => Self::RateLimited,
+            PriceEstimationError::RateLimited => Self::RateLimited { retry_after: None },
             PriceEstimationError::EstimatorInternal(err)
             | PriceEstimationError::ProtocolInternal(err) => Self::Other(err),
         }
@@ -163,7 +220,9 @@ impl Clone for TradeError {
                 Self::UnsupportedOrderType(order_type.clone())
             }
             Self::DeadlineExceeded => Self::DeadlineExceeded,
-            Self::RateLimited => Self::RateLimited,
+            Self::RateLimited { retry_after } => Self::RateLimited {
+                retry_after: *retry_after,
+            },
             Self::Other(err) => Self::Other(crate::clone_anyhow_error(err)),
         }
     }
@@ -175,7 +234,61 @@ pub fn map_interactions(interactions: &[InteractionData]) -> Vec<Interaction> {
 
 #[cfg(test)]
 mod tests {
-    use {super::*, hex_literal::hex};
+    use {
+        super::*,
+        hex_literal::hex,
+        model::order::OrderKind,
+        number::nonzero::U256 as NonZeroU256,
+    };
+
+    #[tokio::test]
+    async fn get_trades_preserves_order_and_isolates_errors() {
+        /// Forwards to the mock, which would otherwise also replace the default
+        /// `get_trades` under test.
+        struct Forward(MockTradeFinding);
+
+        #[async_trait::async_trait]
+        impl TradeFinding for Forward {
+            async fn get_quote(&self, query: &Query) -> Result<Quote, TradeError> {
+                self.0.get_quote(query).await
+            }
+
+            async fn get_trade(&self, query: &Query) -> Result<Trade, TradeError> {
+                self.0.get_trade(query).await
+            }
+        }
+
+        // Only finds trades for sell orders.
+        let mut inner = MockTradeFinding::new();
+        inner
+            .expect_get_trade()
+            .times(3)
+            .returning(|query| match query.kind {
+                OrderKind::Sell => Ok(Trade {
+                    out_amount: query.in_amount.get(),
+                    ..Default::default()
+                }),
+                OrderKind::Buy => Err(TradeError::NoLiquidity),
+            });
+
+        let query = |in_amount: u128, kind| Query {
+            in_amount: NonZeroU256::try_from(in_amount).unwrap(),
+            kind,
+            ..Default::default()
+        };
+        let trades = Forward(inner)
+            .get_trades(&[
+                query(1, OrderKind::Sell),
+                query(2, OrderKind::Buy),
+                query(3, OrderKind::Sell),
+            ])
+            .await;
+
+        assert_eq!(trades.len(), 3);
+        assert_eq!(trades[0].as_ref().unwrap().out_amount, 1.into());
+        assert!(matches!(trades[1], Err(TradeError::NoLiquidity)));
+        assert_eq!(trades[2].as_ref().unwrap().out_amount, 3.into());
+    }
 
     #[test]
     fn trade_for_swap() {
@@ -242,6 +355,8 @@ mod tests {
                 },
             ],
             solver: H160([1; 20]),
+            protocols: Vec::new(),
+            estimated: false,
         };
 
         assert_eq!(
@@ -0,0 +1,126 @@
+//! A trade finder that caches quotes for a short time.
+
+use {
+    crate::{
+        price_estimation::Query,
+        trade_finding::{Quote, Trade, TradeError, TradeFinding},
+    },
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+};
+
+/// Caches the quotes of the wrapped trade finder for identical queries so that
+/// repeated quotes don't result in additional (rate limited) API requests.
+///
+/// Only successful quotes get cached. Trades always get fetched from the
+/// wrapped trade finder since they contain calldata.
+pub struct CachingTradeFinder {
+    inner: Arc<dyn TradeFinding>,
+    cache: Mutex<HashMap<Query, (Quote, Instant)>>,
+    max_age: Duration,
+    now: Box<dyn Fn() -> Instant + Send + Sync>,
+}
+
+impl CachingTradeFinder {
+    pub fn new(inner: Arc<dyn TradeFinding>, max_age: Duration) -> Self {
+        Self::with_clock(inner, max_age, Instant::now)
+    }
+
+    /// Like [`CachingTradeFinder::new`] but gets the current time from `now`
+    /// instead of the system clock.
+    pub fn with_clock(
+        inner: Arc<dyn TradeFinding>,
+        max_age: Duration,
+        now: impl Fn() -> Instant + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            inner,
+            cache: Default::default(),
+            max_age,
+            now: Box::new(now),
+        }
+    }
+
+    fn cached(&self, query: &Query) -> Option<Quote> {
+        let cache = self.cache.lock().unwrap();
+        let (quote, at) = cache.get(query)?;
+        ((self.now)().duration_since(*at) < self.max_age).then(|| quote.clone())
+    }
+
+    fn insert(&self, query: &Query, quote: &Quote) {
+        let now = (self.now)();
+        let mut cache = self.cache.lock().unwrap();
+        // Remove expired entries so that the cache doesn't grow unbounded.
+        cache.retain(|_, (_, at)| now.duration_since(*at) < self.max_age);
+        cache.insert(query.clone(), (quote.clone(), now));
+    }
+}
+
+#[async_trait::async_trait]
+impl TradeFinding for CachingTradeFinder {
+    async fn get_quote(&self, query: &Query) -> Result<Quote, TradeError> {
+        if let Some(quote) = self.cached(query) {
+            return Ok(quote);
+        }
+        let quote = self.inner.get_quote(query).await?;
+        self.insert(query, &quote);
+        Ok(quote)
+    }
+
+    async fn get_trade(&self, query: &Query) -> Result<Trade, TradeError> {
+        self.inner.get_trade(query).await
+    }
+
+    async fn health_check(&self) -> Result<(), TradeError> {
+        // A cached quote doesn't say anything about the current health.
+        self.inner.health_check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::trade_finding::MockTradeFinding};
+
+    #[tokio::test]
+    async fn caches_quotes_until_they_expire() {
+        const MAX_AGE: Duration = Duration::from_secs(60);
+
+        let mut inner = MockTradeFinding::new();
+        inner
+            .expect_get_quote()
+            .times(2)
+            .returning(|_| Ok(Quote::default()));
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock = now.clone();
+        let finder = CachingTradeFinder::with_clock(Arc::new(inner), MAX_AGE, move || {
+            *clock.lock().unwrap()
+        });
+        let query = Query::default();
+
+        // Quoting twice within `MAX_AGE` only requests a quote once.
+        finder.get_quote(&query).await.unwrap();
+        *now.lock().unwrap() += MAX_AGE - Duration::from_secs(1);
+        finder.get_quote(&query).await.unwrap();
+
+        // Once the quote expired it gets requested again.
+        *now.lock().unwrap() += Duration::from_secs(1);
+        finder.get_quote(&query).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn does_not_cache_errors() {
+        let mut inner = MockTradeFinding::new();
+        inner
+            .expect_get_quote()
+            .times(2)
+            .returning(|_| Err(TradeError::RateLimited { retry_after: None }));
+        let finder = CachingTradeFinder::new(Arc::new(inner), Duration::from_secs(60));
+        let query = Query::default();
+
+        assert!(finder.get_quote(&query).await.is_err());
+        assert!(finder.get_quote(&query).await.is_err());
+    }
+}
@@ -54,7 +54,7 @@ impl ExternalTradeFinder {
             .header(header::CONTENT_TYPE, "application/json")
             .header(header::ACCEPT, "application/json");
 
-        if let Some(id) = observe::request_id::get_task_local_storage() {
+        if let Some(id) = observe::request_id::get_task_local() {
             request = request.header("X-REQUEST-ID", id);
         }
 
@@ -118,6 +118,7 @@ impl From<dto::Quote> for Trade {
                 })
                 .collect(),
             solver: quote.solver,
+            protocols: Vec::new(),
         }
     }
 }
@@ -140,6 +141,7 @@ impl TradeFinding for ExternalTradeFinder {
         Ok(Quote {
             out_amount: trade.out_amount,
             gas_estimate: trade.gas_estimate,
+            gas_breakdown: None,
             solver: trade.solver,
         })
     }
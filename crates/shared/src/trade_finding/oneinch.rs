@@ -1,12 +1,13 @@
 //! A 1Inch-based trade finder.
 
 use {
-    super::{Interaction, Query, Quote, Trade, TradeError, TradeFinding},
+    super::{GasBreakdown, Interaction, Query, Quote, Trade, TradeError, TradeFinding},
     crate::{
         oneinch_api::{
             Cache,
             OneInchClient,
             OneInchError,
+            ReferrerFee,
             SellOrderQuoteQuery,
             Slippage,
             Swap,
@@ -15,15 +16,26 @@ use {
         price_estimation::gas,
         request_sharing::{BoxRequestSharing, BoxShared, RequestSharing},
     },
-    futures::FutureExt as _,
+    futures::{future, FutureExt as _},
+    itertools::Itertools as _,
     model::order::OrderKind,
-    primitive_types::H160,
-    std::sync::Arc,
+    primitive_types::{H160, U256},
+    rand::Rng as _,
+    std::{
+        collections::HashMap,
+        future::Future,
+        num::NonZeroUsize,
+        sync::{Arc, RwLock},
+        time::Duration,
+    },
+    tokio::sync::Semaphore,
 };
 
 pub struct OneInchTradeFinder {
     inner: Arc<Inner>,
     sharing: BoxRequestSharing<InternalQuery, Result<Quote, TradeError>>,
+    /// See [`OneInchTradeFinder::with_estimated_trades`].
+    estimated_trades: bool,
 }
 
 struct Inner {
@@ -31,8 +43,46 @@ struct Inner {
     disabled_protocols: Vec<String>,
     cache: Cache,
     referrer_address: Option<H160>,
-    solver: H160,
-    settlement_contract: H160,
+    referrer_fee: Option<ReferrerFee>,
+    /// Updatable at runtime, see [`OneInchTradeFinder::set_solver`].
+    solver: RwLock<H160>,
+    /// Updatable at runtime, see
+    /// [`OneInchTradeFinder::set_settlement_contract`].
+    settlement_contract: RwLock<H160>,
+    slippage: Slippage,
+    retries: RateLimitRetries,
+    /// Limits the number of concurrent requests to the 1Inch API.
+    requests: Semaphore,
+}
+
+/// Configures how requests that got rate limited by the 1Inch API get retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimitRetries {
+    /// The maximum number of attempts per request including the first one. A
+    /// value of 1 disables retries.
+    pub max_attempts: usize,
+    /// The delay before the first retry. It doubles with every further retry
+    /// and gets randomized by up to 50% so that concurrent requests don't
+    /// retry in lockstep.
+    pub base_delay: Duration,
+}
+
+impl RateLimitRetries {
+    fn delay(&self, retry: u32) -> Duration {
+        let jitter = rand::thread_rng().gen_range(0.5..1.5);
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .mul_f64(jitter)
+    }
+}
+
+impl Default for RateLimitRetries {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+        }
+    }
 }
 
 #[derive(Clone, Eq, PartialEq)]
@@ -42,25 +92,63 @@ struct InternalQuery {
 }
 
 impl OneInchTradeFinder {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         api: Arc<dyn OneInchClient>,
         disabled_protocols: Vec<String>,
         referrer_address: Option<H160>,
+        referrer_fee: Option<ReferrerFee>,
         solver: H160,
         settlement_contract: H160,
+        slippage: Slippage,
+        retries: RateLimitRetries,
+        max_concurrent_requests: NonZeroUsize,
     ) -> Self {
         Self {
             inner: Arc::new(Inner::new(
                 api,
                 disabled_protocols,
                 referrer_address,
+                referrer_fee,
                 solver,
                 settlement_contract,
+                slippage,
+                retries,
+                max_concurrent_requests,
             )),
             sharing: RequestSharing::labelled("oneinch".into()),
+            estimated_trades: false,
         }
     }
 
+    /// Makes [`TradeFinding::get_trade`] return trades synthesized from a
+    /// quote instead of requesting the swap from the 1Inch API. Their swap
+    /// interaction targets the 1Inch router but has no calldata, so they are
+    /// marked as [`Trade::estimated`]. Use [`OneInchTradeFinder::precise_trade`]
+    /// when executable calldata is needed.
+    pub fn with_estimated_trades(mut self, estimated_trades: bool) -> Self {
+        self.estimated_trades = estimated_trades;
+        self
+    }
+
+    /// Changes the solver that subsequent quotes and trades are attributed to.
+    pub fn set_solver(&self, solver: H160) {
+        *self.inner.solver.write().unwrap() = solver;
+    }
+
+    /// Changes the settlement contract that subsequent swaps get requested
+    /// for, e.g. during a contract migration. Requests that are already in
+    /// flight keep using the previous address.
+    pub fn set_settlement_contract(&self, settlement_contract: H160) {
+        *self.inner.settlement_contract.write().unwrap() = settlement_contract;
+    }
+
+    /// Returns a trade with the swap calldata from the 1Inch API regardless of
+    /// [`OneInchTradeFinder::with_estimated_trades`].
+    pub async fn precise_trade(&self, query: &Query) -> Result<Trade, TradeError> {
+        self.swap(query).await
+    }
+
     fn shared_quote(
         &self,
         query: &Query,
@@ -83,6 +171,40 @@ impl OneInchTradeFinder {
         self.shared_quote(query, allowed_protocols).await
     }
 
+    async fn trade(&self, query: &Query) -> Result<Trade, TradeError> {
+        if self.estimated_trades {
+            self.estimated_swap(query).await
+        } else {
+            self.swap(query).await
+        }
+    }
+
+    /// Builds a trade from a quote without requesting the swap calldata.
+    async fn estimated_swap(&self, query: &Query) -> Result<Trade, TradeError> {
+        let allowed_protocols = self.inner.verify_query_and_get_protocols(query).await?;
+        let (quote, spender) = futures::try_join!(
+            self.shared_quote(query, allowed_protocols),
+            self.inner.spender(),
+        )?;
+
+        Ok(Trade {
+            estimated: true,
+            ..Trade::swap(
+                query.sell_token,
+                quote.out_amount,
+                quote.gas_estimate,
+                Some(spender),
+                // The spender is the 1Inch router that executes the swap.
+                Interaction {
+                    target: spender,
+                    value: U256::zero(),
+                    data: Vec::new(),
+                },
+                self.inner.solver(),
+            )
+        })
+    }
+
     async fn swap(&self, query: &Query) -> Result<Trade, TradeError> {
         let allowed_protocols = self.inner.verify_query_and_get_protocols(query).await?;
         let (quote, spender, swap) = futures::try_join!(
@@ -91,36 +213,95 @@ impl OneInchTradeFinder {
             self.inner.swap(query, allowed_protocols),
         )?;
 
-        Ok(Trade::swap(
-            query.sell_token,
-            quote.out_amount,
-            quote.gas_estimate,
-            Some(spender),
-            Interaction {
-                target: swap.tx.to,
-                value: swap.tx.value,
-                data: swap.tx.data,
-            },
-            self.inner.solver,
-        ))
+        let protocols = route_protocols(&swap, &self.inner.disabled_protocols);
+        Ok(Trade {
+            protocols,
+            ..Trade::swap(
+                query.sell_token,
+                quote.out_amount,
+                quote.gas_estimate,
+                Some(spender),
+                Interaction {
+                    target: swap.tx.to,
+                    value: swap.tx.value,
+                    data: swap.tx.data,
+                },
+                self.inner.solver(),
+            )
+        })
+    }
+}
+
+/// Returns the names of the protocols a swap gets routed through in the order
+/// they first appear in the route. Disabled protocols never get reported.
+fn route_protocols(swap: &Swap, disabled_protocols: &[String]) -> Vec<String> {
+    let mut protocols = Vec::<String>::new();
+    for segment in swap.protocols.iter().flatten().flatten() {
+        if !disabled_protocols.contains(&segment.name) && !protocols.contains(&segment.name) {
+            protocols.push(segment.name.clone());
+        }
     }
+    protocols
 }
 
 impl Inner {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         api: Arc<dyn OneInchClient>,
         disabled_protocols: Vec<String>,
         referrer_address: Option<H160>,
+        referrer_fee: Option<ReferrerFee>,
         solver: H160,
         settlement_contract: H160,
+        slippage: Slippage,
+        retries: RateLimitRetries,
+        max_concurrent_requests: NonZeroUsize,
     ) -> Self {
         Self {
             api,
             disabled_protocols,
             referrer_address,
+            referrer_fee,
             cache: Default::default(),
-            solver,
-            settlement_contract,
+            solver: RwLock::new(solver),
+            settlement_contract: RwLock::new(settlement_contract),
+            slippage,
+            retries,
+            requests: Semaphore::new(max_concurrent_requests.get()),
+        }
+    }
+
+    /// Sends a request once fewer than the maximum number of concurrent
+    /// requests are in flight.
+    async fn limited<T>(&self, request: impl Future<Output = T>) -> T {
+        let _permit = self
+            .requests
+            .acquire()
+            .await
+            .expect("semaphore never gets closed");
+        request.await
+    }
+
+    /// Sends a request to the 1Inch API and retries it with an exponential
+    /// backoff while it gets rate limited. Other errors are returned
+    /// immediately.
+    async fn with_retries<T, F>(&self, mut request: impl FnMut() -> F) -> Result<T, TradeError>
+    where
+        F: Future<Output = Result<T, OneInchError>>,
+    {
+        let mut retry = 0;
+        loop {
+            match self.limited(request()).await.map_err(TradeError::from) {
+                Err(TradeError::RateLimited { retry_after })
+                    if (retry as usize) + 1 < self.retries.max_attempts =>
+                {
+                    let delay = retry_after.unwrap_or_else(|| self.retries.delay(retry));
+                    tracing::debug!(?delay, "rate limited by 1Inch API; retrying");
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
         }
     }
 
@@ -142,26 +323,43 @@ impl Inner {
 
     async fn perform_quote(&self, query: InternalQuery) -> Result<Quote, TradeError> {
         let quote = self
-            .api
-            .get_sell_order_quote(SellOrderQuoteQuery::with_default_options(
-                query.data.sell_token,
-                query.data.buy_token,
-                query.allowed_protocols,
-                query.data.in_amount.get(),
-                self.referrer_address,
-            ))
+            .with_retries(|| {
+                self.api.get_sell_order_quote(
+                    SellOrderQuoteQuery::with_default_options(
+                        query.data.sell_token,
+                        query.data.buy_token,
+                        query.allowed_protocols.clone(),
+                        query.data.in_amount.get(),
+                        self.referrer_address,
+                    )
+                    .with_referrer_fee(self.referrer_fee),
+                )
+            })
             .await?;
 
+        let gas_breakdown = GasBreakdown {
+            swap_gas: quote.estimated_gas,
+            overhead_gas: gas::SETTLEMENT_OVERHEAD,
+        };
         Ok(Quote {
             out_amount: quote.to_token_amount,
-            gas_estimate: gas::SETTLEMENT_OVERHEAD + quote.estimated_gas,
-            solver: self.solver,
+            gas_estimate: gas_breakdown.overhead_gas + gas_breakdown.swap_gas,
+            gas_breakdown: Some(gas_breakdown),
+            solver: self.solver(),
         })
     }
 
+    fn solver(&self) -> H160 {
+        *self.solver.read().unwrap()
+    }
+
+    fn settlement_contract(&self) -> H160 {
+        *self.settlement_contract.read().unwrap()
+    }
+
     /// Returns the current 1Inch smart contract as the `spender`.
     async fn spender(&self) -> Result<H160, TradeError> {
-        let spender = self.cache.spender(self.api.as_ref()).await?;
+        let spender = self.limited(self.cache.spender(self.api.as_ref())).await?;
         Ok(spender.address)
     }
 
@@ -170,25 +368,31 @@ impl Inner {
         query: &Query,
         allowed_protocols: Option<Vec<String>>,
     ) -> Result<Swap, TradeError> {
-        Ok(self
-            .api
-            .get_swap(SwapQuery::with_default_options(
-                query.sell_token,
-                query.buy_token,
-                query.in_amount.get(),
-                self.settlement_contract,
-                allowed_protocols,
-                Slippage::ONE_PERCENT,
-                self.referrer_address,
-            ))
-            .await?)
+        let settlement_contract = self.settlement_contract();
+        self.with_retries(|| {
+            self.api.get_swap(
+                SwapQuery::with_default_options(
+                    query.sell_token,
+                    query.buy_token,
+                    query.in_amount.get(),
+                    settlement_contract,
+                    allowed_protocols.clone(),
+                    self.slippage,
+                    self.referrer_address,
+                )
+                .with_referrer_fee(self.referrer_fee),
+            )
+        })
+        .await
     }
 }
 
 impl From<OneInchError> for TradeError {
     fn from(err: OneInchError) -> Self {
         match err {
-            OneInchError::Api(err) if err.status_code == 429 => Self::RateLimited,
+            OneInchError::Api(err) if err.status_code == 429 => Self::RateLimited {
+                retry_after: err.retry_after,
+            },
             err if err.is_insuffucient_liquidity() => Self::NoLiquidity,
             err => Self::Other(err.into()),
         }
@@ -202,7 +406,22 @@ impl TradeFinding for OneInchTradeFinder {
     }
 
     async fn get_trade(&self, query: &Query) -> Result<Trade, TradeError> {
-        self.swap(query).await
+        self.trade(query).await
+    }
+
+    async fn get_trades(&self, queries: &[Query]) -> Vec<Result<Trade, TradeError>> {
+        // Quotes for identical queries already get shared but swaps don't, so
+        // only request a swap once per distinct query.
+        let distinct = queries.iter().unique().collect::<Vec<_>>();
+        let trades = future::join_all(distinct.iter().map(|query| self.trade(query))).await;
+        let trades = distinct.into_iter().zip(trades).collect::<HashMap<_, _>>();
+        queries.iter().map(|query| trades[query].clone()).collect()
+    }
+
+    /// Requests the spender address which is much cheaper than a quote.
+    async fn health_check(&self) -> Result<(), TradeError> {
+        self.inner.api.get_spender().await?;
+        Ok(())
     }
 }
 
@@ -211,8 +430,10 @@ mod tests {
     use {
         super::*,
         crate::oneinch_api::{
+            ApiVersion,
             MockOneInchClient,
             OneInchClientImpl,
+            ProtocolRouteSegment,
             RestError,
             SellOrderQuote,
             Spender,
@@ -223,7 +444,10 @@ mod tests {
         hex_literal::hex,
         number::nonzero::U256 as NonZeroU256,
         reqwest::Client,
-        std::time::Duration,
+        std::{
+            sync::atomic::{AtomicUsize, Ordering},
+            time::{Duration, Instant},
+        },
     };
 
     fn create_trade_finder<T: OneInchClient>(api: T) -> OneInchTradeFinder {
@@ -231,8 +455,12 @@ mod tests {
             Arc::new(api),
             Vec::default(),
             None,
+            None,
             H160([1; 20]),
             H160([2; 20]),
+            Slippage::ONE_PERCENT,
+            RateLimitRetries::default(),
+            NonZeroUsize::new(10).unwrap(),
         )
     }
 
@@ -280,6 +508,12 @@ mod tests {
 
         assert_eq!(quote.out_amount, 808_069_760_400_778_577u128.into());
         assert!(quote.gas_estimate > 189_386);
+        let gas_breakdown = quote.gas_breakdown.unwrap();
+        assert_eq!(gas_breakdown.swap_gas, 189_386);
+        assert_eq!(
+            gas_breakdown.swap_gas + gas_breakdown.overhead_gas,
+            quote.gas_estimate
+        );
     }
 
     #[tokio::test]
@@ -337,7 +571,20 @@ mod tests {
                     },
                     to_token_amount: 808_069_760_400_778_577u128.into(),
                     from_token_amount: 100_000_000_000_000_000u128.into(),
-                    protocols: Default::default(),
+                    protocols: vec![vec![vec![
+                        ProtocolRouteSegment {
+                            name: "UNISWAP_V3".to_string(),
+                            part: 60.,
+                            from_token_address: testlib::tokens::WETH,
+                            to_token_address: testlib::tokens::GNO,
+                        },
+                        ProtocolRouteSegment {
+                            name: "SUSHI".to_string(),
+                            part: 40.,
+                            from_token_address: testlib::tokens::WETH,
+                            to_token_address: testlib::tokens::GNO,
+                        },
+                    ]]],
                     tx: Transaction {
                         from: Default::default(),
                         to: addr!("1111111254fb6c44bac0bed2854e76f90643097d"),
@@ -366,6 +613,7 @@ mod tests {
 
         assert_eq!(trade.out_amount, 808_069_760_400_778_577u128.into());
         assert!(trade.gas_estimate > 189_386);
+        assert_eq!(trade.protocols, ["UNISWAP_V3", "SUSHI"]);
         assert_eq!(
             trade.interactions,
             vec![
@@ -398,6 +646,378 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn requests_configured_referrer_fee() {
+        let fee = ReferrerFee::from_bps(50).unwrap();
+        let mut one_inch = MockOneInchClient::new();
+        one_inch
+            .expect_get_sell_order_quote()
+            .times(1)
+            .withf(|query| query.fee == Some(0.5))
+            .returning(|query| {
+                // 1Inch sends the fee to the referrer before swapping so
+                // there is less to swap.
+                let fee = query.amount * 50 / 10_000;
+                let to_token_amount = query.amount - fee;
+                async move {
+                    Ok(SellOrderQuote {
+                        to_token_amount,
+                        ..Default::default()
+                    })
+                }
+                .boxed()
+            });
+
+        let trader = OneInchTradeFinder::new(
+            Arc::new(one_inch),
+            Vec::new(),
+            Some(H160([3; 20])),
+            Some(fee),
+            H160([1; 20]),
+            H160([2; 20]),
+            Slippage::ONE_PERCENT,
+            RateLimitRetries::default(),
+            NonZeroUsize::new(10).unwrap(),
+        );
+
+        let quote = trader
+            .get_quote(&Query {
+                verification: None,
+                sell_token: testlib::tokens::WETH,
+                buy_token: testlib::tokens::GNO,
+                in_amount: NonZeroU256::try_from(1_000_000u128).unwrap(),
+                kind: OrderKind::Sell,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(quote.out_amount, 995_000.into());
+    }
+
+    #[test]
+    fn route_protocols_excludes_disabled_protocols() {
+        let segment = |name: &str| ProtocolRouteSegment {
+            name: name.to_string(),
+            part: 50.,
+            from_token_address: testlib::tokens::WETH,
+            to_token_address: testlib::tokens::GNO,
+        };
+        let swap = Swap {
+            protocols: vec![
+                vec![vec![segment("UNISWAP_V3"), segment("PMM1")]],
+                vec![vec![segment("UNISWAP_V3")]],
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            route_protocols(&swap, &["PMM1".to_string()]),
+            ["UNISWAP_V3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn uses_configured_slippage() {
+        let slippage = Slippage::percentage(0.1).unwrap();
+
+        let mut one_inch = MockOneInchClient::new();
+        one_inch
+            .expect_get_sell_order_quote()
+            .return_once(|_| async { Ok(Default::default()) }.boxed());
+        one_inch
+            .expect_get_spender()
+            .return_once(|| async { Ok(Default::default()) }.boxed());
+        one_inch
+            .expect_get_swap()
+            .withf(move |query| query.slippage == slippage)
+            .times(1)
+            .return_once(|_| async { Ok(Default::default()) }.boxed());
+
+        let trader = OneInchTradeFinder::new(
+            Arc::new(one_inch),
+            Vec::new(),
+            None,
+            None,
+            H160([1; 20]),
+            H160([2; 20]),
+            slippage,
+            RateLimitRetries::default(),
+            NonZeroUsize::new(10).unwrap(),
+        );
+
+        let trade = trader
+            .get_trade(&Query {
+                kind: OrderKind::Sell,
+                ..Default::default()
+            })
+            .await;
+
+        assert!(trade.is_ok());
+    }
+
+    #[tokio::test]
+    async fn swaps_use_updated_settlement_contract() {
+        let old = H160([2; 20]);
+        let new = H160([3; 20]);
+
+        let mut one_inch = MockOneInchClient::new();
+        one_inch
+            .expect_get_sell_order_quote()
+            .returning(|_| async { Ok(Default::default()) }.boxed());
+        one_inch
+            .expect_get_spender()
+            .returning(|| async { Ok(Default::default()) }.boxed());
+        let mut sequence = mockall::Sequence::new();
+        one_inch
+            .expect_get_swap()
+            .withf(move |query| query.from_address == old)
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_| async { Ok(Default::default()) }.boxed());
+        one_inch
+            .expect_get_swap()
+            .withf(move |query| query.from_address == new)
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_| async { Ok(Default::default()) }.boxed());
+
+        // Uses `H160([2; 20])` as the settlement contract.
+        let trader = create_trade_finder(one_inch);
+        let query = Query {
+            kind: OrderKind::Sell,
+            ..Default::default()
+        };
+
+        assert!(trader.get_trade(&query).await.is_ok());
+        trader.set_settlement_contract(new);
+        assert!(trader.get_trade(&query).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn get_trades_requests_swap_once_per_distinct_query() {
+        let mut one_inch = MockOneInchClient::new();
+        one_inch
+            .expect_get_sell_order_quote()
+            .times(1)
+            .returning(|_| async { Ok(Default::default()) }.boxed());
+        one_inch
+            .expect_get_spender()
+            .returning(|| async { Ok(Default::default()) }.boxed());
+        one_inch
+            .expect_get_swap()
+            .times(1)
+            .returning(|_| async { Ok(Default::default()) }.boxed());
+
+        let trader = create_trade_finder(one_inch);
+        let sell = Query {
+            kind: OrderKind::Sell,
+            ..Default::default()
+        };
+        let buy = Query {
+            kind: OrderKind::Buy,
+            ..Default::default()
+        };
+
+        let trades = trader.get_trades(&[sell.clone(), buy, sell]).await;
+
+        assert_eq!(trades.len(), 3);
+        assert!(trades[0].is_ok());
+        assert!(matches!(
+            trades[1],
+            Err(TradeError::UnsupportedOrderType(_))
+        ));
+        assert!(trades[2].is_ok());
+    }
+
+    #[tokio::test]
+    async fn estimated_trades_do_not_request_swaps() {
+        let router = addr!("1111111254eeb25477b68fb85ed929f73a960582");
+        let mut one_inch = MockOneInchClient::new();
+        one_inch.expect_get_sell_order_quote().returning(|_| {
+            async {
+                Ok(SellOrderQuote {
+                    to_token_amount: 1_000.into(),
+                    estimated_gas: 100_000,
+                    ..Default::default()
+                })
+            }
+            .boxed()
+        });
+        one_inch
+            .expect_get_spender()
+            .returning(move || async move { Ok(Spender { address: router }) }.boxed());
+        one_inch.expect_get_swap().never();
+
+        let trader = create_trade_finder(one_inch).with_estimated_trades(true);
+        let query = Query {
+            kind: OrderKind::Sell,
+            ..Default::default()
+        };
+        let quote = trader.get_quote(&query).await.unwrap();
+        let trade = trader.get_trade(&query).await.unwrap();
+
+        assert!(trade.estimated);
+        assert_eq!(trade.out_amount, 1_000.into());
+        assert_eq!(trade.gas_estimate, quote.gas_estimate);
+        assert_eq!(
+            trade.interactions.last().unwrap(),
+            &Interaction {
+                target: router,
+                value: 0.into(),
+                data: Vec::new(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_rate_limited_requests() {
+        let mut one_inch = MockOneInchClient::new();
+        let mut sequence = mockall::Sequence::new();
+        one_inch
+            .expect_get_sell_order_quote()
+            .times(2)
+            .in_sequence(&mut sequence)
+            .returning(|_| {
+                async {
+                    Err(OneInchError::Api(RestError {
+                        status_code: 429,
+                        description: "Too Many Requests".to_string(),
+                        retry_after: None,
+                    }))
+                }
+                .boxed()
+            });
+        one_inch
+            .expect_get_sell_order_quote()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_| {
+                async {
+                    Ok(SellOrderQuote {
+                        to_token_amount: 1_000.into(),
+                        ..Default::default()
+                    })
+                }
+                .boxed()
+            });
+
+        let trader = OneInchTradeFinder::new(
+            Arc::new(one_inch),
+            Vec::new(),
+            None,
+            None,
+            H160([1; 20]),
+            H160([2; 20]),
+            Slippage::ONE_PERCENT,
+            RateLimitRetries {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(1),
+            },
+            NonZeroUsize::new(10).unwrap(),
+        );
+
+        let quote = trader
+            .get_quote(&Query {
+                verification: None,
+                sell_token: testlib::tokens::WETH,
+                buy_token: testlib::tokens::GNO,
+                in_amount: NonZeroU256::try_from(1_000_000_000_000_000_000u128).unwrap(),
+                kind: OrderKind::Sell,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(quote.out_amount, 1_000.into());
+    }
+
+    #[tokio::test]
+    async fn rate_limits_carry_retry_after() {
+        let filter = warp::any().map(|| {
+            warp::reply::with_header(
+                warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "statusCode": 429,
+                        "description": "Too Many Requests",
+                    })),
+                    warp::http::StatusCode::TOO_MANY_REQUESTS,
+                ),
+                "Retry-After",
+                "7",
+            )
+        });
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let api = OneInchClientImpl::new(format!("http://{addr}/"), Client::new(), 1).unwrap();
+
+        let err = api.get_spender().await.unwrap_err();
+        assert!(matches!(
+            TradeError::from(err),
+            TradeError::RateLimited {
+                retry_after: Some(delay)
+            } if delay == Duration::from_secs(7)
+        ));
+
+        let err = OneInchError::Api(RestError {
+            status_code: 429,
+            description: "Too Many Requests".to_string(),
+            retry_after: None,
+        });
+        assert!(matches!(
+            TradeError::from(err),
+            TradeError::RateLimited { retry_after: None }
+        ));
+    }
+
+    #[tokio::test]
+    async fn limits_concurrent_requests() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let mut one_inch = MockOneInchClient::new();
+        one_inch.expect_get_sell_order_quote().returning({
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            move |_| {
+                let in_flight = in_flight.clone();
+                let max_in_flight = max_in_flight.clone();
+                async move {
+                    let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(current, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    Ok(Default::default())
+                }
+                .boxed()
+            }
+        });
+
+        let trader = OneInchTradeFinder::new(
+            Arc::new(one_inch),
+            Vec::new(),
+            None,
+            None,
+            H160([1; 20]),
+            H160([2; 20]),
+            Slippage::ONE_PERCENT,
+            RateLimitRetries::default(),
+            NonZeroUsize::new(2).unwrap(),
+        );
+
+        // Distinct queries so that the requests don't get shared.
+        let queries = (1..=10u128)
+            .map(|amount| Query {
+                kind: OrderKind::Sell,
+                in_amount: NonZeroU256::try_from(amount).unwrap(),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let quotes =
+            futures::future::join_all(queries.iter().map(|query| trader.get_quote(query))).await;
+
+        assert!(quotes.iter().all(Result::is_ok));
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
     #[tokio::test]
     async fn estimating_buy_order_fails() {
         let mut one_inch = MockOneInchClient::new();
@@ -430,6 +1050,7 @@ mod tests {
                     Err(OneInchError::Api(RestError {
                         status_code: 500,
                         description: "Internal Server Error".to_string(),
+                        retry_after: None,
                     }))
                 }
                 .boxed()
@@ -502,8 +1123,12 @@ mod tests {
             Arc::new(oneinch),
             Vec::new(),
             None,
+            None,
             H160([1; 20]),
             H160([1; 20]),
+            Slippage::ONE_PERCENT,
+            RateLimitRetries::default(),
+            NonZeroUsize::new(10).unwrap(),
         );
 
         let query = Query {
@@ -545,7 +1170,7 @@ mod tests {
 
     #[tokio::test]
     async fn spender_gets_cached() {
-        const MAX_AGE: Duration = Duration::from_millis(10);
+        const MAX_AGE: Duration = Duration::from_secs(60);
         let spender = |address: u64| Spender {
             address: H160::from_low_u64_be(address),
         };
@@ -558,14 +1183,20 @@ mod tests {
             one_inch
         };
 
+        let now = Arc::new(std::sync::Mutex::new(Instant::now()));
+        let clock = now.clone();
         let mut inner = Inner {
-            cache: Cache::new(MAX_AGE),
+            cache: Cache::with_clock(MAX_AGE, move || *clock.lock().unwrap()),
             ..Inner::new(
                 Arc::new(mock_api(1)),
                 vec![],
                 None,
+                None,
                 H160([1; 20]),
                 H160([1; 20]),
+                Slippage::ONE_PERCENT,
+                RateLimitRetries::default(),
+                NonZeroUsize::new(10).unwrap(),
             )
         };
 
@@ -583,8 +1214,74 @@ mod tests {
         // After `MAX_AGE` calling `Inner::spender()` again will result in
         // another call to `OneInchClient::spender()` because the cached value
         // expired.
-        tokio::time::sleep(MAX_AGE).await;
+        *now.lock().unwrap() += MAX_AGE;
         let result = inner.spender().await.unwrap();
         assert_eq!(result, spender(2).address);
     }
+
+    #[tokio::test]
+    async fn health_check_requests_spender() {
+        let mut one_inch = MockOneInchClient::new();
+        one_inch.expect_get_sell_order_quote().never();
+        one_inch
+            .expect_get_spender()
+            .times(1)
+            .return_once(|| async { Ok(Default::default()) }.boxed());
+        let trader = create_trade_finder(one_inch);
+        assert!(trader.health_check().await.is_ok());
+
+        let mut one_inch = MockOneInchClient::new();
+        one_inch.expect_get_spender().times(1).return_once(|| {
+            async {
+                Err(OneInchError::Api(RestError {
+                    status_code: 500,
+                    description: "Internal Server Error".to_string(),
+                    retry_after: None,
+                }))
+            }
+            .boxed()
+        });
+        let trader = create_trade_finder(one_inch);
+        assert!(matches!(
+            trader.health_check().await,
+            Err(TradeError::Other(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn targets_configured_api_version_and_chain() {
+        // Responds with a spender address identifying the requested path.
+        let filter = warp::path!(String / u64 / "approve" / "spender").map(
+            |version: String, chain_id: u64| {
+                let version = match version.as_str() {
+                    "v4.0" => 4_000,
+                    "v5.0" => 5_000,
+                    _ => 0,
+                };
+                let address = H160::from_low_u64_be(version + chain_id);
+                warp::reply::json(&serde_json::json!({ "address": address }))
+            },
+        );
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let base_url = format!("http://{addr}/");
+
+        let mainnet = create_trade_finder(
+            OneInchClientImpl::new(base_url.as_str(), Client::new(), 1)
+                .unwrap()
+                .with_version(ApiVersion::V4_0),
+        );
+        let gnosis = create_trade_finder(
+            OneInchClientImpl::new(base_url.as_str(), Client::new(), 100).unwrap(),
+        );
+
+        assert_eq!(
+            mainnet.inner.spender().await.unwrap(),
+            H160::from_low_u64_be(4_001)
+        );
+        assert_eq!(
+            gnosis.inner.spender().await.unwrap(),
+            H160::from_low_u64_be(5_100)
+        );
+    }
 }
@@ -107,6 +107,7 @@ impl Inner {
                 OrderKind::Sell => price.dest_amount,
             },
             gas_estimate: gas::SETTLEMENT_OVERHEAD + price.gas_cost,
+            gas_breakdown: None,
             solver: self.solver,
         };
 
@@ -174,7 +175,7 @@ impl From<ParaswapResponseError> for TradeError {
     fn from(err: ParaswapResponseError) -> Self {
         match err {
             ParaswapResponseError::InsufficientLiquidity(_) => Self::NoLiquidity,
-            ParaswapResponseError::RateLimited => Self::RateLimited,
+            ParaswapResponseError::RateLimited => Self::RateLimited { retry_after: None },
             ParaswapResponseError::Request(_)
             | ParaswapResponseError::Json(_)
             | ParaswapResponseError::Retryable(_)
@@ -196,7 +197,7 @@ mod tests {
         super::*,
         crate::{
             ethrpc::{create_env_test_transport, Web3},
-            paraswap_api::{DefaultParaswapApi, MockParaswapApi},
+            paraswap_api::{DefaultParaswapApi, MockParaswapApi, TransactionBuilderResponse},
             token_info::{MockTokenInfoFetching, TokenInfoFetcher},
         },
         maplit::hashmap,
@@ -242,6 +243,102 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    fn trade_finder(paraswap: MockParaswapApi) -> ParaswapTradeFinder {
+        let mut tokens = MockTokenInfoFetching::new();
+        tokens.expect_get_token_infos().returning(|_| {
+            hashmap! {
+                H160::default() => TokenInfo {
+                    decimals: Some(18),
+                    ..Default::default()
+                },
+            }
+        });
+        ParaswapTradeFinder::new(
+            Arc::new(paraswap),
+            Arc::new(tokens),
+            Vec::new(),
+            H160([1; 20]),
+        )
+    }
+
+    #[tokio::test]
+    async fn maps_api_errors() {
+        for (error, expected) in [
+            (ParaswapResponseError::RateLimited, "RateLimited"),
+            (
+                ParaswapResponseError::InsufficientLiquidity(
+                    "No routes found with enough liquidity".to_string(),
+                ),
+                "NoLiquidity",
+            ),
+            (
+                ParaswapResponseError::Retryable("Server too busy".to_string()),
+                "Other",
+            ),
+        ] {
+            let mut paraswap = MockParaswapApi::new();
+            paraswap
+                .expect_price()
+                .return_once(|_| async move { Err(error) }.boxed());
+
+            let result = trade_finder(paraswap).get_quote(&Query::default()).await;
+            let variant = match result {
+                Err(TradeError::RateLimited { .. }) => "RateLimited",
+                Err(TradeError::NoLiquidity) => "NoLiquidity",
+                Err(TradeError::Other(_)) => "Other",
+                other => panic!("unexpected result {other:?}"),
+            };
+            assert_eq!(variant, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn builds_approval_and_swap_interactions() {
+        let proxy = H160([2; 20]);
+        let router = H160([3; 20]);
+
+        let mut paraswap = MockParaswapApi::new();
+        paraswap.expect_price().return_once(move |_| {
+            async move {
+                Ok(PriceResponse {
+                    dest_amount: 1337.into(),
+                    token_transfer_proxy: proxy,
+                    gas_cost: 100_000,
+                    ..Default::default()
+                })
+            }
+            .boxed()
+        });
+        paraswap.expect_transaction().return_once(move |_| {
+            async move {
+                Ok(TransactionBuilderResponse {
+                    to: router,
+                    data: vec![1, 2, 3],
+                    ..Default::default()
+                })
+            }
+            .boxed()
+        });
+
+        let trade = trade_finder(paraswap)
+            .get_trade(&Query::default())
+            .await
+            .unwrap();
+
+        assert_eq!(trade.out_amount, 1337.into());
+        assert_eq!(trade.gas_estimate, gas::SETTLEMENT_OVERHEAD + 100_000);
+        assert_eq!(trade.interactions.len(), 3);
+        assert_eq!(trade.interactions[0].target, H160::default());
+        assert_eq!(
+            trade.interactions[2],
+            Interaction {
+                target: router,
+                value: 0.into(),
+                data: vec![1, 2, 3],
+            }
+        );
+    }
+
     #[tokio::test]
     #[ignore]
     async fn real_trade() {
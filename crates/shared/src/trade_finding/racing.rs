@@ -0,0 +1,213 @@
+//! A trade finder that races multiple trade finders and picks the best quote.
+
+use {
+    crate::{
+        price_estimation::Query,
+        trade_finding::{Quote, Trade, TradeError, TradeFinding},
+    },
+    anyhow::anyhow,
+    futures::future,
+    itertools::Itertools as _,
+    model::order::OrderKind,
+    std::sync::Arc,
+};
+
+/// Queries all trade finders in parallel and uses the one with the best quote,
+/// i.e. the highest buy amount for sell orders and the lowest sell amount for
+/// buy orders.
+///
+/// Errors of individual trade finders get ignored as long as at least one
+/// trade finder returns a quote.
+pub struct RacingTradeFinder {
+    inner: Vec<Arc<dyn TradeFinding>>,
+}
+
+impl RacingTradeFinder {
+    pub fn new(inner: Vec<Arc<dyn TradeFinding>>) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the index of the trade finder with the best quote.
+    async fn best_quote(&self, query: &Query) -> Result<(usize, Quote), TradeError> {
+        let results = future::join_all(self.inner.iter().map(|inner| inner.get_quote(query))).await;
+        let mut errors = Vec::new();
+        let quotes = results
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, result)| match result {
+                Ok(quote) => Some((i, quote)),
+                Err(err) => {
+                    tracing::debug!(finder = i, ?err, "trade finder failed to quote");
+                    errors.push(err);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let best = match query.kind {
+            OrderKind::Sell => quotes.into_iter().max_by_key(|(_, quote)| quote.out_amount),
+            OrderKind::Buy => quotes.into_iter().min_by_key(|(_, quote)| quote.out_amount),
+        };
+        best.ok_or_else(|| combine_errors(errors))
+    }
+}
+
+#[async_trait::async_trait]
+impl TradeFinding for RacingTradeFinder {
+    async fn get_quote(&self, query: &Query) -> Result<Quote, TradeError> {
+        let (_, quote) = self.best_quote(query).await?;
+        Ok(quote)
+    }
+
+    async fn get_trade(&self, query: &Query) -> Result<Trade, TradeError> {
+        let (winner, _) = self.best_quote(query).await?;
+        self.inner[winner].get_trade(query).await
+    }
+
+    /// Healthy as long as at least one of the trade finders is healthy.
+    async fn health_check(&self) -> Result<(), TradeError> {
+        let results = future::join_all(self.inner.iter().map(|inner| inner.health_check())).await;
+        if results.iter().any(Result::is_ok) {
+            return Ok(());
+        }
+        Err(combine_errors(
+            results.into_iter().filter_map(Result::err).collect(),
+        ))
+    }
+}
+
+/// Combines the errors of all trade finders into a single one. Errors that
+/// all trade finders agree on are kept as is.
+fn combine_errors(errors: Vec<TradeError>) -> TradeError {
+    if errors
+        .iter()
+        .all(|err| matches!(err, TradeError::NoLiquidity))
+    {
+        return TradeError::NoLiquidity;
+    }
+    if errors
+        .iter()
+        .all(|err| matches!(err, TradeError::RateLimited { .. }))
+    {
+        // Wait for the longest requested delay so that all finders are
+        // available again.
+        let retry_after = errors
+            .iter()
+            .filter_map(|err| match err {
+                TradeError::RateLimited { retry_after } => *retry_after,
+                _ => None,
+            })
+            .max();
+        return TradeError::RateLimited { retry_after };
+    }
+    TradeError::Other(anyhow!(
+        "all trade finders failed: {}",
+        errors.iter().format("; ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::trade_finding::MockTradeFinding,
+        ethcontract::{H160, U256},
+        number::nonzero::U256 as NonZeroU256,
+    };
+
+    fn query(kind: OrderKind) -> Query {
+        Query {
+            verification: None,
+            sell_token: H160::from_low_u64_be(1),
+            buy_token: H160::from_low_u64_be(2),
+            in_amount: NonZeroU256::try_from(10).unwrap(),
+            kind,
+        }
+    }
+
+    fn quoting(result: Result<u64, TradeError>) -> MockTradeFinding {
+        let mut finder = MockTradeFinding::new();
+        finder.expect_get_quote().returning(move |_| {
+            result.clone().map(|out_amount| Quote {
+                out_amount: out_amount.into(),
+                ..Default::default()
+            })
+        });
+        finder
+    }
+
+    #[tokio::test]
+    async fn picks_best_quote() {
+        let racing = RacingTradeFinder::new(vec![
+            Arc::new(quoting(Ok(1))),
+            Arc::new(quoting(Err(TradeError::RateLimited { retry_after: None }))),
+            Arc::new(quoting(Ok(3))),
+            Arc::new(quoting(Ok(2))),
+        ]);
+
+        let quote = racing.get_quote(&query(OrderKind::Sell)).await.unwrap();
+        assert_eq!(quote.out_amount, U256::from(3));
+
+        let quote = racing.get_quote(&query(OrderKind::Buy)).await.unwrap();
+        assert_eq!(quote.out_amount, U256::from(1));
+    }
+
+    #[tokio::test]
+    async fn gets_trade_from_winner() {
+        let mut loser = quoting(Ok(1));
+        loser.expect_get_trade().never();
+        let mut winner = quoting(Ok(2));
+        winner.expect_get_trade().returning(|_| {
+            Ok(Trade {
+                out_amount: 2.into(),
+                ..Default::default()
+            })
+        });
+
+        let racing = RacingTradeFinder::new(vec![Arc::new(loser), Arc::new(winner)]);
+
+        let trade = racing.get_trade(&query(OrderKind::Sell)).await.unwrap();
+        assert_eq!(trade.out_amount, U256::from(2));
+    }
+
+    #[tokio::test]
+    async fn combines_errors_if_all_fail() {
+        let racing = RacingTradeFinder::new(vec![
+            Arc::new(quoting(Err(TradeError::NoLiquidity))),
+            Arc::new(quoting(Err(TradeError::NoLiquidity))),
+        ]);
+        let result = racing.get_quote(&query(OrderKind::Sell)).await;
+        assert!(matches!(result, Err(TradeError::NoLiquidity)));
+
+        let racing = RacingTradeFinder::new(vec![
+            Arc::new(quoting(Err(TradeError::NoLiquidity))),
+            Arc::new(quoting(Err(TradeError::RateLimited { retry_after: None }))),
+        ]);
+        let result = racing.get_quote(&query(OrderKind::Sell)).await;
+        assert!(matches!(result, Err(TradeError::Other(_))));
+    }
+
+    #[tokio::test]
+    async fn healthy_if_any_finder_is_healthy() {
+        let checking = |healthy: bool| {
+            let mut finder = MockTradeFinding::new();
+            finder.expect_health_check().returning(move || {
+                if healthy {
+                    Ok(())
+                } else {
+                    Err(TradeError::RateLimited { retry_after: None })
+                }
+            });
+            Arc::new(finder) as Arc<dyn TradeFinding>
+        };
+
+        let racing = RacingTradeFinder::new(vec![checking(false), checking(true)]);
+        assert!(racing.health_check().await.is_ok());
+
+        let racing = RacingTradeFinder::new(vec![checking(false), checking(false)]);
+        assert!(matches!(
+            racing.health_check().await,
+            Err(TradeError::RateLimited { retry_after: None })
+        ));
+    }
+}
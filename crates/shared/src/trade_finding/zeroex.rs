@@ -104,6 +104,7 @@ impl TradeFinding for ZeroExTradeFinder {
         Ok(Quote {
             out_amount: trade.out_amount,
             gas_estimate: trade.gas_estimate,
+            gas_breakdown: None,
             solver: self.inner.solver,
         })
     }
@@ -117,7 +118,7 @@ impl From<ZeroExResponseError> for TradeError {
     fn from(err: ZeroExResponseError) -> Self {
         match err {
             ZeroExResponseError::InsufficientLiquidity => TradeError::NoLiquidity,
-            ZeroExResponseError::RateLimited => TradeError::RateLimited,
+            ZeroExResponseError::RateLimited => TradeError::RateLimited { retry_after: None },
             ZeroExResponseError::ServerError(_)
             | ZeroExResponseError::UnknownZeroExError(_)
             | ZeroExResponseError::DeserializeError(_, _)
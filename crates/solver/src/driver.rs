@@ -352,6 +352,7 @@ impl Driver {
 
         // Report solver competition data to the api.
         let solver_competition = SolverCompetitionDB {
+            version: SolverCompetitionDB::VERSION,
             gas_price: gas_price.effective_gas_price(),
             auction_start_block,
             liquidity_collected_block: current_block_during_liquidity_fetch,
@@ -312,7 +312,7 @@ mod tests {
             ),
         ];
 
-        observe::tracing::initialize_reentrant("INFO");
+        observe::tracing::initialize_reentrant(&observe::tracing::Config::new("INFO"));
         DriverLogger::print_settlements(&a);
     }
 }
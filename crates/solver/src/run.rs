@@ -71,8 +71,9 @@ use {
 pub async fn start(args: impl Iterator<Item = String>) {
     let args = Arguments::parse_from(args);
     observe::tracing::initialize(
-        args.shared.logging.log_filter.as_str(),
-        args.shared.logging.log_stderr_threshold,
+        &observe::tracing::Config::new(args.shared.logging.log_filter.as_str())
+            .with_stderr_threshold(args.shared.logging.log_stderr_threshold)
+            .with_format(args.shared.logging.log_format),
     );
     observe::panic_hook::install();
     tracing::info!("running solver with validated arguments:\n{}", args);
@@ -287,10 +287,9 @@ mod tests {
     #[ignore]
     async fn mainnet() {
         // Create some bogus settlements to see that the simulation returns an error.
-        observe::tracing::initialize(
+        observe::tracing::initialize(&observe::tracing::Config::new(
             "info,solver=debug,shared=debug,shared::transport=trace",
-            tracing::Level::ERROR.into(),
-        );
+        ));
         let transport = create_env_test_transport();
         let web3 = Web3::new(transport);
         let block = web3.eth().block_number().await.unwrap().as_u64();
@@ -716,10 +715,9 @@ mod tests {
     #[tokio::test]
     #[ignore]
     async fn mainnet_chunked() {
-        observe::tracing::initialize(
+        observe::tracing::initialize(&observe::tracing::Config::new(
             "info,solver=debug,shared=debug,shared::transport=trace",
-            tracing::Level::ERROR.into(),
-        );
+        ));
         let transport = create_env_test_transport();
         let web3 = Web3::new(transport);
         let contract = GPv2Settlement::deployed(&web3).await.unwrap();
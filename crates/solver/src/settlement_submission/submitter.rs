@@ -725,7 +725,10 @@ mod tests {
     #[tokio::test]
     #[ignore]
     async fn flashbots_mainnet_settlement() {
-        observe::tracing::initialize("solver=debug,shared=debug", LevelFilter::OFF);
+        observe::tracing::initialize(
+            &observe::tracing::Config::new("solver=debug,shared=debug")
+                .with_stderr_threshold(LevelFilter::OFF),
+        );
 
         let web3 = Web3::new(create_env_test_transport());
         let chain_id = web3.eth().chain_id().await.unwrap().as_u64();
@@ -1,7 +1,7 @@
 //! Serve a solver engine API.
 
 use {
-    crate::domain::solver::Solver,
+    crate::domain::solver,
     std::{future::Future, net::SocketAddr, sync::Arc},
     tokio::sync::oneshot,
     tracing::Instrument,
@@ -11,7 +11,7 @@ pub mod dto;
 
 pub struct Api {
     pub addr: SocketAddr,
-    pub solver: Solver,
+    pub solver: solver::Instrumented,
 }
 
 impl Api {
@@ -39,7 +39,7 @@ impl Api {
 }
 
 async fn solve(
-    state: axum::extract::State<Arc<Solver>>,
+    state: axum::extract::State<Arc<solver::Instrumented>>,
     auction: axum::extract::Json<dto::Auction>,
 ) -> (
     axum::http::StatusCode,
@@ -5,7 +5,7 @@ use {
 };
 
 /// The auction that the solvers need to find solutions to.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Auction {
     /// [`None`] if the auction applies to a quote.
     pub id: Option<Id>,
@@ -16,8 +16,23 @@ pub struct Auction {
     pub deadline: Deadline,
 }
 
+#[cfg(test)]
+impl Auction {
+    /// An auction without orders or liquidity with 30 seconds left to solve.
+    pub fn empty() -> Self {
+        Self {
+            id: None,
+            tokens: Tokens(HashMap::new()),
+            orders: Vec::new(),
+            liquidity: Vec::new(),
+            gas_price: GasPrice(eth::Ether(U256::zero())),
+            deadline: Deadline(chrono::Utc::now() + chrono::Duration::seconds(30)),
+        }
+    }
+}
+
 /// Information about tokens used in the auction.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Tokens(pub HashMap<eth::TokenAddress, Token>);
 
 impl Tokens {
@@ -38,7 +53,7 @@ impl Tokens {
 #[derive(Clone, Debug)]
 pub struct Id(pub i64);
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Token {
     pub decimals: Option<u8>,
     pub symbol: Option<String>,
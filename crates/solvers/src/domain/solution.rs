@@ -4,7 +4,7 @@ use {
         util,
     },
     ethereum_types::{Address, U256},
-    std::collections::HashMap,
+    std::collections::{HashMap, HashSet},
 };
 
 /// A solution to an auction.
@@ -20,6 +20,83 @@ impl Solution {
     pub fn with_score(self, score: Score) -> Self {
         Self { score, ..self }
     }
+
+    /// Returns all tokens that get traded or are used by interactions of this
+    /// solution.
+    fn tokens(&self) -> HashSet<eth::TokenAddress> {
+        let prices = self.prices.0.keys().copied();
+        let trades = self.trades.iter().flat_map(|trade| {
+            let (sell, buy) = match trade {
+                Trade::Fulfillment(fulfillment) => {
+                    (fulfillment.order.sell.token, fulfillment.order.buy.token)
+                }
+                Trade::Jit(jit) => (jit.order.sell.token, jit.order.buy.token),
+            };
+            [sell, buy]
+        });
+        let interactions = self
+            .interactions
+            .iter()
+            .flat_map(|interaction| match interaction {
+                Interaction::Liquidity(liquidity) => {
+                    vec![liquidity.input.token, liquidity.output.token]
+                }
+                Interaction::Custom(custom) => custom
+                    .inputs
+                    .iter()
+                    .chain(&custom.outputs)
+                    .map(|asset| asset.token)
+                    .collect(),
+            });
+        prices.chain(trades).chain(interactions).collect()
+    }
+
+    /// Returns the UIDs of all user orders traded by this solution.
+    fn orders(&self) -> HashSet<order::Uid> {
+        self.trades
+            .iter()
+            .filter_map(|trade| match trade {
+                Trade::Fulfillment(fulfillment) => Some(fulfillment.order.uid),
+                Trade::Jit(_) => None,
+            })
+            .collect()
+    }
+
+    /// Returns `true` if the solutions neither trade the same orders nor touch
+    /// the same tokens and can therefore be settled together.
+    fn is_disjoint(&self, other: &Self) -> bool {
+        self.orders().is_disjoint(&other.orders()) && self.tokens().is_disjoint(&other.tokens())
+    }
+}
+
+/// Merges solutions that don't overlap into bigger solutions which are more
+/// gas efficient to settle than the individual solutions.
+///
+/// Solutions overlap if they trade the same orders or touch the same tokens.
+/// The solutions are processed in order and each solution gets merged into
+/// the first merged solution it doesn't overlap with, which makes the result
+/// deterministic. For example, if `a` and `c` don't overlap but both overlap
+/// with `b`, merging `[a, b, c]` results in `[a + c, b]`.
+pub fn merge(solutions: Vec<Solution>) -> Vec<Solution> {
+    let mut merged = Vec::<Solution>::new();
+    for solution in solutions {
+        let target = merged.iter_mut().find_map(|existing| {
+            let score = existing.score.merge(&solution.score)?;
+            existing.is_disjoint(&solution).then_some((existing, score))
+        });
+        match target {
+            Some((existing, score)) => {
+                // The solutions don't share any tokens so their clearing prices
+                // are independent of each other and can simply be combined.
+                existing.prices.0.extend(solution.prices.0);
+                existing.trades.extend(solution.trades);
+                existing.interactions.extend(solution.interactions);
+                existing.score = score;
+            }
+            None => merged.push(solution),
+        }
+    }
+    merged
 }
 
 /// A solution for a settling a single order.
@@ -309,8 +386,72 @@ pub enum Score {
     RiskAdjusted(SuccessProbability),
 }
 
+impl Score {
+    /// Returns the score of two solutions that get settled together or `None`
+    /// if the scores are of a different kind and can't be combined.
+    fn merge(&self, other: &Self) -> Option<Self> {
+        match (self, other) {
+            (Self::Solver(a), Self::Solver(b)) => Some(Self::Solver(a.checked_add(*b)?)),
+            // The merged solution only succeeds if all its parts would have.
+            (Self::RiskAdjusted(a), Self::RiskAdjusted(b)) => Some(Self::RiskAdjusted(a * b)),
+            _ => None,
+        }
+    }
+}
+
 impl Default for Score {
     fn default() -> Self {
         Self::RiskAdjusted(1.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, ethereum_types::H160};
+
+    fn solution(tokens: &[u64]) -> Solution {
+        Solution {
+            prices: ClearingPrices::new(tokens.iter().map(|token| {
+                (
+                    eth::TokenAddress(H160::from_low_u64_be(*token)),
+                    U256::one(),
+                )
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn tokens(solution: &Solution) -> Vec<u64> {
+        let mut tokens = solution
+            .tokens()
+            .into_iter()
+            .map(|token| token.0.to_low_u64_be())
+            .collect::<Vec<_>>();
+        tokens.sort();
+        tokens
+    }
+
+    #[test]
+    fn merges_disjoint_solutions() {
+        let a = solution(&[1, 2]);
+        let b = solution(&[2, 3]);
+        let c = solution(&[3, 4]);
+
+        let merged = merge(vec![a, b, c]);
+
+        assert_eq!(
+            merged.iter().map(tokens).collect::<Vec<_>>(),
+            [vec![1, 2, 3, 4], vec![2, 3]],
+        );
+    }
+
+    #[test]
+    fn does_not_merge_different_score_kinds() {
+        let a = solution(&[1, 2]).with_score(Score::Solver(1.into()));
+        let b = solution(&[3, 4]);
+
+        let merged = merge(vec![a, b]);
+
+        assert_eq!(merged.len(), 2);
+    }
+}
@@ -7,7 +7,9 @@ use {
         infra,
     },
     futures::{future, stream, StreamExt},
-    std::num::NonZeroUsize,
+    std::{num::NonZeroUsize, time::Instant},
+    tokio::sync::{Semaphore, SemaphorePermit},
+    tokio_util::sync::CancellationToken,
     tracing::Instrument,
 };
 
@@ -23,6 +25,10 @@ pub struct Dex {
     /// The number of concurrent requests to make.
     concurrent_requests: NonZeroUsize,
 
+    /// Limits the number of in-flight requests to the DEX API across all
+    /// auctions that are being solved, see [`InFlight`].
+    requests: Semaphore,
+
     /// Helps to manage the strategy to fill orders (especially partially
     /// fillable orders).
     fills: Fills,
@@ -34,11 +40,37 @@ impl Dex {
             dex,
             slippage: config.slippage,
             concurrent_requests: config.concurrent_requests,
+            requests: Semaphore::new(config.concurrent_requests.get()),
             fills: Fills::new(config.smallest_partial_fill),
         }
     }
 
     pub async fn solve(&self, auction: auction::Auction) -> Vec<solution::Solution> {
+        self.solve_with_deadline(auction, super::far_future()).await
+    }
+
+    /// Solves the auction until either the auction deadline or the specified
+    /// `deadline` is reached, whichever comes first. In-flight requests to the
+    /// DEX API get cancelled when the deadline is reached and the solutions
+    /// found so far are returned.
+    pub async fn solve_with_deadline(
+        &self,
+        auction: auction::Auction,
+        deadline: Instant,
+    ) -> Vec<solution::Solution> {
+        self.solve_cancellable(auction, deadline, CancellationToken::new())
+            .await
+    }
+
+    /// Like [`Dex::solve_with_deadline`] but also stops solving once `token`
+    /// gets cancelled. In-flight requests get aborted the same way as when
+    /// the deadline is reached.
+    pub async fn solve_cancellable(
+        &self,
+        auction: auction::Auction,
+        deadline: Instant,
+        token: CancellationToken,
+    ) -> Vec<solution::Solution> {
         let mut solutions = Vec::new();
         let solve_orders = async {
             let mut stream = self.solution_stream(&auction);
@@ -47,9 +79,16 @@ impl Dex {
             }
         };
 
-        let deadline = auction.deadline.remaining().unwrap_or_default();
-        if tokio::time::timeout(deadline, solve_orders).await.is_err() {
-            tracing::debug!("reached deadline; stopping to solve");
+        let remaining = auction.deadline.remaining().unwrap_or_default();
+        let deadline = deadline.min(Instant::now() + remaining);
+        tokio::select! {
+            _ = solve_orders => {}
+            _ = tokio::time::sleep_until(deadline.into()) => {
+                tracing::debug!("reached deadline; stopping to solve");
+            }
+            _ = token.cancelled() => {
+                tracing::debug!("cancelled; stopping to solve");
+            }
         }
 
         self.fills.collect_garbage();
@@ -71,6 +110,9 @@ impl Dex {
             .filter_map(future::ready)
     }
 
+    /// Solves a single order with a swap from the DEX API. The gas price of
+    /// the auction is forwarded to the API so that swaps get priced at the gas
+    /// price the auction gets settled with rather than the API's own estimate.
     async fn solve_order(
         &self,
         order: order::UserOrder<'_>,
@@ -81,7 +123,10 @@ impl Dex {
         let swap = {
             let order = self.fills.dex_order(order, tokens)?;
             let slippage = self.slippage.relative(&order.amount(), tokens);
-            self.dex.swap(&order, &slippage, tokens, gas_price).await
+            let request = InFlight::new(&self.requests).await;
+            let swap = self.dex.swap(&order, &slippage, tokens, gas_price).await;
+            request.complete();
+            swap
         };
 
         let swap = match swap {
@@ -120,3 +165,101 @@ impl Dex {
         Some(solution)
     }
 }
+
+/// A request to the DEX API holding one of the solver's request permits.
+///
+/// The request future and this guard live in the same `solve` future. When
+/// that gets dropped mid-flight (e.g. because the auction got superseded), the
+/// pending request is aborted together with it and dropping the guard
+/// releases the permit, so a request can never complete after the solver
+/// stopped waiting for it.
+struct InFlight<'a> {
+    _permit: SemaphorePermit<'a>,
+    completed: bool,
+}
+
+impl<'a> InFlight<'a> {
+    async fn new(requests: &'a Semaphore) -> Self {
+        Self {
+            _permit: requests.acquire().await.expect("semaphore closed"),
+            completed: false,
+        }
+    }
+
+    /// Marks the request as completed and releases its permit.
+    fn complete(mut self) {
+        self.completed = true;
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            tracing::debug!("aborted in-flight DEX request");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::domain::eth, std::time::Duration};
+
+    fn order(id: u8) -> order::Order {
+        order::Order {
+            uid: order::Uid([id; 56]),
+            sell: eth::Asset {
+                token: eth::TokenAddress(eth::H160([1; 20])),
+                amount: 1_000.into(),
+            },
+            buy: eth::Asset {
+                token: eth::TokenAddress(eth::H160([2; 20])),
+                amount: 1_000.into(),
+            },
+            fee: order::Fee(0.into()),
+            side: order::Side::Sell,
+            class: order::Class::Market,
+            partially_fillable: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn dropping_solve_releases_request_permits() {
+        // Accepts connections but never responds, so requests stay in flight.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+
+        let dex = Dex::new(
+            infra::dex::Dex::Balancer(infra::dex::balancer::Sor::new(
+                infra::dex::balancer::Config {
+                    endpoint,
+                    vault: eth::ContractAddress(Default::default()),
+                    settlement: eth::ContractAddress(Default::default()),
+                },
+            )),
+            infra::config::dex::Config {
+                slippage: slippage::Limits::new(Default::default(), None).unwrap(),
+                concurrent_requests: NonZeroUsize::new(2).unwrap(),
+                smallest_partial_fill: eth::Ether::default(),
+            },
+        );
+        let auction = auction::Auction {
+            orders: vec![order(1), order(2), order(3)],
+            ..auction::Auction::empty()
+        };
+
+        let all_in_flight = async {
+            while dex.requests.available_permits() > 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        };
+        tokio::select! {
+            _ = dex.solve(auction) => panic!("solved without any response"),
+            _ = all_in_flight => {}
+        }
+
+        // The `solve` future got dropped while its requests were in flight.
+        assert_eq!(dex.requests.available_permits(), 2);
+    }
+}
@@ -10,6 +10,7 @@ use {
         domain::{auction, eth, solution},
     },
     reqwest::Url,
+    std::time::{Duration, Instant},
 };
 
 pub struct Config {
@@ -17,17 +18,45 @@ pub struct Config {
     pub solver_name: String,
     pub chain_id: eth::ChainId,
     pub endpoint: Url,
+    /// How long to wait for the legacy solver to respond. Waits until it
+    /// responds if `None`.
+    pub timeout: Option<Duration>,
 }
 
-pub struct Legacy(boundary::legacy::Legacy);
+pub struct Legacy {
+    inner: boundary::legacy::Legacy,
+    timeout: Option<Duration>,
+}
 
 impl Legacy {
     pub fn new(config: Config) -> Self {
-        Self(boundary::legacy::Legacy::new(config))
+        let timeout = config.timeout;
+        Self {
+            inner: boundary::legacy::Legacy::new(config),
+            timeout,
+        }
     }
 
     pub async fn solve(&self, auction: auction::Auction) -> Vec<solution::Solution> {
-        match self.0.solve(auction).await {
+        let id = auction.id.clone();
+        let start = Instant::now();
+        let solve = self.inner.solve(auction);
+        let result = match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, solve).await {
+                Ok(result) => result,
+                Err(_) => {
+                    tracing::warn!(
+                        auction_id = ?id,
+                        elapsed = ?start.elapsed(),
+                        "legacy solver timed out"
+                    );
+                    return vec![];
+                }
+            },
+            None => solve.await,
+        };
+
+        match result {
             Ok(solution) => vec![solution],
             Err(err) => {
                 tracing::warn!(?err, "failed to solve auction");
@@ -36,3 +65,73 @@ impl Legacy {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        ethereum_types::{H160, U256},
+        std::net::SocketAddr,
+    };
+
+    /// Serves `handler` as the legacy solver's `/solve` endpoint until the
+    /// returned task gets aborted.
+    fn serve<H, T>(handler: H) -> (SocketAddr, tokio::task::JoinHandle<()>)
+    where
+        H: axum::handler::Handler<T, ()>,
+        T: 'static,
+    {
+        let app = axum::Router::new().route("/solve", axum::routing::post(handler));
+        let server =
+            axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+        let address = server.local_addr();
+        let handle = tokio::spawn(async move {
+            server.await.unwrap();
+        });
+        (address, handle)
+    }
+
+    fn solver(address: SocketAddr, timeout: Option<Duration>) -> Legacy {
+        Legacy::new(Config {
+            weth: eth::WethAddress(H160::zero()),
+            solver_name: "legacy".to_owned(),
+            chain_id: eth::ChainId::Mainnet,
+            endpoint: format!("http://{address}/solve").parse().unwrap(),
+            timeout,
+        })
+    }
+
+    #[tokio::test]
+    async fn times_out_slow_solver() {
+        let (address, handle) = serve(|| async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            "{}"
+        });
+        let solver = solver(address, Some(Duration::from_millis(100)));
+
+        let start = Instant::now();
+        let solutions = solver.solve(auction::Auction::empty()).await;
+
+        assert!(solutions.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(10));
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn passes_through_solver_scores() {
+        let solve = |response: &'static str| async move {
+            let (address, handle) = serve(move || async move { response });
+            let mut solutions = solver(address, None).solve(auction::Auction::empty()).await;
+            handle.abort();
+            assert_eq!(solutions.len(), 1);
+            solutions.remove(0).score
+        };
+
+        let score = solve(r#"{"orders": {}, "prices": {}, "score": "100"}"#).await;
+        assert!(matches!(score, solution::Score::Solver(score) if score == U256::from(100)));
+
+        // Without a score the protocol computes it.
+        let score = solve(r#"{"orders": {}, "prices": {}}"#).await;
+        assert!(matches!(score, solution::Score::RiskAdjusted(probability) if probability == 1.));
+    }
+}
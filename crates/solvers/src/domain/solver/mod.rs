@@ -1,11 +1,32 @@
-use crate::domain::{auction, solution};
+use {
+    crate::domain::{auction, solution},
+    futures::future,
+    prometheus::{HistogramVec, IntCounterVec},
+    prometheus_metric_storage::StorageRegistry,
+    std::{
+        future::Future,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    },
+    tokio_util::sync::CancellationToken,
+};
 
 pub mod baseline;
 pub mod dex;
 pub mod legacy;
 pub mod naive;
+pub mod score;
 
-pub use self::{baseline::Baseline, dex::Dex, legacy::Legacy, naive::Naive};
+pub use self::{
+    baseline::Baseline,
+    dex::Dex,
+    legacy::Legacy,
+    naive::Naive,
+    score::{ProtocolScorePolicy, ScorePolicy},
+};
 
 pub enum Solver {
     Baseline(Baseline),
@@ -17,13 +38,441 @@ pub enum Solver {
 impl Solver {
     /// Solves a given auction and returns multiple solutions. We allow
     /// returning multiple solutions to later merge multiple non-overlapping
-    /// solutions to get one big more gas efficient solution.
+    /// solutions to get one big more gas efficient solution (see
+    /// [`solution::merge`]).
     pub async fn solve(&self, auction: auction::Auction) -> Vec<solution::Solution> {
+        self.solve_with_deadline(auction, far_future()).await
+    }
+
+    /// Like [`Solver::solve`] but additionally returns statistics about
+    /// solving the auction.
+    pub async fn solve_with_stats(
+        &self,
+        auction: auction::Auction,
+    ) -> (Vec<solution::Solution>, SolveStats) {
+        let start = Instant::now();
+        let solutions = self.solve(auction).await;
+        let stats = SolveStats {
+            solver: self.name(),
+            solutions: solutions.len(),
+            duration: start.elapsed(),
+        };
+        (solutions, stats)
+    }
+
+    /// Like [`Solver::solve`] but stops solving once the specified `deadline`
+    /// is reached. Solutions that weren't found by then get discarded.
+    pub async fn solve_with_deadline(
+        &self,
+        auction: auction::Auction,
+        deadline: Instant,
+    ) -> Vec<solution::Solution> {
+        self.solve_until(auction, deadline, CancellationToken::new())
+            .await
+    }
+
+    /// Like [`Solver::solve`] but stops solving once `token` gets cancelled,
+    /// e.g. because the auction got superseded by a newer one. In-flight
+    /// requests to external APIs get aborted. Solvers that find solutions
+    /// incrementally return the ones found so far, the others none.
+    pub async fn solve_cancellable(
+        &self,
+        auction: auction::Auction,
+        token: CancellationToken,
+    ) -> Vec<solution::Solution> {
+        self.solve_until(auction, far_future(), token).await
+    }
+
+    async fn solve_until(
+        &self,
+        auction: auction::Auction,
+        deadline: Instant,
+        token: CancellationToken,
+    ) -> Vec<solution::Solution> {
+        let name = self.name();
+        let auction_id = auction.id.as_ref().map(|id| id.0);
+        let num_orders = auction.orders.len();
+        let start = Instant::now();
+        let solutions = match self {
+            Solver::Baseline(solver) => {
+                with_deadline(name, solver.solve(auction), deadline, &token).await
+            }
+            // The naive solver handles cancellation itself so that it can keep
+            // the solutions it already found.
+            Solver::Naive(solver) => {
+                let solve = solver.solve_cancellable(auction, token);
+                with_deadline(name, solve, deadline, &CancellationToken::new()).await
+            }
+            // Dropping the future on cancellation aborts the request to the
+            // legacy solver.
+            Solver::Legacy(solver) => {
+                with_deadline(name, solver.solve(auction), deadline, &token).await
+            }
+            // The DEX solver handles the deadline and cancellation itself so
+            // that it can keep the solutions it already found.
+            Solver::Dex(solver) => solver.solve_cancellable(auction, deadline, token).await,
+        };
+        // Uses a dedicated target so that the summary can be enabled
+        // independently of the other logs.
+        tracing::info!(
+            target: "solvers::solve_summary",
+            solver = name,
+            ?auction_id,
+            num_orders,
+            num_solutions = solutions.len(),
+            duration_ms = start.elapsed().as_millis() as u64,
+            "auction solved"
+        );
+        solutions
+    }
+
+    /// Like [`Solver::solve_with_deadline`] but the solutions get scored by
+    /// `policy` instead of keeping the scores the solver assigned.
+    pub async fn solve_with_policy(
+        &self,
+        auction: auction::Auction,
+        deadline: Instant,
+        policy: &dyn ScorePolicy,
+    ) -> Vec<solution::Solution> {
+        let solutions = self.solve_with_deadline(auction.clone(), deadline).await;
+        solutions
+            .into_iter()
+            .map(|solution| {
+                let score = policy.score(&solution, &auction);
+                solution.with_score(score)
+            })
+            .collect()
+    }
+
+    /// A stable name identifying the kind of solver. It is used in logs and
+    /// as the `solver` label of metrics.
+    pub fn name(&self) -> &'static str {
         match self {
-            Solver::Baseline(solver) => solver.solve(auction).await,
-            Solver::Naive(solver) => solver.solve(auction).await,
-            Solver::Legacy(solver) => solver.solve(auction).await,
-            Solver::Dex(solver) => solver.solve(auction).await,
+            Solver::Baseline(_) => "baseline",
+            Solver::Naive(_) => "naive",
+            Solver::Legacy(_) => "legacy",
+            Solver::Dex(_) => "dex",
+        }
+    }
+}
+
+/// Multiple solvers that solve the same auction concurrently, e.g. to compare
+/// their solutions.
+pub struct Composite {
+    solvers: Vec<Arc<Solver>>,
+}
+
+impl Composite {
+    pub fn new(solvers: Vec<Solver>) -> Self {
+        Self {
+            solvers: solvers.into_iter().map(Arc::new).collect(),
+        }
+    }
+
+    /// Solves the auction with every solver and returns their solutions
+    /// labelled by [`Solver::name`], in the order the solvers were specified.
+    ///
+    /// Every solver runs in its own task so that one of them panicking doesn't
+    /// affect the others. A solver that panicked reports no solutions.
+    pub async fn solve_all(
+        &self,
+        auction: auction::Auction,
+    ) -> Vec<(String, Vec<solution::Solution>)> {
+        let tasks = self.solvers.iter().map(|solver| {
+            let solver = solver.clone();
+            let auction = auction.clone();
+            async move {
+                let name = solver.name();
+                let solutions = tokio::spawn(async move { solver.solve(auction).await })
+                    .await
+                    .unwrap_or_else(|err| {
+                        tracing::error!(solver = name, ?err, "solver failed");
+                        Vec::new()
+                    });
+                (name.to_owned(), solutions)
+            }
+        });
+        future::join_all(tasks).await
+    }
+}
+
+/// Statistics about solving a single auction.
+#[derive(Clone, Debug)]
+pub struct SolveStats {
+    /// The name of the solver, see [`Solver::name`].
+    pub solver: &'static str,
+    /// The number of solutions found.
+    pub solutions: usize,
+    /// The wall-clock time it took to solve the auction.
+    pub duration: Duration,
+}
+
+async fn with_deadline(
+    name: &'static str,
+    solve: impl Future<Output = Vec<solution::Solution>>,
+    deadline: Instant,
+    token: &CancellationToken,
+) -> Vec<solution::Solution> {
+    tokio::select! {
+        solutions = solve => solutions,
+        _ = tokio::time::sleep_until(deadline.into()) => {
+            tracing::debug!(solver = name, "reached deadline; discarding solutions");
+            Vec::new()
+        }
+        _ = token.cancelled() => {
+            tracing::debug!(solver = name, "cancelled; discarding solutions");
+            Vec::new()
+        }
+    }
+}
+
+/// A deadline that is far enough in the future to never be reached.
+fn far_future() -> Instant {
+    // Roughly 30 years, adding [`Duration::MAX`] would overflow.
+    Instant::now() + Duration::from_secs(86400 * 365 * 30)
+}
+
+/// A solver that records how long solving takes and how many solutions get
+/// found.
+///
+/// The solver can be disabled at runtime, e.g. during an incident with an
+/// external API it depends on. Disabled solvers don't find any solutions.
+pub struct Instrumented {
+    inner: Solver,
+    metrics: &'static Metrics,
+    enabled: AtomicBool,
+    /// Keeps the scores of the wrapped solver if `None`.
+    score_policy: Option<Box<dyn ScorePolicy>>,
+}
+
+impl Instrumented {
+    /// Wraps a solver, registering its metrics in the specified registry. The
+    /// solver starts out enabled.
+    pub fn new(inner: Solver, registry: &StorageRegistry) -> Self {
+        let metrics = Metrics::instance(registry).unwrap();
+        Self {
+            inner,
+            metrics,
+            enabled: AtomicBool::new(true),
+            score_policy: None,
+        }
+    }
+
+    /// Scores the solutions of the wrapped solver with `policy`. By default
+    /// the scores the solver assigned are kept, like with the
+    /// [`ProtocolScorePolicy`].
+    pub fn with_score_policy(mut self, policy: impl ScorePolicy + 'static) -> Self {
+        self.score_policy = Some(Box::new(policy));
+        self
+    }
+
+    /// Enables or disables the wrapped solver.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Solves a given auction with the wrapped solver. See [`Solver::solve`].
+    pub async fn solve(&self, auction: auction::Auction) -> Vec<solution::Solution> {
+        self.solve_with_deadline(auction, far_future()).await
+    }
+
+    /// Solves a given auction with the wrapped solver. See
+    /// [`Solver::solve_with_deadline`].
+    pub async fn solve_with_deadline(
+        &self,
+        auction: auction::Auction,
+        deadline: Instant,
+    ) -> Vec<solution::Solution> {
+        let name = self.inner.name();
+        if !self.is_enabled() {
+            tracing::info!(solver = name, "solver disabled; skipping auction");
+            return Vec::new();
         }
+        tracing::debug!(solver = name, "solving auction");
+        let start = Instant::now();
+        let solutions = match &self.score_policy {
+            Some(policy) => {
+                self.inner
+                    .solve_with_policy(auction, deadline, policy.as_ref())
+                    .await
+            }
+            None => self.inner.solve_with_deadline(auction, deadline).await,
+        };
+        let elapsed = start.elapsed();
+        tracing::debug!(
+            solver = name,
+            solutions = solutions.len(),
+            ?elapsed,
+            "finished solving auction"
+        );
+        self.metrics
+            .solve_duration_seconds
+            .with_label_values(&[name])
+            .observe(elapsed.as_secs_f64());
+        self.metrics
+            .solutions
+            .with_label_values(&[name])
+            .inc_by(solutions.len() as u64);
+        solutions
+    }
+}
+
+#[derive(prometheus_metric_storage::MetricStorage)]
+#[metric(subsystem = "solver")]
+struct Metrics {
+    /// Time it took to solve an auction.
+    #[metric(labels("solver"))]
+    solve_duration_seconds: HistogramVec,
+
+    /// Number of solutions returned.
+    #[metric(labels("solver"))]
+    solutions: IntCounterVec,
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            domain::{dex::slippage, eth},
+            infra::{self, config},
+        },
+        bigdecimal::BigDecimal,
+        ethereum_types::{H160, U256},
+        num::Zero,
+        std::num::NonZeroUsize,
+    };
+
+    #[tokio::test]
+    async fn records_solve_metrics() {
+        let registry = StorageRegistry::default();
+        let solver = Instrumented::new(Solver::Naive(Naive), &registry);
+
+        let solutions = solver.solve(auction::Auction::empty()).await;
+        assert!(solutions.is_empty());
+
+        let families = registry.registry().gather();
+        let duration = families
+            .iter()
+            .find(|family| family.get_name() == "solver_solve_duration_seconds")
+            .unwrap();
+        let metric = &duration.get_metric()[0];
+        assert_eq!(metric.get_label()[0].get_value(), "naive");
+        assert_eq!(metric.get_histogram().get_sample_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn disabled_solver_skips_auctions() {
+        let registry = StorageRegistry::default();
+        let solver = Instrumented::new(Solver::Naive(Naive), &registry);
+        let solves = || {
+            registry
+                .registry()
+                .gather()
+                .iter()
+                .find(|family| family.get_name() == "solver_solve_duration_seconds")
+                .map_or(0, |family| {
+                    family.get_metric()[0].get_histogram().get_sample_count()
+                })
+        };
+
+        solver.set_enabled(false);
+        assert!(solver.solve(auction::Auction::empty()).await.is_empty());
+        assert_eq!(solves(), 0);
+
+        solver.set_enabled(true);
+        solver.solve(auction::Auction::empty()).await;
+        assert_eq!(solves(), 1);
+    }
+
+    #[tokio::test]
+    async fn solve_stats_count_solutions() {
+        let (solutions, stats) = Solver::Naive(Naive)
+            .solve_with_stats(auction::Auction::empty())
+            .await;
+
+        assert_eq!(stats.solver, "naive");
+        assert_eq!(stats.solutions, solutions.len());
+    }
+
+    #[tokio::test]
+    async fn cancelled_naive_solver_returns_no_solutions() {
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let solutions = Solver::Naive(Naive)
+            .solve_cancellable(auction::Auction::empty(), token)
+            .await;
+
+        assert!(solutions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn composite_labels_solutions_by_solver() {
+        let composite = Composite::new(vec![
+            Solver::Naive(Naive),
+            Solver::Baseline(Baseline::new(config::baseline::Config {
+                weth: eth::WethAddress(H160::zero()),
+                base_tokens: Vec::new(),
+                max_hops: 0,
+                max_partial_attempts: 0,
+            })),
+        ]);
+
+        let results = composite.solve_all(auction::Auction::empty()).await;
+
+        assert_eq!(
+            results
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            ["naive", "baseline"],
+        );
+    }
+
+    #[test]
+    fn names() {
+        let weth = eth::WethAddress(H160::zero());
+        let endpoint = reqwest::Url::parse("http://localhost").unwrap();
+        let solvers = [
+            Solver::Baseline(Baseline::new(config::baseline::Config {
+                weth,
+                base_tokens: Vec::new(),
+                max_hops: 0,
+                max_partial_attempts: 0,
+            })),
+            Solver::Naive(Naive),
+            Solver::Legacy(Legacy::new(legacy::Config {
+                weth,
+                solver_name: "legacy".to_owned(),
+                chain_id: eth::ChainId::Mainnet,
+                endpoint: endpoint.clone(),
+                timeout: None,
+            })),
+            Solver::Dex(Dex::new(
+                infra::dex::Dex::ParaSwap(infra::dex::paraswap::ParaSwap::new(
+                    infra::dex::paraswap::Config {
+                        endpoint,
+                        exclude_dexs: Vec::new(),
+                        address: H160::zero(),
+                        partner: String::new(),
+                    },
+                )),
+                config::dex::Config {
+                    slippage: slippage::Limits::new(BigDecimal::zero(), None).unwrap(),
+                    concurrent_requests: NonZeroUsize::new(1).unwrap(),
+                    smallest_partial_fill: eth::Ether(U256::zero()),
+                },
+            )),
+        ];
+
+        assert_eq!(
+            solvers.iter().map(Solver::name).collect::<Vec<_>>(),
+            ["baseline", "naive", "legacy", "dex"],
+        );
     }
 }
@@ -4,13 +4,19 @@
 //! pair, computing how many leftover tokens can't be matched peer-to-peer, and
 //! matching that excess over a Uniswap V2 pool. This allows for naive
 //! coincidence of wants over a single Uniswap V2 pools.
+//!
+//! If no solution executing all orders can be found, partially fillable orders
+//! are matched peer-to-peer at the pool's spot price instead.
 
 use {
     crate::{
         boundary,
-        domain::{auction, liquidity, order, solution},
+        domain::{auction, eth, liquidity, order, solution},
     },
+    ethereum_types::U256,
+    itertools::Itertools,
     std::collections::HashMap,
+    tokio_util::sync::CancellationToken,
 };
 
 pub struct Naive;
@@ -19,6 +25,20 @@ impl Naive {
     /// Solves the specified auction, returning a vector of all possible
     /// solutions.
     pub async fn solve(&self, auction: auction::Auction) -> Vec<solution::Solution> {
+        self.solve_cancellable(auction, CancellationToken::new())
+            .await
+    }
+
+    /// Like [`Naive::solve`] but stops solving once `token` gets cancelled,
+    /// returning the solutions for the token pairs solved so far.
+    pub async fn solve_cancellable(
+        &self,
+        auction: auction::Auction,
+        token: CancellationToken,
+    ) -> Vec<solution::Solution> {
+        if token.is_cancelled() {
+            return Vec::new();
+        }
         // Make sure to push the CPU-heavy code to a separate thread in order to
         // not lock up the [`tokio`] runtime and cause it to slow down handling
         // the real async things.
@@ -26,7 +46,11 @@ impl Naive {
             let groups = group_by_token_pair(&auction);
             groups
                 .values()
-                .filter_map(|group| boundary::naive::solve(&group.orders, group.liquidity))
+                .take_while(|_| !token.is_cancelled())
+                .filter_map(|group| {
+                    boundary::naive::solve(&group.orders, group.liquidity)
+                        .or_else(|| match_partially(group))
+                })
                 .collect()
         })
         .await
@@ -91,3 +115,111 @@ fn group_by_token_pair(auction: &auction::Auction) -> Groups {
     groups.retain(|_, group| !group.orders.is_empty());
     groups
 }
+
+/// Matches the orders of a group peer-to-peer at the pool's spot price without
+/// using the pool's liquidity. Orders on the side with more volume get
+/// partially filled in order to balance out the volume on the other side.
+///
+/// Returns `None` if the group doesn't contain any partially fillable orders
+/// or if no orders can be matched.
+fn match_partially(group: &Group) -> Option<solution::Solution> {
+    if !group.orders.iter().any(|order| order.partially_fillable) {
+        return None;
+    }
+
+    // Same as for the clearing prices that are computed by the boundary solver
+    // when no pool swap is needed, the price of a token is the reserve of the
+    // other token. This means that the prices reflect the pool's spot price.
+    let (reserve0, reserve1) = group.pool.reserves.get();
+    let prices = HashMap::from([
+        (reserve0.token, reserve1.amount),
+        (reserve1.token, reserve0.amount),
+    ]);
+    let value = |asset: &eth::Asset| asset.amount.checked_mul(*prices.get(&asset.token)?);
+
+    let (side0, side1): (Vec<_>, Vec<_>) = group
+        .orders
+        .iter()
+        .copied()
+        // Like the boundary solver we don't compute fees for orders.
+        .filter(|order| !order.solver_determines_fee())
+        .filter_map(|order| {
+            // Only consider orders whose limit price is satisfied by the spot
+            // price.
+            if value(&order.sell)? < value(&order.buy)? {
+                return None;
+            }
+            let volume = match order.side {
+                order::Side::Buy => value(&order.buy)?,
+                order::Side::Sell => value(&order.sell)?,
+            };
+            Some((order, volume))
+        })
+        .partition(|(order, _)| order.sell.token == reserve0.token);
+
+    // Partially filling orders on one side might not be enough to match the
+    // volume of the other side if fill-or-kill orders don't fit. So we reduce
+    // the volume until both sides match.
+    let total = |fills: &[(&order::Order, U256)]| {
+        fills.iter().fold(U256::zero(), |total, (_, volume)| {
+            total.saturating_add(*volume)
+        })
+    };
+    let mut volume = total(&side0).min(total(&side1));
+    let (fills0, fills1) = loop {
+        let fills0 = fill(&side0, volume);
+        let fills1 = fill(&side1, total(&fills0));
+        if total(&fills0) == total(&fills1) {
+            break (fills0, fills1);
+        }
+        volume = total(&fills1);
+    };
+    if total(&fills0).is_zero() {
+        return None;
+    }
+
+    let trades = fills0
+        .into_iter()
+        .chain(fills1)
+        .map(|(order, volume)| {
+            let executed = match order.side {
+                order::Side::Buy => volume / prices[&order.buy.token],
+                order::Side::Sell => volume / prices[&order.sell.token],
+            };
+            solution::Fulfillment::new(order.clone(), executed, solution::Fee::Protocol)
+                .map(solution::Trade::Fulfillment)
+        })
+        .collect::<Option<_>>()?;
+
+    Some(solution::Solution {
+        prices: solution::ClearingPrices::new(prices),
+        trades,
+        interactions: Vec::new(),
+        score: Default::default(),
+    })
+}
+
+/// Fills orders with up to the specified total volume. Fill-or-kill orders are
+/// either filled completely or not at all, partially fillable orders are used
+/// to fill the remaining volume.
+fn fill<'a>(orders: &[(&'a order::Order, U256)], volume: U256) -> Vec<(&'a order::Order, U256)> {
+    let mut remaining = volume;
+    orders
+        .iter()
+        .sorted_by_key(|(order, _)| order.partially_fillable)
+        .filter_map(|(order, volume)| {
+            let fill = if order.partially_fillable {
+                (*volume).min(remaining)
+            } else if *volume <= remaining {
+                *volume
+            } else {
+                return None;
+            };
+            if fill.is_zero() {
+                return None;
+            }
+            remaining -= fill;
+            Some((*order, fill))
+        })
+        .collect()
+}
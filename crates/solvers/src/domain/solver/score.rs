@@ -0,0 +1,53 @@
+//! Policies for scoring the solutions solvers found.
+
+use crate::domain::{auction, solution};
+
+/// Decides the score of a solution, e.g. to try out different ways of
+/// accounting for the risk of a solution reverting without changing the
+/// solvers themselves.
+pub trait ScorePolicy: Send + Sync {
+    fn score(&self, solution: &solution::Solution, auction: &auction::Auction) -> solution::Score;
+}
+
+/// Keeps the score a solver assigned to its solution. Solutions without a
+/// score of their own get scored by the protocol (see
+/// [`solution::Score::RiskAdjusted`]).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProtocolScorePolicy;
+
+impl ScorePolicy for ProtocolScorePolicy {
+    fn score(&self, solution: &solution::Solution, _: &auction::Auction) -> solution::Score {
+        solution.score.clone()
+    }
+}
+
+/// Lets the protocol score all solutions assuming they succeed with a fixed
+/// probability, ignoring scores computed by the solver.
+#[derive(Clone, Copy, Debug)]
+pub struct SuccessProbabilityScorePolicy(pub f64);
+
+impl ScorePolicy for SuccessProbabilityScorePolicy {
+    fn score(&self, _: &solution::Solution, _: &auction::Auction) -> solution::Score {
+        solution::Score::RiskAdjusted(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, ethereum_types::U256};
+
+    #[test]
+    fn policies_score_the_same_solution_differently() {
+        let auction = auction::Auction::empty();
+        let solution = solution::Solution::default().with_score(solution::Score::Solver(42.into()));
+
+        assert!(matches!(
+            ProtocolScorePolicy.score(&solution, &auction),
+            solution::Score::Solver(score) if score == U256::from(42)
+        ));
+        assert!(matches!(
+            SuccessProbabilityScorePolicy(0.5).score(&solution, &auction),
+            solution::Score::RiskAdjusted(probability) if probability == 0.5
+        ));
+    }
+}
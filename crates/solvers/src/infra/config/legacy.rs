@@ -6,8 +6,8 @@ use {
     },
     reqwest::Url,
     serde::Deserialize,
-    serde_with::serde_as,
-    std::path::Path,
+    serde_with::{serde_as, DurationMilliSeconds},
+    std::{path::Path, time::Duration},
     tokio::fs,
 };
 
@@ -25,6 +25,11 @@ struct Config {
 
     /// The URL of the endpoint that responds to solve requests.
     endpoint: String,
+
+    /// How many milliseconds to wait for the endpoint to respond before giving
+    /// up on the auction. Waits until the endpoint responds if unset.
+    #[serde_as(as = "Option<DurationMilliSeconds<u64>>")]
+    timeout_ms: Option<Duration>,
 }
 
 /// Load the driver configuration from a TOML file.
@@ -44,5 +49,6 @@ pub async fn load(path: &Path) -> legacy::Config {
         solver_name: config.solver_name,
         chain_id: config.chain_id,
         endpoint: Url::parse(&config.endpoint).unwrap(),
+        timeout: config.timeout_ms,
     }
 }
@@ -25,7 +25,7 @@ pub async fn run(
 }
 
 async fn run_with(args: cli::Args, bind: Option<oneshot::Sender<SocketAddr>>) {
-    observe::tracing::initialize_reentrant(&args.log);
+    observe::tracing::initialize_reentrant(&observe::tracing::Config::new(&args.log));
     tracing::info!("running solver engine with {args:#?}");
 
     let solver = match args.command {
@@ -72,7 +72,7 @@ async fn run_with(args: cli::Args, bind: Option<oneshot::Sender<SocketAddr>>) {
 
     crate::api::Api {
         addr: args.addr,
-        solver,
+        solver: solver::Instrumented::new(solver, observe::metrics::get_storage_registry()),
     }
     .serve(bind, shutdown_signal())
     .await
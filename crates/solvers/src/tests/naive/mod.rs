@@ -2,6 +2,7 @@ mod extract_deepest_pool;
 mod filters_out_of_price_orders;
 mod limit_order_price;
 mod matches_orders;
+mod partial_fill;
 mod reserves_too_small;
 mod rounds_prices_in_favour_of_traders;
 mod swap_less_than_reserves;
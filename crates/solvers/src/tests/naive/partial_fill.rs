@@ -0,0 +1,254 @@
+//! Tests that partially fillable orders get matched peer-to-peer at the pool's
+//! spot price when the pool is too shallow to cover the excess of all orders.
+
+use {crate::tests, serde_json::json};
+
+#[tokio::test]
+async fn partially_fills_order_against_fill_or_kill_order() {
+    let engine = tests::SolverEngine::new("naive", tests::Config::None).await;
+
+    let solution = engine
+        .solve(json!({
+            "id": "1",
+            "tokens": {},
+            "orders": [
+                {
+                    "uid": "0x0101010101010101010101010101010101010101010101010101010101010101\
+                              0101010101010101010101010101010101010101\
+                              01010101",
+                    "sellToken": "0x000000000000000000000000000000000000000a",
+                    "buyToken": "0x000000000000000000000000000000000000000b",
+                    "sellAmount": "100000000000000000000",
+                    "buyAmount": "95000000000000000000",
+                    "feeAmount": "0",
+                    "kind": "sell",
+                    "partiallyFillable": true,
+                    "class": "market",
+                },
+                {
+                    "uid": "0x0202020202020202020202020202020202020202020202020202020202020202\
+                              0202020202020202020202020202020202020202\
+                              02020202",
+                    "sellToken": "0x000000000000000000000000000000000000000b",
+                    "buyToken": "0x000000000000000000000000000000000000000a",
+                    "sellAmount": "50000000000000000000",
+                    "buyAmount": "45000000000000000000",
+                    "feeAmount": "0",
+                    "kind": "sell",
+                    "partiallyFillable": false,
+                    "class": "market",
+                },
+            ],
+            "liquidity": [
+                {
+                    "kind": "constantproduct",
+                    "tokens": {
+                        "0x000000000000000000000000000000000000000a": {
+                            "balance": "100000000000000000000"
+                        },
+                        "0x000000000000000000000000000000000000000b": {
+                            "balance": "100000000000000000000"
+                        }
+                    },
+                    "fee": "0.003",
+                    "id": "0",
+                    "address": "0xffffffffffffffffffffffffffffffffffffffff",
+                    "gasEstimate": "110000"
+                },
+            ],
+            "effectiveGasPrice": "15000000000",
+            "deadline": "2106-01-01T00:00:00.000Z",
+        }))
+        .await;
+
+    assert_eq!(
+        solution,
+        json!({
+            "solutions": [{
+                "id": 0,
+                "prices": {
+                    "0x000000000000000000000000000000000000000a": "100000000000000000000",
+                    "0x000000000000000000000000000000000000000b": "100000000000000000000",
+                },
+                "trades": [
+                    {
+                        "kind": "fulfillment",
+                        "order": "0x0101010101010101010101010101010101010101010101010101010101010101\
+                                    0101010101010101010101010101010101010101\
+                                    01010101",
+                        "executedAmount": "50000000000000000000",
+                    },
+                    {
+                        "kind": "fulfillment",
+                        "order": "0x0202020202020202020202020202020202020202020202020202020202020202\
+                                    0202020202020202020202020202020202020202\
+                                    02020202",
+                        "executedAmount": "50000000000000000000",
+                    },
+                ],
+                "interactions": [],
+                "score": {
+                    "riskadjusted": 1.0
+                }
+            }]
+        }),
+    );
+}
+
+#[tokio::test]
+async fn partially_fillable_orders_settle_for_smaller_amount() {
+    let engine = tests::SolverEngine::new("naive", tests::Config::None).await;
+
+    let solution = engine
+        .solve(json!({
+            "id": "1",
+            "tokens": {},
+            "orders": [
+                {
+                    "uid": "0x0101010101010101010101010101010101010101010101010101010101010101\
+                              0101010101010101010101010101010101010101\
+                              01010101",
+                    "sellToken": "0x000000000000000000000000000000000000000a",
+                    "buyToken": "0x000000000000000000000000000000000000000b",
+                    "sellAmount": "100000000000000000000",
+                    "buyAmount": "95000000000000000000",
+                    "feeAmount": "0",
+                    "kind": "sell",
+                    "partiallyFillable": true,
+                    "class": "market",
+                },
+                {
+                    "uid": "0x0202020202020202020202020202020202020202020202020202020202020202\
+                              0202020202020202020202020202020202020202\
+                              02020202",
+                    "sellToken": "0x000000000000000000000000000000000000000b",
+                    "buyToken": "0x000000000000000000000000000000000000000a",
+                    "sellAmount": "60000000000000000000",
+                    "buyAmount": "55000000000000000000",
+                    "feeAmount": "0",
+                    "kind": "sell",
+                    "partiallyFillable": true,
+                    "class": "market",
+                },
+            ],
+            "liquidity": [
+                {
+                    "kind": "constantproduct",
+                    "tokens": {
+                        "0x000000000000000000000000000000000000000a": {
+                            "balance": "100000000000000000000"
+                        },
+                        "0x000000000000000000000000000000000000000b": {
+                            "balance": "100000000000000000000"
+                        }
+                    },
+                    "fee": "0.003",
+                    "id": "0",
+                    "address": "0xffffffffffffffffffffffffffffffffffffffff",
+                    "gasEstimate": "110000"
+                },
+            ],
+            "effectiveGasPrice": "15000000000",
+            "deadline": "2106-01-01T00:00:00.000Z",
+        }))
+        .await;
+
+    assert_eq!(
+        solution,
+        json!({
+            "solutions": [{
+                "id": 0,
+                "prices": {
+                    "0x000000000000000000000000000000000000000a": "100000000000000000000",
+                    "0x000000000000000000000000000000000000000b": "100000000000000000000",
+                },
+                "trades": [
+                    {
+                        "kind": "fulfillment",
+                        "order": "0x0101010101010101010101010101010101010101010101010101010101010101\
+                                    0101010101010101010101010101010101010101\
+                                    01010101",
+                        "executedAmount": "60000000000000000000",
+                    },
+                    {
+                        "kind": "fulfillment",
+                        "order": "0x0202020202020202020202020202020202020202020202020202020202020202\
+                                    0202020202020202020202020202020202020202\
+                                    02020202",
+                        "executedAmount": "60000000000000000000",
+                    },
+                ],
+                "interactions": [],
+                "score": {
+                    "riskadjusted": 1.0
+                }
+            }]
+        }),
+    );
+}
+
+#[tokio::test]
+async fn does_not_match_when_fill_or_kill_order_does_not_fit() {
+    let engine = tests::SolverEngine::new("naive", tests::Config::None).await;
+
+    let solution = engine
+        .solve(json!({
+            "id": "1",
+            "tokens": {},
+            "orders": [
+                {
+                    "uid": "0x0101010101010101010101010101010101010101010101010101010101010101\
+                              0101010101010101010101010101010101010101\
+                              01010101",
+                    "sellToken": "0x000000000000000000000000000000000000000a",
+                    "buyToken": "0x000000000000000000000000000000000000000b",
+                    "sellAmount": "100000000000000000000",
+                    "buyAmount": "95000000000000000000",
+                    "feeAmount": "0",
+                    "kind": "sell",
+                    "partiallyFillable": false,
+                    "class": "market",
+                },
+                {
+                    "uid": "0x0202020202020202020202020202020202020202020202020202020202020202\
+                              0202020202020202020202020202020202020202\
+                              02020202",
+                    "sellToken": "0x000000000000000000000000000000000000000b",
+                    "buyToken": "0x000000000000000000000000000000000000000a",
+                    "sellAmount": "50000000000000000000",
+                    "buyAmount": "45000000000000000000",
+                    "feeAmount": "0",
+                    "kind": "sell",
+                    "partiallyFillable": true,
+                    "class": "market",
+                },
+            ],
+            "liquidity": [
+                {
+                    "kind": "constantproduct",
+                    "tokens": {
+                        "0x000000000000000000000000000000000000000a": {
+                            "balance": "100000000000000000000"
+                        },
+                        "0x000000000000000000000000000000000000000b": {
+                            "balance": "100000000000000000000"
+                        }
+                    },
+                    "fee": "0.003",
+                    "id": "0",
+                    "address": "0xffffffffffffffffffffffffffffffffffffffff",
+                    "gasEstimate": "110000"
+                },
+            ],
+            "effectiveGasPrice": "15000000000",
+            "deadline": "2106-01-01T00:00:00.000Z",
+        }))
+        .await;
+
+    assert_eq!(
+        solution,
+        json!({
+            "solutions": []
+        }),
+    );
+}
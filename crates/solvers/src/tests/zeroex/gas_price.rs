@@ -0,0 +1,74 @@
+//! This test ensures that the 0x solver requests swaps priced at the gas price
+//! of the auction rather than at a gas price estimated by the API.
+
+use {
+    crate::tests::{self, mock, zeroex},
+    serde_json::json,
+};
+
+#[tokio::test]
+async fn test() {
+    let api = mock::http::setup(vec![mock::http::Expectation::Get {
+        path: mock::http::Path::exact(
+            "swap/v1/quote\
+             ?sellToken=0x1111111111111111111111111111111111111111\
+             &buyToken=0x2222222222222222222222222222222222222222\
+             &sellAmount=1000000000000000000\
+             &slippagePercentage=0.01\
+             &gasPrice=250000000000\
+             &takerAddress=0x9008d19f58aabd9ed0d60971565aa8510560ab41\
+             &skipValidation=true\
+             &intentOnFilling=false\
+             &affiliateAddress=0x9008d19f58aabd9ed0d60971565aa8510560ab41\
+             &enableSlippageProtection=false",
+        ),
+        res: json!({
+            "code": 100,
+            "reason": "Validation Failed",
+            "validationErrors": [
+                {
+                    "field": "buyAmount",
+                    "code": 1004,
+                    "reason": "INSUFFICIENT_ASSET_LIQUIDITY",
+                    "description": "We are not able to fulfill an order for this token pair \
+                                    at the requested amount due to a lack of liquidity",
+                },
+            ],
+        }),
+    }])
+    .await;
+
+    let engine = tests::SolverEngine::new("zeroex", zeroex::config(&api.address)).await;
+
+    let solution = engine
+        .solve(json!({
+            "id": "1",
+            "tokens": {},
+            "orders": [
+                {
+                    "uid": "0x2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a\
+                              2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a\
+                              2a2a2a2a",
+                    "sellToken": "0x1111111111111111111111111111111111111111",
+                    "buyToken": "0x2222222222222222222222222222222222222222",
+                    "sellAmount": "1000000000000000000",
+                    "buyAmount": "1000000000000000000",
+                    "feeAmount": "1000000000000000",
+                    "kind": "sell",
+                    "partiallyFillable": false,
+                    "class": "market",
+                },
+            ],
+            "liquidity": [],
+            "effectiveGasPrice": "250000000000",
+            "deadline": "2106-01-01T00:00:00.000Z",
+        }))
+        .await;
+
+    assert_eq!(
+        solution,
+        json!({
+            "solutions": []
+        }),
+    );
+}
@@ -1,5 +1,6 @@
 use {crate::tests, std::net::SocketAddr};
 
+mod gas_price;
 mod market_order;
 mod not_found;
 mod options;